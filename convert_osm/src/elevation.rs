@@ -0,0 +1,240 @@
+use abstutil::Timer;
+use geom::{Distance, LonLat};
+use map_model::raw::{OriginalIntersection, RawMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+
+// SRTM tiles mark missing samples with this.
+const HGT_VOID: i16 = -32768;
+
+// Sample a digital elevation model at every intersection. The input is either an SRTM .hgt tile or
+// anything GDAL can read, like a GeoTIFF.
+pub fn add_elevation(map: &mut RawMap, path: &str, timer: &mut Timer) {
+    timer.start("sample elevation data");
+    let mut ids: Vec<OriginalIntersection> = Vec::new();
+    let mut pts: Vec<LonLat> = Vec::new();
+    for (id, i) in &map.intersections {
+        if let Some(pt) = i.point.to_gps(&map.gps_bounds) {
+            ids.push(*id);
+            pts.push(pt);
+        }
+    }
+
+    let heights = if path.ends_with(".hgt") {
+        HgtTile::load(path).map(|tile| pts.iter().map(|pt| tile.sample(*pt)).collect())
+    } else {
+        sample_with_gdal(path, &pts)
+    }
+    .expect(&format!("couldn't sample elevation from {}", path));
+
+    let mut missing = 0;
+    for (id, height) in ids.into_iter().zip(heights) {
+        if let Some(h) = height {
+            map.intersections.get_mut(&id).unwrap().elevation = Distance::meters(h);
+        } else {
+            missing += 1;
+        }
+    }
+    if missing > 0 {
+        timer.warn(format!(
+            "{} intersections aren't covered by {}; leaving them at 0m",
+            missing, path
+        ));
+    }
+    timer.stop("sample elevation data");
+}
+
+// One 1x1 degree tile, named after its southwest corner, like N47W123.hgt. Samples are big-endian
+// meters, starting from the northwest corner.
+struct HgtTile {
+    south: f64,
+    west: f64,
+    // Number of samples per row and column
+    size: usize,
+    samples: Vec<i16>,
+}
+
+impl HgtTile {
+    fn load(path: &str) -> Result<HgtTile, Error> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        HgtTile::parse(path, bytes)
+    }
+
+    fn parse(path: &str, bytes: Vec<u8>) -> Result<HgtTile, Error> {
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_uppercase();
+        let bad_name = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} isn't named like N47W123.hgt", path),
+            )
+        };
+        if name.len() != 7 {
+            return Err(bad_name());
+        }
+        let lat: f64 = name[1..3].parse().map_err(|_| bad_name())?;
+        let lon: f64 = name[4..7].parse().map_err(|_| bad_name())?;
+        let south = match &name[0..1] {
+            "N" => lat,
+            "S" => -lat,
+            _ => return Err(bad_name()),
+        };
+        let west = match &name[3..4] {
+            "E" => lon,
+            "W" => -lon,
+            _ => return Err(bad_name()),
+        };
+
+        // 1201 for 3 arc-second data, 3601 for 1 arc-second
+        let size = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} isn't a square tile", path),
+            ));
+        }
+        let samples = bytes
+            .chunks(2)
+            .map(|pair| i16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(HgtTile {
+            south,
+            west,
+            size,
+            samples,
+        })
+    }
+
+    // Bilinear interpolation between the 4 surrounding samples
+    fn sample(&self, pt: LonLat) -> Option<f64> {
+        let max = (self.size - 1) as f64;
+        let col = (pt.longitude - self.west) * max;
+        let row = (self.south + 1.0 - pt.latitude) * max;
+        if col < 0.0 || row < 0.0 || col > max || row > max {
+            return None;
+        }
+        let (col0, row0) = (col.floor().min(max - 1.0), row.floor().min(max - 1.0));
+        let (dx, dy) = (col - col0, row - row0);
+
+        let mut total = 0.0;
+        for &(r, c, weight) in &[
+            (row0, col0, (1.0 - dx) * (1.0 - dy)),
+            (row0, col0 + 1.0, dx * (1.0 - dy)),
+            (row0 + 1.0, col0, (1.0 - dx) * dy),
+            (row0 + 1.0, col0 + 1.0, dx * dy),
+        ] {
+            // A void sample doesn't matter if the point is exactly on the other side.
+            if weight == 0.0 {
+                continue;
+            }
+            let value = self.samples[(r as usize) * self.size + (c as usize)];
+            if value == HGT_VOID {
+                return None;
+            }
+            total += weight * f64::from(value);
+        }
+        Some(total)
+    }
+}
+
+// gdallocationinfo reads one "lon lat" pair per line and prints one value per line, or a blank
+// line for points outside the raster.
+fn sample_with_gdal(path: &str, pts: &[LonLat]) -> Result<Vec<Option<f64>>, Error> {
+    let mut child = Command::new("gdallocationinfo")
+        .args(vec!["-valonly", "-wgs84", path])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for pt in pts {
+            writeln!(stdin, "{} {}", pt.longitude, pt.latitude)?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("gdallocationinfo failed on {}", path),
+        ));
+    }
+
+    let mut heights = Vec::new();
+    for line in BufReader::new(output.stdout.as_slice()).lines() {
+        heights.push(line?.trim().parse::<f64>().ok());
+    }
+    if heights.len() != pts.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "gdallocationinfo returned {} values for {} points",
+                heights.len(),
+                pts.len()
+            ),
+        ));
+    }
+    Ok(heights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HgtTile, HGT_VOID};
+    use geom::LonLat;
+
+    fn approx(a: Option<f64>, b: f64) {
+        let a = a.unwrap();
+        assert!((a - b).abs() < 0.001, "{} != {}", a, b);
+    }
+
+    // A 3x3 tile, so samples are half a degree apart. Rows go from north to south.
+    fn tile(name: &str, samples: Vec<i16>) -> HgtTile {
+        let bytes = samples
+            .into_iter()
+            .flat_map(|s| s.to_be_bytes().to_vec())
+            .collect();
+        HgtTile::parse(name, bytes).unwrap()
+    }
+
+    #[test]
+    fn parse_name() {
+        let t = tile("data/N47W123.hgt", vec![0; 9]);
+        assert_eq!((t.south, t.west, t.size), (47.0, -123.0, 3));
+        let t = tile("s05e010.hgt", vec![0; 9]);
+        assert_eq!((t.south, t.west), (-5.0, 10.0));
+
+        assert!(HgtTile::parse("N47W12.hgt", vec![0; 18]).is_err());
+        assert!(HgtTile::parse("X47W123.hgt", vec![0; 18]).is_err());
+        // Not square
+        assert!(HgtTile::parse("N47W123.hgt", vec![0; 16]).is_err());
+    }
+
+    #[test]
+    fn sample() {
+        #[rustfmt::skip]
+        let t = tile("N47W123.hgt", vec![
+            100, 200, 300,
+            0, 100, 200,
+            0, 0, HGT_VOID,
+        ]);
+        // Corners
+        approx(t.sample(LonLat::new(-123.0, 48.0)), 100.0);
+        approx(t.sample(LonLat::new(-122.0, 48.0)), 300.0);
+        approx(t.sample(LonLat::new(-123.0, 47.0)), 0.0);
+        // Exactly on a sample, and between them
+        approx(t.sample(LonLat::new(-122.5, 47.5)), 100.0);
+        approx(t.sample(LonLat::new(-122.75, 48.0)), 150.0);
+        approx(t.sample(LonLat::new(-122.75, 47.75)), 100.0);
+
+        // Anything near a void has no answer
+        assert_eq!(t.sample(LonLat::new(-122.25, 47.4)), None);
+        approx(t.sample(LonLat::new(-122.25, 47.5)), 150.0);
+        // Outside the tile
+        assert_eq!(t.sample(LonLat::new(-123.5, 47.5)), None);
+        assert_eq!(t.sample(LonLat::new(-122.5, 48.5)), None);
+    }
+}
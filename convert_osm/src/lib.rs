@@ -1,4 +1,5 @@
 mod clip;
+mod elevation;
 mod neighborhoods;
 mod osm_reader;
 mod split_ways;
@@ -17,6 +18,8 @@ pub struct Flags {
     pub gtfs: Option<String>,
     pub neighborhoods: Option<String>,
     pub clip: Option<String>,
+    // An SRTM .hgt tile, or any raster GDAL can read
+    pub elevation: Option<String>,
     pub output: String,
 }
 
@@ -30,6 +33,9 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     // Need to do a first pass of removing cul-de-sacs here, or we wind up with loop PolyLines when doing the parking hint matching.
     abstutil::retain_btreemap(&mut map.roads, |r, _| r.i1 != r.i2);
 
+    if let Some(ref path) = flags.elevation {
+        elevation::add_elevation(&mut map, path, timer);
+    }
     if let Some(ref path) = flags.parking_shapes {
         use_parking_hints(&mut map, path, timer);
     }
@@ -42,6 +48,15 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     if let Some(ref path) = flags.gtfs {
         timer.start("load GTFS");
         map.bus_routes = gtfs::load(path).unwrap();
+        // Only keep the part of each trip inside the map; otherwise every map would store the
+        // timetable for the whole region.
+        let gps_bounds = &map.gps_bounds;
+        for route in &mut map.bus_routes {
+            for trip in &mut route.trips {
+                trip.stop_times.retain(|st| gps_bounds.contains(st.stop));
+            }
+            route.trips.retain(|trip| trip.stop_times.len() >= 2);
+        }
         timer.stop("load GTFS");
     }
 
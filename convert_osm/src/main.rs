@@ -11,6 +11,7 @@ fn main() {
         gtfs: args.optional("--gtfs"),
         neighborhoods: args.optional("--neighborhoods"),
         clip: args.optional("--clip"),
+        elevation: args.optional("--elevation"),
         output: args.required("--output"),
     };
     args.done();
@@ -17,6 +17,8 @@ pub fn extract_osm(
     Vec<(i64, RawRoad)>,
     // Traffic signals
     HashSet<HashablePt2D>,
+    // Pedestrian crossings, with the value of the OSM crossing tag
+    HashMap<HashablePt2D, String>,
     // OSM Node IDs
     HashMap<HashablePt2D, i64>,
     // Turn restrictions: (restriction type, from way ID, via node ID, to way ID)
@@ -46,6 +48,7 @@ pub fn extract_osm(
     let mut id_to_way: HashMap<i64, Vec<Pt2D>> = HashMap::new();
     let mut roads: Vec<(i64, RawRoad)> = Vec::new();
     let mut traffic_signals: HashSet<HashablePt2D> = HashSet::new();
+    let mut crossings: HashMap<HashablePt2D, String> = HashMap::new();
     let mut osm_node_ids = HashMap::new();
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -59,6 +62,11 @@ pub fn extract_osm(
         if tags.get(osm::HIGHWAY) == Some(&"traffic_signals".to_string()) {
             traffic_signals.insert(pt);
         }
+        if tags.get(osm::HIGHWAY) == Some(&"crossing".to_string()) {
+            if let Some(crossing) = tags.get("crossing") {
+                crossings.insert(pt, crossing.clone());
+            }
+        }
     }
 
     let mut coastline_groups: Vec<Vec<Pt2D>> = Vec::new();
@@ -119,6 +127,17 @@ pub fn extract_osm(
                 }
             }
 
+            roads.push((
+                way.id,
+                RawRoad {
+                    center_points: pts,
+                    osm_tags: tags,
+                    turn_restrictions: Vec::new(),
+                },
+            ));
+        } else if osm::is_light_rail(&tags) {
+            // Tracks are modeled as roads with only light rail lanes, so they don't need any of
+            // the parking or sidewalk inference.
             roads.push((
                 way.id,
                 RawRoad {
@@ -203,6 +222,33 @@ pub fn extract_osm(
                     }
                 }
             }
+        } else if tags.get("type") == Some(&"route".to_string())
+            && (tags.get("route") == Some(&"light_rail".to_string())
+                || tags.get("route") == Some(&"tram".to_string()))
+        {
+            // The stations are nodes, in order. The tracks are ways, but those were already
+            // picked up.
+            let mut stops = Vec::new();
+            for member in &rel.members {
+                if let osm_xml::Member::Node(ref node_ref, ref role) = member {
+                    // Also stop_entry_only and stop_exit_only
+                    if !role.starts_with("stop") {
+                        continue;
+                    }
+                    if let osm_xml::Reference::Node(node) = doc.resolve_reference(node_ref) {
+                        stops.push(LonLat::new(node.lon, node.lat));
+                    }
+                }
+            }
+            if let Some(name) = tags.get(osm::NAME).or_else(|| tags.get("ref")) {
+                map.light_rail_routes.push(gtfs::Route {
+                    name: name.to_string(),
+                    stops,
+                    trips: Vec::new(),
+                });
+            } else {
+                println!("Light rail relation {} has no name, skipping it", rel.id);
+            }
         } else if tags.get("type") == Some(&"restriction".to_string()) {
             let mut from_way_id: Option<i64> = None;
             let mut via_node_id: Option<i64> = None;
@@ -253,7 +299,14 @@ pub fn extract_osm(
         });
     }
 
-    (map, roads, traffic_signals, osm_node_ids, turn_restrictions)
+    (
+        map,
+        roads,
+        traffic_signals,
+        crossings,
+        osm_node_ids,
+        turn_restrictions,
+    )
 }
 
 fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
@@ -1,16 +1,21 @@
 use abstutil::{Counter, Timer};
-use geom::{HashablePt2D, Pt2D};
+use geom::{Distance, HashablePt2D, Pt2D};
 use map_model::raw::{
     OriginalIntersection, OriginalRoad, RawIntersection, RawMap, RawRoad, RestrictionType,
 };
 use map_model::{osm, IntersectionType};
 use std::collections::{HashMap, HashSet};
 
+// OSM crossings further than this from the end of a road are mid-block. Crosswalks only exist at
+// intersections, so those are ignored.
+const MAX_CROSSING_DIST: Distance = Distance::const_meters(20.0);
+
 pub fn split_up_roads(
-    (mut map, roads, traffic_signals, osm_node_ids, turn_restrictions): (
+    (mut map, roads, traffic_signals, crossings, osm_node_ids, turn_restrictions): (
         RawMap,
         Vec<(i64, RawRoad)>,
         HashSet<HashablePt2D>,
+        HashMap<HashablePt2D, String>,
         HashMap<HashablePt2D, i64>,
         Vec<(RestrictionType, i64, i64, i64)>,
     ),
@@ -47,6 +52,7 @@ pub fn split_up_roads(
                 } else {
                     IntersectionType::StopSign
                 },
+                elevation: Distance::ZERO,
             },
         );
     }
@@ -75,6 +81,20 @@ pub fn split_up_roads(
                     r.osm_tags
                         .insert(osm::ENDPT_FWD.to_string(), "true".to_string());
                 }
+                // Which end is each crossing in the middle of this piece closest to?
+                for crossing_pt in &pts[1..pts.len() - 1] {
+                    if let Some(crossing) = crossings.get(&crossing_pt.to_hashable()) {
+                        let dist_back = crossing_pt.dist_to(pts[0]);
+                        let dist_fwd = crossing_pt.dist_to(*pt);
+                        if dist_back <= dist_fwd && dist_back <= MAX_CROSSING_DIST {
+                            r.osm_tags
+                                .insert(osm::CROSSING_BACK.to_string(), crossing.clone());
+                        } else if dist_fwd < dist_back && dist_fwd <= MAX_CROSSING_DIST {
+                            r.osm_tags
+                                .insert(osm::CROSSING_FWD.to_string(), crossing.clone());
+                        }
+                    }
+                }
                 r.center_points = dedupe_angles(std::mem::replace(&mut pts, Vec::new()));
                 // Start a new road
                 map.roads.insert(
@@ -87,6 +107,8 @@ pub fn split_up_roads(
                 );
                 r.osm_tags.remove(osm::ENDPT_FWD);
                 r.osm_tags.remove(osm::ENDPT_BACK);
+                r.osm_tags.remove(osm::CROSSING_FWD);
+                r.osm_tags.remove(osm::CROSSING_BACK);
                 i1 = *i2;
                 pts.push(*pt);
             }
@@ -166,6 +166,9 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                             load: abstutil::path_map(&test.map_name),
                             use_map_fixes: current_flags.sim_flags.use_map_fixes,
                             rng_seed: current_flags.sim_flags.rng_seed,
+                            travel_times: current_flags.sim_flags.travel_times.clone(),
+                            turn_penalties: current_flags.sim_flags.turn_penalties.clone(),
+                            car_routes: current_flags.sim_flags.car_routes.clone(),
                             opts: SimOptions {
                                 run_name: format!("{} with {}", test.test_name, test.edits2_name),
                                 savestate_every: None,
@@ -177,10 +180,17 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                                     .sim_flags
                                     .opts
                                     .disable_block_the_box,
+                                crosswalk_rules: current_flags.sim_flags.opts.crosswalk_rules,
                                 recalc_lanechanging: current_flags
                                     .sim_flags
                                     .opts
                                     .recalc_lanechanging,
+                                use_acceleration: current_flags.sim_flags.opts.use_acceleration,
+                                mid_lane_changing: current_flags.sim_flags.opts.mid_lane_changing,
+                                pass_bikes: current_flags.sim_flags.opts.pass_bikes,
+                                dynamic_rerouting: current_flags.sim_flags.opts.dynamic_rerouting,
+                                event_logs: Vec::new(),
+                                service_day: current_flags.sim_flags.opts.service_day,
                             },
                         },
                         ..current_flags.clone()
@@ -180,6 +180,9 @@ pub fn prebake() {
             ),
             use_map_fixes: true,
             rng_seed: Some(42),
+            travel_times: None,
+            turn_penalties: None,
+            car_routes: None,
             opts: SimOptions::new("prebaked"),
         }
         .load(&mut timer);
@@ -5,8 +5,8 @@ use crate::ui::UI;
 use abstutil::prettyprint_usize;
 use ezgui::{hotkey, Color, EventCtx, GfxCtx, Key, Line, ModalMenu, Text};
 use geom::Duration;
-use map_model::PathConstraints;
-use sim::CarID;
+use map_model::{PathConstraints, Traversable};
+use sim::{Blockage, CarID};
 use std::collections::BTreeMap;
 
 pub struct InfoPanel {
@@ -81,6 +81,10 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
                 r.id,
                 r.center_pts.length()
             )));
+            txt.add(Line(format!(
+                "Lane has a {:.1}% grade",
+                100.0 * l.grade(map)
+            )));
 
             txt.add(Line(""));
             styled_kv(&mut txt, &r.osm_tags);
@@ -96,6 +100,23 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
                     "Parking blackhole redirect? {:?}",
                     l.parking_blackhole
                 )));
+            } else if l.is_sidewalk() {
+                let (peds, los) = ui.primary.sim.current_ped_los(id, map);
+                txt.add(Line(format!(
+                    "{} wide, {} pedestrians now (level-of-service {})",
+                    r.get_sidewalk_width(),
+                    peds,
+                    los
+                )));
+            }
+            for incident in ui.primary.sim.get_active_incidents() {
+                if incident.blocks(Traversable::Lane(id)) {
+                    txt.add(Line(format!(
+                        "Blocked by a {} until {}",
+                        incident.kind,
+                        incident.start_time + incident.duration
+                    )));
+                }
             }
 
             txt.add(Line(""));
@@ -120,6 +141,7 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
                     PathConstraints::Car,
                     PathConstraints::Bike,
                     PathConstraints::Bus,
+                    PathConstraints::Train,
                 ] {
                     if constraint.can_use(l, map) {
                         txt.add(Line(format!(
@@ -147,6 +169,15 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
                     Line(")"),
                 ]);
             }
+            for incident in ui.primary.sim.get_active_incidents() {
+                if incident.blockage == Blockage::Intersection(id) {
+                    txt.add(Line(format!(
+                        "Blocked by a {} until {}",
+                        incident.kind,
+                        incident.start_time + incident.duration
+                    )));
+                }
+            }
 
             let delays = ui.primary.sim.get_intersection_delays(id);
             if let Some(p) = delays.percentile(50.0) {
@@ -156,6 +187,19 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
             if let Some(p) = delays.percentile(90.0) {
                 txt.add(Line(format!("90%ile delay: {}", p)));
             }
+            if map.maybe_get_traffic_signal(id).is_some() {
+                let ped_delays = ui.primary.sim.get_ped_signal_delays(id);
+                if let Some(p) = ped_delays.percentile(50.0) {
+                    txt.add(Line(format!("50%ile pedestrian signal delay: {}", p)));
+                }
+                let stranded = ui.primary.sim.get_num_stranded_peds(id);
+                if stranded > 0 {
+                    txt.add(Line(format!(
+                        "{} pedestrians still crossing when the walk signal ended",
+                        stranded
+                    )));
+                }
+            }
 
             let accepted = ui.primary.sim.get_accepted_agents(id);
             if !accepted.is_empty() {
@@ -271,6 +315,12 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
                     "  {} passengers total (any stop)",
                     prettyprint_usize(passengers.get(r.id))
                 )));
+                if let Some(n) = sim.get_analytics().bus_passups(sim.time(), r.id).get(&id) {
+                    txt.add(Line(format!(
+                        "  {} passengers left behind by full buses",
+                        prettyprint_usize(*n)
+                    )));
+                }
             }
         }
         ID::Area(id) => {
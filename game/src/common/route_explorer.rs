@@ -1,14 +1,27 @@
 use crate::common::CommonState;
 use crate::game::{State, Transition};
+use crate::helpers::rotating_color;
 use crate::render::DrawTurn;
 use crate::ui::UI;
-use ezgui::{Color, EventCtx, GfxCtx, Key, Line, Text, WarpingItemSlider};
+use ezgui::{
+    Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Text, VerticalAlignment,
+    WarpingItemSlider,
+};
 use geom::{Distance, Polygon, Pt2D};
-use map_model::{Traversable, LANE_THICKNESS};
+use map_model::{
+    Path, PathConstraints, PathRequest, PathStep, Position, Traversable, LANE_THICKNESS,
+};
+use sim::AgentID;
+
+const NUM_ALTERNATIVES: usize = 3;
 
 pub struct RouteExplorer {
     slider: WarpingItemSlider<Traversable>,
     entire_trace: Option<Polygon>,
+    path: Path,
+    constraints: Option<PathConstraints>,
+    // The trace of each alternative route, and a description of all of them
+    alternatives: Option<(Vec<(Color, Polygon)>, Text)>,
 }
 
 impl RouteExplorer {
@@ -24,6 +37,11 @@ impl RouteExplorer {
             return None;
         }
 
+        let constraints = match agent {
+            AgentID::Car(c) => Some(c.1.to_constraints()),
+            AgentID::Pedestrian(_) => None,
+        };
+
         // TODO Actual start dist
         let entire_trace = path
             .trace(&ui.primary.map, Distance::ZERO, None)
@@ -50,8 +68,56 @@ impl RouteExplorer {
                 ctx,
             ),
             entire_trace,
+            path,
+            constraints,
+            alternatives: None,
+        })
+    }
+}
+
+// From wherever the agent is now to the end of its route
+fn make_alternatives(
+    path: &Path,
+    constraints: PathConstraints,
+    ui: &UI,
+) -> (Vec<(Color, Polygon)>, Text) {
+    let map = &ui.primary.map;
+    // The agent might be in the middle of a turn
+    let start = path
+        .get_steps()
+        .iter()
+        .find_map(|step| match step {
+            PathStep::Lane(l) => Some(*l),
+            _ => None,
         })
+        .unwrap();
+    let req = PathRequest {
+        start: Position::new(start, Distance::ZERO),
+        end: Position::new(path.last_step().as_lane(), path.end_dist()),
+        constraints,
+    };
+    let mut traces = Vec::new();
+    let mut txt = Text::prompt("Alternative routes");
+    for (idx, alt) in map
+        .pathfind_alternatives(req, NUM_ALTERNATIVES)
+        .into_iter()
+        .enumerate()
+    {
+        let color = rotating_color(idx);
+        if let Some(pl) = alt.path.trace(map, Distance::ZERO, None) {
+            traces.push((color, pl.make_polygons(LANE_THICKNESS)));
+        }
+        txt.add(
+            Line(format!(
+                "Route {}: cost {}, {}% shared with earlier routes",
+                idx + 1,
+                alt.cost,
+                (alt.overlap * 100.0).round()
+            ))
+            .fg(color),
+        );
     }
+    (traces, txt)
 }
 
 impl State for RouteExplorer {
@@ -61,6 +127,18 @@ impl State for RouteExplorer {
         }
         ctx.canvas.handle_event(ctx.input);
 
+        if let Some(constraints) = self.constraints {
+            if self.alternatives.is_none()
+                && ctx.input.key_pressed(Key::A, "compare alternative routes")
+            {
+                self.alternatives = Some(make_alternatives(&self.path, constraints, ui));
+            } else if self.alternatives.is_some()
+                && ctx.input.key_pressed(Key::A, "hide alternative routes")
+            {
+                self.alternatives = None;
+            }
+        }
+
         // We don't really care about setting current_selection to the current step; drawing covers
         // it up anyway.
         if let Some((evmode, _)) = self.slider.event(ctx) {
@@ -74,6 +152,12 @@ impl State for RouteExplorer {
         if let Some(ref poly) = self.entire_trace {
             g.draw_polygon(ui.cs.get_def("entire route", Color::BLUE.alpha(0.2)), poly);
         }
+        if let Some((ref traces, ref txt)) = self.alternatives {
+            for (color, poly) in traces {
+                g.draw_polygon(color.alpha(0.5), poly);
+            }
+            g.draw_blocking_text(txt, (HorizontalAlignment::Left, VerticalAlignment::Top));
+        }
 
         let color = ui.cs.get_def("current step", Color::RED);
         match self.slider.get().1 {
@@ -38,6 +38,11 @@ impl TripExplorer {
                     ID::Intersection(i),
                     Text::from(Line(format!("enter map via {}", i))),
                 ),
+                TripStart::BusStop(bs) => (
+                    map.get_bs(bs).driving_pos.pt(map),
+                    ID::BusStop(bs),
+                    Text::from(Line(format!("start at {}", bs))),
+                ),
             },
             (
                 ui.primary
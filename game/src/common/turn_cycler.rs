@@ -35,14 +35,13 @@ impl TurnCyclerState {
                 }
             }
             Some(ID::Intersection(i)) => {
-                if let Some(ref signal) = ui.primary.map.maybe_get_traffic_signal(i) {
+                if ui.primary.map.maybe_get_traffic_signal(i).is_some() {
                     if ctx
                         .input
                         .contextual_action(Key::F, "show full traffic signal diagram")
                     {
                         ui.primary.current_selection = None;
-                        let (idx, _, _) =
-                            signal.current_phase_and_remaining_time(ui.primary.sim.time());
+                        let (idx, _, _) = ui.primary.sim.current_signal_phase(i, &ui.primary.map);
                         return Some(Transition::Push(Box::new(ShowTrafficSignal {
                             menu: ModalMenu::new(
                                 "Traffic Signal Diagram",
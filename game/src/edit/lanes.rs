@@ -289,6 +289,10 @@ fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String
         return None;
     }
 
+    if map.get_l(l).is_light_rail() {
+        return Some("Light rail tracks can't be changed".to_string());
+    }
+
     // Only one parking lane per side.
     if proposed_lts
         .iter()
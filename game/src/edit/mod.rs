@@ -178,6 +178,7 @@ impl State for EditMode {
         } else if self.menu.action("load different edits") {
             return Transition::Push(WizardState::new(Box::new(load_edits)));
         } else if self.menu.action("back to sandbox mode") {
+            // The sim is about to pathfind, so wait for the background rebuild to finish.
             // TODO Maybe put a loading screen around these.
             ui.primary
                 .map
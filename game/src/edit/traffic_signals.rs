@@ -11,9 +11,10 @@ use ezgui::{
     hotkey, lctrl, Choice, Color, EventCtx, EventLoopMode, GeomBatch, GfxCtx, Key, Line, ModalMenu,
     Text,
 };
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{
-    ControlTrafficSignal, EditCmd, IntersectionID, Phase, TurnGroupID, TurnPriority, TurnType,
+    ControlTrafficSignal, EditCmd, IntersectionID, Phase, SignalMode, TurnGroupID, TurnPriority,
+    TurnType,
 };
 use std::collections::BTreeSet;
 use std::time::Instant;
@@ -47,6 +48,10 @@ impl TrafficSignalEditor {
                     "convert to dedicated pedestrian scramble phase",
                 ),
                 (hotkey(Key::O), "change signal offset"),
+                (hotkey(Key::A), "toggle between fixed-time and actuated"),
+                (hotkey(Key::G), "change phase min and max green"),
+                (hotkey(Key::X), "change actuated gap and detector length"),
+                (hotkey(Key::L), "add leading bike intervals"),
                 (lctrl(Key::P), "preview changes"),
                 (hotkey(Key::Escape), "quit"),
             ],
@@ -67,13 +72,26 @@ impl State for TrafficSignalEditor {
 
         self.menu.event(ctx);
         // TODO This really needs to be shown in the diagram!
-        self.menu.set_info(
-            ctx,
-            Text::from(Line(format!(
-                "Signal offset: {}",
-                orig_signal.offset.minimal_tostring()
-            ))),
-        );
+        let mut txt = Text::from(Line(format!(
+            "Signal offset: {}",
+            orig_signal.offset.minimal_tostring()
+        )));
+        match orig_signal.mode {
+            SignalMode::FixedTime => {
+                txt.add(Line("Fixed-time"));
+            }
+            SignalMode::Actuated {
+                gap,
+                detector_length,
+            } => {
+                txt.add(Line(format!(
+                    "Actuated, {} gap, {} detectors",
+                    gap.minimal_tostring(),
+                    detector_length
+                )));
+            }
+        }
+        self.menu.set_info(ctx, txt);
         ctx.canvas.handle_event(ctx.input);
         self.diagram.event(ctx, &mut self.menu);
 
@@ -131,6 +149,27 @@ impl State for TrafficSignalEditor {
                     return Transition::Keep;
                 }
             }
+
+            if id.crosswalk.is_none() {
+                let has_box = orig_signal.bike_boxes.contains(&id.from);
+                if ctx.input.contextual_action(
+                    Key::C,
+                    if has_box {
+                        "remove the bike box from this approach"
+                    } else {
+                        "add a bike box to this approach"
+                    },
+                ) {
+                    let mut new_signal = orig_signal.clone();
+                    if has_box {
+                        new_signal.bike_boxes.remove(&id.from);
+                    } else {
+                        new_signal.bike_boxes.insert(id.from);
+                    }
+                    change_traffic_signal(new_signal, ui, ctx);
+                    return Transition::Keep;
+                }
+            }
         }
 
         if self.menu.action("quit") {
@@ -138,11 +177,23 @@ impl State for TrafficSignalEditor {
         }
 
         if self.menu.action("change phase duration") {
+            let phase = &orig_signal.phases[self.diagram.current_phase()];
             return Transition::Push(change_phase_duration(
-                orig_signal.phases[self.diagram.current_phase()].duration,
+                phase.duration,
+                orig_signal.ped_clearance(phase, &ui.primary.map),
             ));
         } else if self.menu.action("change signal offset") {
             return Transition::Push(change_offset(orig_signal.offset));
+        } else if self.menu.action("toggle between fixed-time and actuated") {
+            let mut new_signal = orig_signal.clone();
+            new_signal.mode = match new_signal.mode {
+                SignalMode::FixedTime => SignalMode::default_actuated(),
+                SignalMode::Actuated { .. } => SignalMode::FixedTime,
+            };
+            change_traffic_signal(new_signal, ui, ctx);
+            self.diagram =
+                TrafficSignalDiagram::new(self.diagram.i, self.diagram.current_phase(), ui, ctx);
+            return Transition::Keep;
         } else if self.menu.action("choose a preset signal") {
             return Transition::Push(change_preset(self.diagram.i));
         } else if self.menu.action("reset to original") {
@@ -164,6 +215,23 @@ impl State for TrafficSignalEditor {
 
         let current_phase = self.diagram.current_phase();
 
+        if let SignalMode::Actuated {
+            gap,
+            detector_length,
+        } = orig_signal.mode
+        {
+            if self.menu.action("change phase min and max green") {
+                let phase = &orig_signal.phases[current_phase];
+                return Transition::Push(change_min_max_green(
+                    phase.min_green,
+                    phase.max_green,
+                    orig_signal.ped_clearance(phase, &ui.primary.map),
+                ));
+            } else if self.menu.action("change actuated gap and detector length") {
+                return Transition::Push(change_actuated_settings(gap, detector_length));
+            }
+        }
+
         if current_phase != 0 && self.menu.action("move current phase up") {
             let mut new_signal = orig_signal.clone();
             new_signal.phases.swap(current_phase, current_phase - 1);
@@ -216,6 +284,15 @@ impl State for TrafficSignalEditor {
             new_signal.convert_to_ped_scramble(&ui.primary.map);
             change_traffic_signal(new_signal, ui, ctx);
             self.diagram = TrafficSignalDiagram::new(self.diagram.i, 0, ui, ctx);
+        } else if self.menu.action("add leading bike intervals") {
+            let mut new_signal = orig_signal.clone();
+            if !new_signal.has_bike_groups() {
+                new_signal.split_bike_groups(&ui.primary.map);
+            }
+            new_signal.add_leading_bike_intervals(Duration::seconds(5.0));
+            change_traffic_signal(new_signal, ui, ctx);
+            self.groups = DrawTurnGroup::for_i(self.diagram.i, &ui.primary.map);
+            self.diagram = TrafficSignalDiagram::new(self.diagram.i, 0, ui, ctx);
         }
 
         if self.menu.action("preview changes") {
@@ -299,6 +376,9 @@ impl State for TrafficSignalEditor {
 }
 
 fn change_traffic_signal(signal: ControlTrafficSignal, ui: &mut UI, ctx: &mut EventCtx) {
+    // Any change (protecting a crosswalk, adding a phase, switching to actuated) might leave a
+    // phase too short for pedestrians to finish crossing.
+    let signal = signal.with_ped_clearance(&ui.primary.map);
     let mut edits = ui.primary.map.get_edits().clone();
     // TODO Only record one command for the entire session. Otherwise, we can exit this editor and
     // undo a few times, potentially ending at an invalid state!
@@ -317,12 +397,22 @@ fn change_traffic_signal(signal: ControlTrafficSignal, ui: &mut UI, ctx: &mut Ev
     apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
 }
 
-fn change_phase_duration(current_duration: Duration) -> Box<dyn State> {
+// Phases with crosswalks have to last long enough for pedestrians to finish crossing.
+fn change_phase_duration(current_duration: Duration, ped_clearance: Duration) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, _| {
         let new_duration = wiz.wrap(ctx).input_usize_prefilled(
             "How long should this phase be (seconds)?",
             format!("{}", current_duration.inner_seconds() as usize),
         )?;
+        if Duration::seconds(new_duration as f64) < ped_clearance {
+            return Some(Transition::Replace(msg(
+                "Error",
+                vec![format!(
+                    "Pedestrians need at least {} to cross during this phase",
+                    ped_clearance
+                )],
+            )));
+        }
         Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
             let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
             let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
@@ -334,6 +424,86 @@ fn change_phase_duration(current_duration: Duration) -> Box<dyn State> {
     }))
 }
 
+fn change_min_max_green(
+    min_green: Duration,
+    max_green: Duration,
+    ped_clearance: Duration,
+) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, _| {
+        let mut wizard = wiz.wrap(ctx);
+        let new_min = wizard.input_usize_prefilled(
+            "What's the minimum green time for this phase (seconds)?",
+            format!("{}", min_green.inner_seconds() as usize),
+        )?;
+        let new_max = wizard.input_usize_prefilled(
+            "What's the maximum green time for this phase (seconds)?",
+            format!("{}", max_green.inner_seconds() as usize),
+        )?;
+        if new_min == 0 || new_min > new_max {
+            return Some(Transition::Replace(msg(
+                "Error",
+                vec![format!(
+                    "Min green {}s must be positive and no more than max green {}s",
+                    new_min, new_max
+                )],
+            )));
+        }
+        if Duration::seconds(new_min as f64) < ped_clearance {
+            return Some(Transition::Replace(msg(
+                "Error",
+                vec![format!(
+                    "Pedestrians need at least {} to cross, so min green can't be {}s",
+                    ped_clearance, new_min
+                )],
+            )));
+        }
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
+            let idx = editor.diagram.current_phase();
+            signal.phases[idx].min_green = Duration::seconds(new_min as f64);
+            signal.phases[idx].max_green = Duration::seconds(new_max as f64);
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram = TrafficSignalDiagram::new(editor.diagram.i, idx, ui, ctx);
+        })))
+    }))
+}
+
+fn change_actuated_settings(gap: Duration, detector_length: Distance) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, _| {
+        let mut wizard = wiz.wrap(ctx);
+        let new_gap = wizard.input_usize_prefilled(
+            "End the phase after nothing's been detected for how long (seconds)?",
+            format!("{}", gap.inner_seconds() as usize),
+        )?;
+        let new_length = wizard.input_usize_prefilled(
+            "How far back from the intersection do the detectors reach (meters)?",
+            format!("{}", detector_length.inner_meters() as usize),
+        )?;
+        if new_gap == 0 || new_length == 0 {
+            return Some(Transition::Replace(msg(
+                "Error",
+                vec!["The gap and detector length must be positive"],
+            )));
+        }
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
+            signal.mode = SignalMode::Actuated {
+                gap: Duration::seconds(new_gap as f64),
+                detector_length: Distance::meters(new_length as f64),
+            };
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram = TrafficSignalDiagram::new(
+                editor.diagram.i,
+                editor.diagram.current_phase(),
+                ui,
+                ctx,
+            );
+        })))
+    }))
+}
+
 fn change_offset(current_duration: Duration) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, _| {
         let new_duration = wiz.wrap(ctx).input_usize_prefilled(
@@ -389,9 +559,11 @@ fn check_for_missing_groups(
         }
     }
     if missing.is_empty() {
-        let i = signal.id;
-        if let Err(err) = signal.validate() {
-            panic!("Edited traffic signal {} finalized with errors: {}", i, err);
+        if let Err(err) = signal.validate(&ui.primary.map) {
+            return Transition::Push(msg(
+                "Error: invalid traffic signal",
+                vec![err, "Please fix this before quitting.".to_string()],
+            ));
         }
         return Transition::Pop;
     }
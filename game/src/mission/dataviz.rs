@@ -1,15 +1,17 @@
 use crate::common::CommonState;
-use crate::game::{State, Transition};
+use crate::game::{State, Transition, WizardState};
 use crate::helpers::{rotating_color_total, ID};
 use crate::ui::UI;
 use abstutil::{prettyprint_usize, Timer};
 use ezgui::{
-    hotkey, Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, ModalMenu, Text,
-    VerticalAlignment,
+    hotkey, Choice, Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, ModalMenu, Text,
+    VerticalAlignment, Wizard,
 };
-use geom::{Distance, Polygon, Pt2D};
-use popdat::{Estimate, PopDat};
-use std::collections::BTreeMap;
+use geom::{Distance, Duration, Polygon, Pt2D};
+use map_model::{BuildingID, Map, MapEdits};
+use popdat::{clip_parcels, psrc, Estimate, JobAccess, PopDat};
+use sim::TripMode;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct DataVisualizer {
     menu: ModalMenu,
@@ -18,27 +20,38 @@ pub struct DataVisualizer {
 
     // Table if false
     show_bars: bool,
-    // TODO Urgh. 0, 1, or 2.
+    // TODO Urgh. 0, 1, 2, or 3.
     current_dataset: usize,
     current_tract: Option<String>,
+
+    parcels: HashMap<BuildingID, psrc::Parcel>,
+    job_access: Option<JobAccessComparison>,
+}
+
+struct JobAccessComparison {
+    // Edits names
+    before_edits: String,
+    after_edits: String,
+    before: JobAccess,
+    after: JobAccess,
 }
 
 struct Tract {
     polygon: Polygon,
     color: Color,
 
-    num_bldgs: usize,
+    bldgs: Vec<BuildingID>,
     num_parking_spots: usize,
     total_owned_cars: usize,
 }
 
 impl DataVisualizer {
     pub fn new(ctx: &mut EventCtx, ui: &UI) -> DataVisualizer {
-        let (popdat, tracts) = ctx.loading_screen("initialize popdat", |_, mut timer| {
-            let popdat: PopDat = abstutil::read_binary("../data/shapes/popdat.bin", &mut timer)
-                .expect("Couldn't load popdat.bin");
+        let (popdat, tracts, parcels) = ctx.loading_screen("initialize popdat", |_, mut timer| {
+            let popdat = PopDat::load(&mut timer);
             let tracts = clip_tracts(&popdat, ui, &mut timer);
-            (popdat, tracts)
+            let parcels = clip_parcels(&popdat.parcels, &ui.primary.map);
+            (popdat, tracts, parcels)
         });
 
         DataVisualizer {
@@ -50,6 +63,9 @@ impl DataVisualizer {
                     (hotkey(Key::Num1), "household vehicles"),
                     (hotkey(Key::Num2), "commute times"),
                     (hotkey(Key::Num3), "commute modes"),
+                    (hotkey(Key::Num4), "job access"),
+                    (hotkey(Key::J), "compare job access with other edits"),
+                    (hotkey(Key::S), "export job access"),
                 ],
                 ctx,
             ),
@@ -58,6 +74,8 @@ impl DataVisualizer {
             show_bars: false,
             current_dataset: 0,
             current_tract: None,
+            parcels,
+            job_access: None,
         }
     }
 }
@@ -73,7 +91,7 @@ impl State for DataVisualizer {
                 let tract = &self.tracts[name];
                 txt.add(Line(format!(
                     "{} buildings",
-                    prettyprint_usize(tract.num_bldgs)
+                    prettyprint_usize(tract.bldgs.len())
                 )));
                 txt.add(Line(format!(
                     "{} parking spots ",
@@ -98,6 +116,25 @@ impl State for DataVisualizer {
             self.current_dataset = 1;
         } else if self.current_dataset != 2 && self.menu.action("commute modes") {
             self.current_dataset = 2;
+        } else if self.current_dataset != 3
+            && self.job_access.is_some()
+            && self.menu.action("job access")
+        {
+            self.current_dataset = 3;
+        } else if self.menu.action("compare job access with other edits") {
+            return Transition::Push(WizardState::new(Box::new(compare_job_access)));
+        } else if self.job_access.is_some() && self.menu.action("export job access") {
+            let cmp = self.job_access.as_ref().unwrap();
+            let path = format!(
+                "../data/job_access_{}_{}_vs_{}.csv",
+                ui.primary.map.get_name(),
+                cmp.before_edits,
+                cmp.after_edits
+            );
+            cmp.before
+                .save_csv(&cmp.after, &self.parcels, &path)
+                .unwrap();
+            println!("Saved {}", path);
         } else if self.menu.action("toggle table/bar chart") {
             self.show_bars = !self.show_bars;
         }
@@ -140,6 +177,30 @@ impl State for DataVisualizer {
         }
 
         if let Some(ref name) = self.current_tract {
+            if self.current_dataset == 3 {
+                let cmp = self.job_access.as_ref().unwrap();
+                let mut txt = Text::new();
+                txt.add(Line(format!(
+                    "Average jobs reachable per household within {}",
+                    cmp.before.threshold
+                )));
+                txt.add(Line(format!("{} vs {}", cmp.before_edits, cmp.after_edits)));
+                let bldgs = &self.tracts[name].bldgs;
+                for mode in TripMode::all() {
+                    let before = cmp.before.average(bldgs, &self.parcels, mode);
+                    let after = cmp.after.average(bldgs, &self.parcels, mode);
+                    txt.add_appended(vec![
+                        Line(mode.to_string()).fg(Color::RED),
+                        Line(" = "),
+                        Line(prettyprint_usize(before as usize)).fg(Color::CYAN),
+                        Line(" -> "),
+                        Line(prettyprint_usize(after as usize)).fg(Color::CYAN),
+                    ]);
+                }
+                g.draw_blocking_text(&txt, (HorizontalAlignment::Left, VerticalAlignment::Top));
+                return;
+            }
+
             let tract = &self.popdat.tracts[name];
             let kv = if self.current_dataset == 0 {
                 &tract.household_vehicles
@@ -180,7 +241,7 @@ fn clip_tracts(popdat: &PopDat, ui: &UI, timer: &mut Timer) -> BTreeMap<String,
             let polygon = Polygon::new(&pts);
 
             // TODO Don't just use the center...
-            let mut num_bldgs = 0;
+            let mut bldgs = Vec::new();
             let mut num_parking_spots = 0;
             for id in ui
                 .primary
@@ -190,7 +251,7 @@ fn clip_tracts(popdat: &PopDat, ui: &UI, timer: &mut Timer) -> BTreeMap<String,
                 match id {
                     ID::Building(b) => {
                         if polygon.contains_pt(ui.primary.map.get_b(b).polygon.center()) {
-                            num_bldgs += 1;
+                            bldgs.push(b);
                         }
                     }
                     ID::Lane(l) => {
@@ -209,7 +270,7 @@ fn clip_tracts(popdat: &PopDat, ui: &UI, timer: &mut Timer) -> BTreeMap<String,
                     polygon,
                     // Update it after we know the total number of matching tracts.
                     color: Color::WHITE,
-                    num_bldgs,
+                    bldgs,
                     num_parking_spots,
                     total_owned_cars: tract.total_owned_cars(),
                 },
@@ -228,6 +289,46 @@ fn clip_tracts(popdat: &PopDat, ui: &UI, timer: &mut Timer) -> BTreeMap<String,
     results
 }
 
+// Compare job access with the current edits against some other edits.
+fn compare_job_access(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &mut UI) -> Option<Transition> {
+    let mut wizard = wiz.wrap(ctx);
+    let map_name = ui.primary.map.get_name().to_string();
+    let (_, before_edits) = wizard.choose("Compare the current edits against what?", || {
+        let mut list = Choice::from(abstutil::load_all_objects(abstutil::EDITS, &map_name));
+        list.push(Choice::new("no_edits", MapEdits::new(map_name.clone())));
+        list
+    })?;
+    let minutes = wizard.choose_string("Count jobs within how long?", || {
+        vec!["15 minutes", "30 minutes", "45 minutes", "60 minutes"]
+    })?;
+    let threshold = Duration::minutes(minutes.split(' ').next().unwrap().parse().unwrap());
+
+    Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+        let dataviz = state.downcast_mut::<DataVisualizer>().unwrap();
+        let parcels = &dataviz.parcels;
+        let cmp = ctx.loading_screen("compare job access", |_, mut timer| {
+            // The edits to compare against need their own copy of the map.
+            let mut map: Map =
+                abstutil::read_binary(&abstutil::path_map(&map_name), &mut timer).unwrap();
+            let before_name = before_edits.edits_name.clone();
+            map.apply_edits(before_edits, &mut timer);
+            map.recalculate_pathfinding_after_edits(&mut timer);
+            ui.primary
+                .map
+                .recalculate_pathfinding_after_edits(&mut timer);
+
+            JobAccessComparison {
+                before_edits: before_name,
+                after_edits: ui.primary.map.get_edits().edits_name.clone(),
+                before: JobAccess::new(&map, parcels, threshold, &mut timer),
+                after: JobAccess::new(&ui.primary.map, parcels, threshold, &mut timer),
+            }
+        });
+        dataviz.job_access = Some(cmp);
+        dataviz.current_dataset = 3;
+    })))
+}
+
 fn bar_chart(g: &mut GfxCtx, data: &BTreeMap<String, Estimate>) {
     let mut max = 0;
     let mut sum = 0;
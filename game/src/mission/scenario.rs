@@ -23,7 +23,7 @@ pub struct ScenarioManager {
     common: CommonState,
     scenario: Scenario,
 
-    // The usizes are indices into scenario.individ_trips
+    // The usizes are indices into scenario.all_trips()
     trips_from_bldg: MultiMap<BuildingID, usize>,
     trips_to_bldg: MultiMap<BuildingID, usize>,
     trips_from_border: MultiMap<IntersectionID, usize>,
@@ -41,7 +41,7 @@ impl ScenarioManager {
         let mut trips_to_bldg = MultiMap::new();
         let mut trips_from_border = MultiMap::new();
         let mut trips_to_border = MultiMap::new();
-        for (idx, trip) in scenario.individ_trips.iter().enumerate() {
+        for (idx, trip) in scenario.all_trips().into_iter().enumerate() {
             // trips_from_bldg and trips_from_border
             match trip {
                 // TODO CarAppearing might be from a border
@@ -156,7 +156,7 @@ impl State for ScenarioManager {
             txt.add(Line(&self.scenario.scenario_name));
             txt.add(Line(format!(
                 "{} total trips",
-                prettyprint_usize(self.scenario.individ_trips.len())
+                prettyprint_usize(self.scenario.all_trips().len())
             )));
             txt.add(Line(format!(
                 "seed {} parked cars",
@@ -324,7 +324,7 @@ impl State for ScenarioEditor {
                 let mut manager = state.downcast_mut::<ScenarioManager>().unwrap();
                 manager.scenario = scenario;
                 // Don't need to update trips_from_bldg or trips_to_bldg, since edit_scenario
-                // doesn't touch individ_trips.
+                // doesn't touch individ_trips or people.
             }));
         } else if self.wizard.aborted() {
             return Transition::Pop;
@@ -485,10 +485,11 @@ fn make_trip_picker(
             .wrap(ctx)
             .choose(&format!("Trips from/to this {}", noun), || {
                 // TODO Panics if there are two duplicate trips (b1124 in montlake)
+                let trips = scenario.all_trips();
                 indices
                     .iter()
                     .map(|idx| {
-                        let trip = &scenario.individ_trips[*idx];
+                        let trip = trips[*idx];
                         Choice::new(
                             describe(trip, home),
                             other_endpt(trip, home, &ui.primary.map),
@@ -640,21 +641,14 @@ fn show_demand(
     ui: &UI,
     ctx: &EventCtx,
 ) -> Drawable {
+    let trips = scenario.all_trips();
     let mut from_ids = Counter::new();
     for idx in from {
-        from_ids.inc(other_endpt(
-            &scenario.individ_trips[*idx],
-            home,
-            &ui.primary.map,
-        ));
+        from_ids.inc(other_endpt(trips[*idx], home, &ui.primary.map));
     }
     let mut to_ids = Counter::new();
     for idx in to {
-        to_ids.inc(other_endpt(
-            &scenario.individ_trips[*idx],
-            home,
-            &ui.primary.map,
-        ));
+        to_ids.inc(other_endpt(trips[*idx], home, &ui.primary.map));
     }
     let from_count = from_ids.consume();
     let mut to_count = to_ids.consume();
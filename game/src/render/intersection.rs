@@ -140,14 +140,13 @@ impl Renderable for DrawIntersection {
             if self.intersection_type == IntersectionType::TrafficSignal
                 && opts.suppress_traffic_signal_details != Some(self.id)
             {
-                let signal = ctx.map.get_traffic_signal(self.id);
                 let mut maybe_redraw = self.draw_traffic_signal.borrow_mut();
                 let recalc = maybe_redraw
                     .as_ref()
                     .map(|(_, t)| *t != ctx.sim.time())
                     .unwrap_or(true);
                 if recalc {
-                    let (_, phase, t) = signal.current_phase_and_remaining_time(ctx.sim.time());
+                    let (_, phase, t) = ctx.sim.current_signal_phase(self.id, ctx.map);
                     let mut batch = GeomBatch::new();
                     draw_signal_phase(phase, self.id, Some(t), &mut batch, ctx);
                     *maybe_redraw = Some((g.prerender.upload(batch), ctx.sim.time()));
@@ -56,6 +56,7 @@ impl DrawLane {
                 LaneType::Construction => {
                     cs.get_def("construction background", Color::rgb(255, 109, 0))
                 }
+                LaneType::LightRail => cs.get_def("light rail track", Color::rgb(145, 120, 93)),
             },
             polygon.clone(),
         );
@@ -106,6 +107,12 @@ impl DrawLane {
                         polygon.clone(),
                     );
                 }
+                LaneType::LightRail => {
+                    draw.extend(
+                        cs.get_def("light rail rails", Color::grey(0.4)),
+                        calculate_rail_lines(lane, timer),
+                    );
+                }
             };
         }
 
@@ -175,6 +182,21 @@ fn calculate_sidewalk_lines(lane: &Lane) -> Vec<Polygon> {
     result
 }
 
+// Two rails, a standard gauge apart
+fn calculate_rail_lines(lane: &Lane, timer: &mut Timer) -> Vec<Polygon> {
+    let half_gauge = Distance::meters(0.72);
+    vec![
+        lane.lane_center_pts
+            .shift_left(half_gauge)
+            .get(timer)
+            .make_polygons(Distance::meters(0.15)),
+        lane.lane_center_pts
+            .shift_right(half_gauge)
+            .get(timer)
+            .make_polygons(Distance::meters(0.15)),
+    ]
+}
+
 fn calculate_parking_lines(lane: &Lane) -> Vec<Polygon> {
     // meters, but the dims get annoying below to remove
     let leg_length = Distance::meters(1.0);
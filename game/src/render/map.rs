@@ -401,6 +401,7 @@ impl AgentColorScheme {
                 Some(VehicleType::Car) => cs.get_def("unzoomed car", Color::RED.alpha(0.5)),
                 Some(VehicleType::Bike) => cs.get_def("unzoomed bike", Color::GREEN.alpha(0.5)),
                 Some(VehicleType::Bus) => cs.get_def("unzoomed bus", Color::BLUE.alpha(0.5)),
+                Some(VehicleType::Train) => cs.get_def("unzoomed train", Color::PURPLE.alpha(0.5)),
                 None => cs.get_def("unzoomed pedestrian", Color::ORANGE.alpha(0.5)),
             },
             _ => self.by_metadata(&agent.metadata),
@@ -422,6 +423,8 @@ impl AgentColorScheme {
             AgentColorScheme::VehicleTypes => {
                 if input.id.1 == VehicleType::Bus {
                     cs.get_def("bus", Color::rgb(50, 133, 117))
+                } else if input.id.1 == VehicleType::Train {
+                    cs.get_def("train", Color::rgb(0, 84, 166))
                 } else {
                     match input.status {
                         CarStatus::Moving => cs.get_def("moving car", Color::CYAN),
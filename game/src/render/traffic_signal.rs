@@ -5,7 +5,7 @@ use ezgui::{
     ScreenPt, Scroller, Text,
 };
 use geom::{Circle, Distance, Duration, Line, Polygon, Pt2D};
-use map_model::{IntersectionID, Phase, TurnPriority, TurnType, LANE_THICKNESS};
+use map_model::{IntersectionID, Phase, SignalMode, TurnPriority, TurnType, LANE_THICKNESS};
 
 // Only draws a box when time_left is present
 pub fn draw_signal_phase(
@@ -96,7 +96,11 @@ pub fn draw_signal_phase(
     let box_height = 6.5 * radius;
     let center = ctx.map.get_i(i).polygon.center();
     let top_left = center.offset(-box_width / 2.0, -box_height / 2.0);
-    let percent = time_left.unwrap() / phase.duration;
+    let max_duration = match signal.mode {
+        SignalMode::FixedTime => phase.duration,
+        SignalMode::Actuated { .. } => phase.max_green,
+    };
+    let percent = time_left.unwrap() / max_duration;
     // TODO Tune colors.
     batch.push(
         ctx.cs.get_def("traffic signal box", Color::grey(0.5)),
@@ -294,7 +298,8 @@ impl TrafficSignalDiagram {
                 b.max_y - b.min_y,
             )
         };
-        let phases = &ui.primary.map.get_traffic_signal(i).phases;
+        let signal = ui.primary.map.get_traffic_signal(i);
+        let phases = &signal.phases;
 
         let zoom = ctx.canvas.window_width * PERCENT_WIDTH / intersection_width;
         let item_dims = ScreenDims::new(
@@ -313,11 +318,7 @@ impl TrafficSignalDiagram {
         );
         let mut labels = Vec::new();
         for (idx, phase) in phases.iter().enumerate() {
-            labels.push(Text::from(Line(format!(
-                "Phase {}: {}",
-                idx + 1,
-                phase.duration.minimal_tostring()
-            ))));
+            labels.push(Text::from(Line(phase_label(idx, phase, &signal.mode))));
         }
 
         TrafficSignalDiagram {
@@ -382,7 +383,8 @@ fn make_new_scroller(i: IntersectionID, draw_ctx: &DrawCtx, ctx: &EventCtx) -> N
     // but this is close enough.
     let bounds = draw_ctx.map.get_i(i).polygon.get_bounds();
     let mut y_offset = 0.0;
-    for (idx, phase) in draw_ctx.map.get_traffic_signal(i).phases.iter().enumerate() {
+    let signal = draw_ctx.map.get_traffic_signal(i);
+    for (idx, phase) in signal.phases.iter().enumerate() {
         let mut batch = GeomBatch::new();
         draw_signal_phase(phase, i, None, &mut batch, draw_ctx);
         for (color, poly) in batch.consume() {
@@ -395,7 +397,7 @@ fn make_new_scroller(i: IntersectionID, draw_ctx: &DrawCtx, ctx: &EventCtx) -> N
             );
         }
         txt.add(
-            Text::from(Line(format!("Phase {}: {}", idx + 1, phase.duration))),
+            Text::from(Line(phase_label(idx, phase, &signal.mode))),
             ScreenPt::new(10.0 + (bounds.max_x - bounds.min_x) * zoom, y_offset * zoom),
         );
         y_offset += bounds.max_y - bounds.min_y;
@@ -403,3 +405,17 @@ fn make_new_scroller(i: IntersectionID, draw_ctx: &DrawCtx, ctx: &EventCtx) -> N
 
     NewScroller::new(master_batch, txt, zoom, ctx)
 }
+
+fn phase_label(idx: usize, phase: &Phase, mode: &SignalMode) -> String {
+    match mode {
+        SignalMode::FixedTime => {
+            format!("Phase {}: {}", idx + 1, phase.duration.minimal_tostring())
+        }
+        SignalMode::Actuated { .. } => format!(
+            "Phase {}: {} to {} green",
+            idx + 1,
+            phase.min_green.minimal_tostring(),
+            phase.max_green.minimal_tostring()
+        ),
+    }
+}
@@ -6,7 +6,7 @@ use ezgui::{
     Choice, Color, EventCtx, GeomBatch, GfxCtx, Key, Line, MenuUnderButton, Text, WarpingItemSlider,
 };
 use geom::{Circle, Distance, Pt2D};
-use map_model::{BusRoute, BusRouteID, BusStopID, PathConstraints, PathRequest, PathStep};
+use map_model::{BusRoute, BusRouteID, BusStopID, PathRequest, PathStep};
 
 pub struct ShowBusRoute {
     colorer: RoadColorer,
@@ -47,7 +47,7 @@ impl ShowBusRoute {
                 .pathfind(PathRequest {
                     start: bs1.driving_pos,
                     end: bs2.driving_pos,
-                    constraints: PathConstraints::Bus,
+                    constraints: route.constraints,
                 })
                 .unwrap()
                 .get_steps()
@@ -138,6 +138,19 @@ fn bus_route_panel(id: BusRouteID, ui: &UI, stat: Statistic) -> Text {
 
     let route = ui.primary.map.get_br(id);
     let mut txt = Text::new();
+    let (early, on_time, late) = ui
+        .primary
+        .sim
+        .get_analytics()
+        .bus_on_time_performance(ui.primary.sim.time(), id);
+    if early + on_time + late > 0 {
+        txt.add(Line(format!(
+            "{}% of arrivals on time ({} early, {} late)",
+            100 * on_time / (early + on_time + late),
+            early,
+            late
+        )));
+    }
     txt.add(Line(format!("{} delay between stops", stat)));
     for idx1 in 0..route.stops.len() {
         let idx2 = if idx1 == route.stops.len() - 1 {
@@ -19,7 +19,7 @@ use ezgui::{
 pub use gameplay::spawner::spawn_agents_around;
 pub use gameplay::GameplayMode;
 use geom::Duration;
-use map_model::MapEdits;
+use map_model::{MapEdits, PathConstraints};
 use sim::Sim;
 
 pub struct SandboxMode {
@@ -227,6 +227,34 @@ impl State for SandboxMode {
                 })));
             }
         }
+        if let Some(ID::Building(b)) = ui.primary.current_selection {
+            if ctx
+                .input
+                .contextual_action(Key::R, "show what's reachable within 15 minutes")
+            {
+                return Transition::Push(WizardState::new(Box::new(move |wiz, ctx, _| {
+                    let (choice, _) = wiz.wrap(ctx).choose("Get around how?", || {
+                        vec![
+                            Choice::new("walking", ()).key(Key::W),
+                            Choice::new("walking and transit", ()).key(Key::T),
+                            Choice::new("biking", ()).key(Key::B),
+                            Choice::new("driving", ()).key(Key::D),
+                        ]
+                    })?;
+                    let (constraints, use_transit) = match choice.as_ref() {
+                        "walking" => (PathConstraints::Pedestrian, false),
+                        "walking and transit" => (PathConstraints::Pedestrian, true),
+                        "biking" => (PathConstraints::Bike, false),
+                        "driving" => (PathConstraints::Car, false),
+                        _ => unreachable!(),
+                    };
+                    Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+                        let sandbox = state.downcast_mut::<SandboxMode>().unwrap();
+                        sandbox.overlay = Overlays::isochrone(b, constraints, use_transit, ctx, ui);
+                    })))
+                })));
+            }
+        }
         if let Some(ID::Lane(l)) = ui.primary.current_selection {
             if ctx
                 .input
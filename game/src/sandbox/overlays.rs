@@ -8,9 +8,13 @@ use crate::sandbox::bus_explorer::ShowBusRoute;
 use crate::sandbox::SandboxMode;
 use crate::ui::{ShowEverything, UI};
 use abstutil::{prettyprint_usize, Counter};
-use ezgui::{Choice, Color, EventCtx, GfxCtx, Key, Line, MenuUnderButton, Text};
+use ezgui::{
+    Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, Key, Line, MenuUnderButton, Text,
+};
 use geom::Duration;
-use map_model::{IntersectionID, LaneID, PathConstraints, PathStep, RoadID};
+use map_model::{
+    Budget, BuildingID, IntersectionID, LaneID, PathConstraints, PathStep, Reachability, RoadID,
+};
 use sim::{ParkingSpot, TripMode};
 use std::collections::{BTreeMap, HashSet};
 
@@ -24,6 +28,8 @@ pub enum Overlays {
     BikeNetwork(RoadColorer),
     BikePathCosts(RoadColorer),
     BusNetwork(RoadColorer),
+    // Everywhere reachable from one building, and the isochrone polygons
+    Isochrone(RoadColorer, Drawable),
     // Only set by certain gameplay modes
     BusRoute(ShowBusRoute),
     BusDelaysOverTime(Plot<Duration>),
@@ -88,7 +94,7 @@ impl Overlays {
         let now = ui.primary.sim.time();
         match self {
             // Don't bother with Inactive, BusRoute, BusDelaysOverTime, BikeNetwork, BikePathCosts,
-            // BusNetwork -- nothing needed or the gameplay mode will update it.
+            // BusNetwork, Isochrone -- nothing needed or the gameplay mode will update it.
             Overlays::ParkingAvailability(t, _) if now != *t => {
                 *self = Overlays::parking_availability(ctx, ui);
             }
@@ -126,6 +132,11 @@ impl Overlays {
                 heatmap.draw(g, ui);
                 true
             }
+            Overlays::Isochrone(ref heatmap, ref draw) => {
+                heatmap.draw(g, ui);
+                g.redraw(draw);
+                true
+            }
             Overlays::IntersectionDelay(_, ref heatmap)
             | Overlays::CumulativeThroughput(_, ref heatmap)
             | Overlays::Chokepoints(_, ref heatmap) => {
@@ -434,6 +445,53 @@ impl Overlays {
         Overlays::BikeNetwork(colorer.build(ctx, &ui.primary.map))
     }
 
+    pub fn isochrone(
+        b: BuildingID,
+        constraints: PathConstraints,
+        use_transit: bool,
+        ctx: &EventCtx,
+        ui: &UI,
+    ) -> Overlays {
+        let map = &ui.primary.map;
+        let thresholds = vec![
+            Duration::minutes(5),
+            Duration::minutes(10),
+            Duration::minutes(15),
+        ];
+        let colors = vec![Color::GREEN, Color::YELLOW, Color::RED];
+        let mut colorer = RoadColorerBuilder::new(
+            Text::prompt(&format!("reachable from {}", b)),
+            vec![
+                ("<= 5 minutes", colors[0]),
+                ("<= 10 minutes", colors[1]),
+                ("<= 15 minutes", colors[2]),
+            ],
+        );
+        let mut batch = GeomBatch::new();
+        if let Some(reachable) = Reachability::from_building(
+            b,
+            constraints,
+            use_transit,
+            Budget::Time(*thresholds.last().unwrap()),
+            map,
+        ) {
+            for (l, reached) in &reachable.lanes {
+                let idx = thresholds.iter().position(|t| reached.time <= *t).unwrap();
+                colorer.add(*l, colors[idx], map);
+            }
+            // Draw the biggest first, so the smaller ones show up on top
+            for (t, poly) in reachable
+                .isochrones(thresholds.clone(), map)
+                .into_iter()
+                .rev()
+            {
+                let idx = thresholds.iter().position(|x| *x == t).unwrap();
+                batch.push(colors[idx].alpha(0.2), poly);
+            }
+        }
+        Overlays::Isochrone(colorer.build(ctx, map), ctx.prerender.upload(batch))
+    }
+
     fn bus_network(ctx: &EventCtx, ui: &UI) -> Overlays {
         let mut colorer = RoadColorerBuilder::new(
             Text::prompt("bus networks"),
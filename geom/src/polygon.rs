@@ -191,6 +191,47 @@ impl Polygon {
         Some(Polygon::rectangle_topleft(Pt2D::new(x1, y1), width, height))
     }
 
+    // The smallest convex polygon containing every point. None if the points are all on a line.
+    pub fn convex_hull(pts: &[Pt2D]) -> Option<Polygon> {
+        let mut pts = pts.to_vec();
+        pts.sort_by(|a, b| {
+            a.x()
+                .partial_cmp(&b.x())
+                .unwrap()
+                .then(a.y().partial_cmp(&b.y()).unwrap())
+        });
+        pts.dedup_by(|a, b| a.x() == b.x() && a.y() == b.y());
+
+        // Andrew's monotone chain. Cross product of (a -> b) and (a -> c); positive means
+        // counter-clockwise.
+        let cross = |a: Pt2D, b: Pt2D, c: Pt2D| {
+            (b.x() - a.x()) * (c.y() - a.y()) - (b.y() - a.y()) * (c.x() - a.x())
+        };
+        let mut hull: Vec<Pt2D> = Vec::new();
+        for pass in 0..2 {
+            let start = hull.len();
+            let iter: Box<dyn Iterator<Item = &Pt2D>> = if pass == 0 {
+                Box::new(pts.iter())
+            } else {
+                Box::new(pts.iter().rev())
+            };
+            for pt in iter {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], *pt) <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(*pt);
+            }
+            // The last point of each half is the first of the other.
+            hull.pop();
+        }
+        if hull.len() < 3 {
+            return None;
+        }
+        Some(Polygon::new(&hull))
+    }
+
     pub fn union(self, other: Polygon) -> Polygon {
         let mut points = self.points;
         let mut indices = self.indices;
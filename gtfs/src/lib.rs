@@ -1,16 +1,92 @@
 use abstutil::elapsed_seconds;
-use failure::Error;
-use geom::LonLat;
+use failure::{err_msg, Error};
+use geom::{Duration, LonLat};
 use itertools::Itertools;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
+use std::path::Path;
 use std::time::Instant;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Route {
     pub name: String,
     pub stops: Vec<LonLat>,
+    // Every trip along this route, in either direction, sorted by departure time.
+    pub trips: Vec<Trip>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Trip {
+    // The service days from calendar.txt. Exceptions from calendar_dates.txt and the date range
+    // are ignored, except for services only defined in calendar_dates.txt; those run on every day
+    // of the week that they're ever added.
+    pub days: BTreeSet<Weekday>,
+    pub stop_times: Vec<StopTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct StopTime {
+    pub stop: LonLat,
+    // Since midnight at the start of the service day, so these can exceed 24 hours.
+    pub arrival: Duration,
+    pub departure: Duration,
+    // If true, the bus shouldn't leave before departure. Otherwise the times are approximate.
+    pub timepoint: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    // In the same order as the columns of calendar.txt
+    pub fn all() -> Vec<Weekday> {
+        vec![
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+            Weekday::Thursday,
+            Weekday::Friday,
+            Weekday::Saturday,
+            Weekday::Sunday,
+        ]
+    }
+
+    // GTFS dates look like YYYYMMDD.
+    pub fn from_date(s: &str) -> Result<Weekday, Error> {
+        let s = s.trim();
+        if s.len() != 8 {
+            return Err(err_msg(format!("Bad GTFS date {}", s)));
+        }
+        let mut year: i64 = s[0..4].parse()?;
+        let month: usize = s[4..6].parse()?;
+        let day: i64 = s[6..8].parse()?;
+        if !(1..=12).contains(&month) {
+            return Err(err_msg(format!("Bad GTFS date {}", s)));
+        }
+        // Sakamoto's method, where 0 is Sunday
+        let offsets = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+        if month < 3 {
+            year -= 1;
+        }
+        let dow = (year + year / 4 - year / 100 + year / 400 + offsets[month - 1] + day) % 7;
+        Ok(Weekday::all()[((dow + 6) % 7) as usize])
+    }
+
+    pub fn parse(s: &str) -> Result<Weekday, Error> {
+        Weekday::all()
+            .into_iter()
+            .find(|day| format!("{:?}", day).to_lowercase() == s.to_lowercase())
+            .ok_or_else(|| err_msg(format!("Unknown day of the week {}", s)))
+    }
 }
 
 pub fn load(dir_path: &str) -> Result<Vec<Route>, Error> {
@@ -31,31 +107,99 @@ pub fn load(dir_path: &str) -> Result<Vec<Route>, Error> {
         stop_id_to_pt.insert(rec[0].to_string(), LonLat::new(lon, lat));
     }
 
-    let mut trip_id_to_route_id_and_direction: HashMap<String, (String, bool)> = HashMap::new();
+    // Feeds need calendar.txt, calendar_dates.txt, or both.
+    let mut service_id_to_days: HashMap<String, BTreeSet<Weekday>> = HashMap::new();
+    let calendar_path = format!("{}/calendar.txt", dir_path);
+    if Path::new(&calendar_path).exists() {
+        for rec in csv::Reader::from_reader(File::open(calendar_path)?).records() {
+            let rec = rec?;
+            let mut days = BTreeSet::new();
+            for (idx, day) in Weekday::all().into_iter().enumerate() {
+                if &rec[idx + 1] == "1" {
+                    days.insert(day);
+                }
+            }
+            service_id_to_days.insert(rec[0].to_string(), days);
+        }
+    }
+    let calendar_dates_path = format!("{}/calendar_dates.txt", dir_path);
+    if Path::new(&calendar_dates_path).exists() {
+        let mut reader = csv::Reader::from_reader(File::open(calendar_dates_path)?);
+        let headers = reader.headers()?.clone();
+        let col = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| err_msg(format!("calendar_dates.txt is missing {}", name)))
+        };
+        let (service_col, date_col, exception_col) =
+            (col("service_id")?, col("date")?, col("exception_type")?);
+        let mut added_days: HashMap<String, BTreeSet<Weekday>> = HashMap::new();
+        for rec in reader.records() {
+            let rec = rec?;
+            // 1 means service is added on that date
+            if &rec[exception_col] == "1" {
+                added_days
+                    .entry(rec[service_col].to_string())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(Weekday::from_date(&rec[date_col])?);
+            }
+        }
+        for (service_id, days) in added_days {
+            service_id_to_days.entry(service_id).or_insert(days);
+        }
+    }
+
+    // Trip ID to (route ID, direction, service ID)
+    let mut trip_id_to_route_id_and_direction: HashMap<String, (String, bool, String)> =
+        HashMap::new();
     for rec in csv::Reader::from_reader(File::open(format!("{}/trips.txt", dir_path))?).records() {
         let rec = rec?;
-        trip_id_to_route_id_and_direction
-            .insert(rec[2].to_string(), (rec[0].to_string(), &rec[5] == "0"));
+        trip_id_to_route_id_and_direction.insert(
+            rec[2].to_string(),
+            (rec[0].to_string(), &rec[5] == "0", rec[1].to_string()),
+        );
     }
 
-    // Each (directed) route has many trips. Ignore all but the first and assume the list of stops
-    // is the same. Also assume that records with the same trip are contiguous and that
-    // stop_sequence is monotonic.
+    // Each (directed) route has many trips. Use the first for the list of stops along the route,
+    // and assume the others mostly match. Also assume that records with the same trip are
+    // contiguous and that stop_sequence is monotonic.
     let mut directed_routes: HashMap<(String, bool), Vec<LonLat>> = HashMap::new();
+    let mut trips_per_route: HashMap<String, Vec<Trip>> = HashMap::new();
     let mut reader = csv::Reader::from_reader(File::open(format!("{}/stop_times.txt", dir_path))?);
+    // timepoint is optional, and when it's missing, all times are supposed to be exact.
+    let timepoint_col = reader.headers()?.iter().position(|h| h == "timepoint");
     for (key, group) in reader
         .records()
         .group_by(|rec| rec.as_ref().unwrap()[0].to_string())
         .into_iter()
     {
-        let (route_id, forwards) = trip_id_to_route_id_and_direction[&key].clone();
-        if directed_routes.contains_key(&(route_id.clone(), forwards)) {
-            continue;
+        let (route_id, forwards, service_id) = trip_id_to_route_id_and_direction[&key].clone();
+        // Stops that aren't timepoints can leave the times blank.
+        let mut raw_times = Vec::new();
+        for rec in group {
+            let rec = rec?;
+            raw_times.push((
+                stop_id_to_pt[&rec[3]],
+                maybe_parse_time(&rec[1])?,
+                maybe_parse_time(&rec[2])?,
+                timepoint_col.map(|col| &rec[col] != "0").unwrap_or(true),
+            ));
         }
-        directed_routes.insert(
-            (route_id, forwards),
-            group.map(|rec| stop_id_to_pt[&rec.unwrap()[3]]).collect(),
-        );
+        let stop_times = interpolate_times(&key, raw_times)?;
+        directed_routes
+            .entry((route_id.clone(), forwards))
+            .or_insert_with(|| stop_times.iter().map(|st| st.stop).collect());
+        trips_per_route
+            .entry(route_id)
+            .or_insert_with(Vec::new)
+            .push(Trip {
+                days: service_id_to_days
+                    .get(&service_id)
+                    .cloned()
+                    .unwrap_or_else(BTreeSet::new),
+                stop_times,
+            });
     }
 
     // Group together the pairs of directed routes
@@ -72,9 +216,17 @@ pub fn load(dir_path: &str) -> Result<Vec<Route>, Error> {
             stops.extend(more_stops);
         }
         assert!(!stops.is_empty());
+        let mut trips = trips_per_route.remove(&route_id).unwrap_or_else(Vec::new);
+        trips.sort_by(|t1, t2| {
+            t1.stop_times[0]
+                .departure
+                .partial_cmp(&t2.stop_times[0].departure)
+                .unwrap()
+        });
         results.push(Route {
             name: route_id_to_name[&route_id].to_string(),
             stops,
+            trips,
         });
     }
     assert!(directed_routes.is_empty());
@@ -82,3 +234,85 @@ pub fn load(dir_path: &str) -> Result<Vec<Route>, Error> {
     println!("Loading GTFS took {}s", elapsed_seconds(timer));
     Ok(results)
 }
+
+// Blank times are None.
+fn maybe_parse_time(s: &str) -> Result<Option<Duration>, Error> {
+    if s.trim().is_empty() {
+        Ok(None)
+    } else {
+        parse_time(s).map(Some)
+    }
+}
+
+// Fill in blank times by assuming the bus moves at a constant speed between the stops with times.
+// The first and last stops of every trip must have times.
+fn interpolate_times(
+    trip: &str,
+    raw: Vec<(LonLat, Option<Duration>, Option<Duration>, bool)>,
+) -> Result<Vec<StopTime>, Error> {
+    // Arrival and departure are both required if either is there.
+    let known: Vec<Option<(Duration, Duration)>> = raw
+        .iter()
+        .map(|(_, arrival, departure, _)| match (arrival, departure) {
+            (Some(a), Some(d)) => Some((*a, *d)),
+            (Some(t), None) | (None, Some(t)) => Some((*t, *t)),
+            (None, None) => None,
+        })
+        .collect();
+    if known.is_empty() || known[0].is_none() || known[known.len() - 1].is_none() {
+        return Err(err_msg(format!(
+            "Trip {} needs times at its first and last stop",
+            trip
+        )));
+    }
+    // Straight-line distance from the start of the trip to each stop
+    let mut dist_so_far = Vec::new();
+    let mut total = 0.0;
+    for (idx, (pt, _, _, _)) in raw.iter().enumerate() {
+        if idx > 0 {
+            total += raw[idx - 1].0.gps_dist_meters(*pt).inner_meters();
+        }
+        dist_so_far.push(total);
+    }
+
+    let mut stop_times = Vec::new();
+    let mut prev = 0;
+    for (idx, (stop, _, _, timepoint)) in raw.iter().enumerate() {
+        let (arrival, departure) = if let Some(times) = known[idx] {
+            prev = idx;
+            times
+        } else {
+            let next = (idx..raw.len()).find(|i| known[*i].is_some()).unwrap();
+            let (_, leave) = known[prev].unwrap();
+            let (arrive, _) = known[next].unwrap();
+            let span = dist_so_far[next] - dist_so_far[prev];
+            let pct = if span > 0.0 {
+                (dist_so_far[idx] - dist_so_far[prev]) / span
+            } else {
+                ((idx - prev) as f64) / ((next - prev) as f64)
+            };
+            let t = leave + (arrive - leave) * pct;
+            (t, t)
+        };
+        stop_times.push(StopTime {
+            stop: *stop,
+            arrival,
+            departure,
+            // Interpolated times are never exact.
+            timepoint: *timepoint && known[idx].is_some(),
+        });
+    }
+    Ok(stop_times)
+}
+
+// GTFS times look like HH:MM:SS, and the hours can go past 24.
+fn parse_time(s: &str) -> Result<Duration, Error> {
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() != 3 {
+        return Err(err_msg(format!("Bad GTFS time {}", s)));
+    }
+    let hours: f64 = parts[0].parse()?;
+    let minutes: f64 = parts[1].parse()?;
+    let seconds: f64 = parts[2].parse()?;
+    Ok(Duration::seconds(hours * 3600.0 + minutes * 60.0 + seconds))
+}
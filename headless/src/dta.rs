@@ -0,0 +1,233 @@
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{Map, Path, PathConstraints, PathRequest, Position};
+use sim::{Scenario, Sim, SimFlags, TripID};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+pub struct Options {
+    pub max_iterations: usize,
+    // What fraction of the drivers can switch to a better route after each iteration
+    pub reroute_fraction: f64,
+    // Stop once the total time drivers could save by switching routes drops below this fraction
+    // of the total time they spent
+    pub relative_gap: f64,
+    // Or stop once the total time drivers spent changes by less than this fraction
+    pub travel_time_change: f64,
+    pub save_routes: Option<String>,
+    pub save_stats: Option<String>,
+}
+
+struct Iteration {
+    finished_trips: usize,
+    aborted_trips: usize,
+    car_trips: usize,
+    total_car_time: Duration,
+    relative_gap: f64,
+    rerouted: usize,
+}
+
+// Iterative dynamic traffic assignment. Run the same scenario repeatedly. After each run, find the
+// travel time of every lane throughout the day, then move some drivers to routes that would've
+// been faster.
+pub fn run(sim_flags: &SimFlags, num_agents: Option<usize>, opts: Options) {
+    let mut timer = Timer::new("setup traffic assignment");
+    let (map, scenario) = load_scenario(sim_flags, num_agents, &mut timer);
+    // Every iteration has to make the same random choices.
+    let mut flags = sim_flags.clone();
+    flags.rng_seed.get_or_insert(42);
+    timer.done();
+
+    let mut routes: BTreeMap<TripID, Path> = BTreeMap::new();
+    let mut iterations: Vec<Iteration> = Vec::new();
+    loop {
+        let mut timer = Timer::new(&format!(
+            "traffic assignment iteration {}",
+            iterations.len()
+        ));
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+        sim.assign_car_routes(routes);
+        sim.record_car_routes();
+        scenario.instantiate(&mut sim, &map, &mut flags.make_rng(), &mut timer);
+        timer.done();
+        sim.run_until_done(&map, |_, _| {}, None);
+
+        let (next_routes, iteration) = reroute(&sim, &map, opts.reroute_fraction);
+        routes = next_routes;
+        println!(
+            "Iteration {}: {} finished trips, {} aborted, average car trip {}, relative gap {}, \
+             {} rerouted",
+            iterations.len(),
+            iteration.finished_trips,
+            iteration.aborted_trips,
+            average(iteration.total_car_time, iteration.car_trips),
+            iteration.relative_gap,
+            iteration.rerouted
+        );
+
+        let mut converged = iteration.relative_gap < opts.relative_gap;
+        if let Some(prev) = iterations.last() {
+            converged |= relative_change(prev.total_car_time, iteration.total_car_time)
+                < opts.travel_time_change;
+        }
+        iterations.push(iteration);
+        if converged {
+            println!("Converged after {} iterations", iterations.len());
+            // The routes just experienced, not the ones that would've been used next
+            routes = sim
+                .get_car_routes()
+                .iter()
+                .map(|(trip, (_, _, path))| (*trip, path.clone()))
+                .collect();
+            break;
+        }
+        if iterations.len() == opts.max_iterations {
+            println!(
+                "Didn't converge after {} iterations, stopping anyway",
+                iterations.len()
+            );
+            break;
+        }
+    }
+
+    if let Some(path) = opts.save_routes {
+        abstutil::write_binary(&path, &routes).unwrap();
+        println!("Saved routes to {}", path);
+    }
+    if let Some(path) = opts.save_stats {
+        save_stats(&path, &iterations).unwrap();
+        println!("Saved statistics for every iteration to {}", path);
+    }
+}
+
+fn load_scenario(
+    sim_flags: &SimFlags,
+    num_agents: Option<usize>,
+    timer: &mut Timer,
+) -> (Map, Scenario) {
+    if sim_flags.load.starts_with("../data/scenarios/") {
+        let scenario: Scenario =
+            abstutil::read_binary(&sim_flags.load, timer).expect("loading scenario failed");
+        let mut flags = sim_flags.clone();
+        flags.load = abstutil::path_map(&scenario.map_name);
+        let (map, _, _) = flags.load(timer);
+        (map, scenario)
+    } else if sim_flags.load.starts_with("../data/maps/")
+        || sim_flags.load.starts_with("../data/raw_maps/")
+    {
+        let (map, _, _) = sim_flags.load(timer);
+        let scenario = if let Some(n) = num_agents {
+            Scenario::scaled_run(&map, n)
+        } else {
+            Scenario::small_run(&map)
+        };
+        (map, scenario)
+    } else {
+        panic!(
+            "--dta needs to start from a scenario or map, not {}",
+            sim_flags.load
+        );
+    }
+}
+
+// Returns the routes for the next iteration. Using the travel times just observed, compare the
+// route each driver took to the best route they could've taken, then switch the drivers who
+// would've saved the most.
+fn reroute(sim: &Sim, map: &Map, fraction: f64) -> (BTreeMap<TripID, Path>, Iteration) {
+    let table = sim.get_analytics().travel_time_table(Duration::minutes(15));
+    let mut routes = BTreeMap::new();
+    let mut better: Vec<(Duration, TripID, Path)> = Vec::new();
+    let mut total_car_time = Duration::ZERO;
+    let mut total_best_time = Duration::ZERO;
+    for (trip, (depart, start, path)) in sim.get_car_routes() {
+        let current = table.route_cost(path, *depart, map);
+        total_car_time += current;
+        routes.insert(*trip, path.clone());
+
+        let req = PathRequest {
+            start: *start,
+            end: Position::new(path.last_step().as_lane(), path.end_dist()),
+            constraints: PathConstraints::Car,
+        };
+        let best = table.pathfind(&req, map, *depart).and_then(|best| {
+            let cost = table.route_cost(&best, *depart, map);
+            if cost < current {
+                Some((cost, best))
+            } else {
+                None
+            }
+        });
+        if let Some((cost, best)) = best {
+            total_best_time += cost;
+            better.push((current - cost, *trip, best));
+        } else {
+            total_best_time += current;
+        }
+    }
+
+    better.sort_by(|(s1, _, _), (s2, _, _)| s2.partial_cmp(s1).unwrap());
+    better.truncate(((routes.len() as f64) * fraction).ceil() as usize);
+    let rerouted = better.len();
+    for (_, trip, path) in better {
+        routes.insert(trip, path);
+    }
+
+    let results = sim.get_finished_trips();
+    let iteration = Iteration {
+        finished_trips: results.finished_trips.len(),
+        aborted_trips: results.aborted_trips,
+        car_trips: sim.get_car_routes().len(),
+        total_car_time,
+        relative_gap: if total_car_time == Duration::ZERO {
+            0.0
+        } else {
+            (total_car_time - total_best_time) / total_car_time
+        },
+        rerouted,
+    };
+    (routes, iteration)
+}
+
+fn save_stats(path: &str, iterations: &[Iteration]) -> Result<(), std::io::Error> {
+    let mut f = File::create(path)?;
+    writeln!(
+        f,
+        "iteration,finished_trips,aborted_trips,car_trips,avg_car_travel_time,relative_gap,\
+         rerouted"
+    )?;
+    for (idx, i) in iterations.iter().enumerate() {
+        writeln!(
+            f,
+            "{},{},{},{},{},{},{}",
+            idx,
+            i.finished_trips,
+            i.aborted_trips,
+            i.car_trips,
+            average(i.total_car_time, i.car_trips).inner_seconds(),
+            i.relative_gap,
+            i.rerouted
+        )?;
+    }
+    Ok(())
+}
+
+fn average(total: Duration, count: usize) -> Duration {
+    if count == 0 {
+        Duration::ZERO
+    } else {
+        total * (1.0 / (count as f64))
+    }
+}
+
+fn relative_change(before: Duration, after: Duration) -> f64 {
+    if before == Duration::ZERO {
+        return 0.0;
+    }
+    let change = if after > before {
+        after - before
+    } else {
+        before - after
+    };
+    change / before
+}
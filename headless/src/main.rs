@@ -1,6 +1,12 @@
+mod dta;
+
 use abstutil::{CmdArgs, Timer};
-use geom::Duration;
-use sim::{GetDrawAgents, Scenario, SimFlags};
+use geom::{Duration, Speed};
+use map_model::{
+    Budget, BuildingID, IntersectionID, Map, MapEdits, PathConstraints, Reachability, TurnPenalties,
+};
+use popdat::{Skims, Zone};
+use sim::{GetDrawAgents, Scenario, Sim, SimFlags};
 
 fn main() {
     let mut args = CmdArgs::new();
@@ -10,8 +16,153 @@ fn main() {
     let enable_profiler = args.enabled("--enable_profiler");
     // Every 0.1s, pretend to draw everything to make sure there are no bugs.
     let paranoia = args.enabled("--paranoia");
+    // After the run, write a CSV of observed lane travel times per 15 minutes. Pass it to a later
+    // run with --travel_times to route cars around congestion.
+    let save_travel_times = args.optional("--save_travel_times");
+    // After the run, write a CSV of the average delay at each intersection. Pass it to a later run
+    // with --turn_penalties to make vehicles avoid slow intersections.
+    let save_intersection_delays = args.optional("--save_intersection_delays");
+    // After the run, write a CSV grading how crowded each sidewalk got per 15 minutes.
+    let save_ped_los = args.optional("--save_ped_los");
+    // Instead of one normal run, score green wave plans for a corridor of intersections. Takes a
+    // comma-separated list of intersection IDs, then the progression speeds (in mph) and optional
+    // common cycle lengths (in seconds) to try.
+    let green_wave: Option<Vec<usize>> = args.optional_parse("--green_wave", parse_list);
+    let progression_speeds = args
+        .optional_parse("--progression_speeds", parse_list)
+        .unwrap_or_else(|| vec![25.0]);
+    let cycle_lengths = args.optional_parse("--cycle_lengths", parse_list);
+    // Instead of one normal run, repeat the run and move some drivers to faster routes each time,
+    // until the travel times settle down. --save_routes keeps the final routes; a later run can
+    // use them with --car_routes.
+    let dta = args.enabled("--dta");
+    let dta_opts = dta::Options {
+        max_iterations: args
+            .optional_parse("--max_iterations", |s| s.parse())
+            .unwrap_or(10),
+        reroute_fraction: args
+            .optional_parse("--reroute_fraction", |s| s.parse())
+            .unwrap_or(0.1),
+        relative_gap: args
+            .optional_parse("--relative_gap", |s| s.parse())
+            .unwrap_or(0.01),
+        travel_time_change: args
+            .optional_parse("--travel_time_change", |s| s.parse())
+            .unwrap_or(0.005),
+        save_routes: args.optional("--save_routes"),
+        save_stats: args.optional("--save_dta_stats"),
+    };
+    // Instead of running the simulation, find everywhere reachable from a building within some
+    // minutes, and save the time and distance to reach each lane as a CSV. The mode is walk,
+    // transit, bike, or drive.
+    let isochrone_from = args.optional_parse("--isochrone_from", |s| s.parse::<usize>());
+    let isochrone_mode = args
+        .optional("--isochrone_mode")
+        .unwrap_or_else(|| "walk".to_string());
+    let isochrone_minutes = args
+        .optional_parse("--isochrone_minutes", |s| s.parse::<usize>())
+        .unwrap_or(15);
+    let isochrone_output = args
+        .optional("--isochrone_output")
+        .unwrap_or_else(|| "isochrone.csv".to_string());
+    // Instead of running the simulation, compute zone-to-zone travel time, distance, and transfers
+    // for every mode. Zones are the map's neighborhoods, or shapes from a KML file or extra shapes
+    // .bin. Driving uses the travel times passed in with --travel_times, if any. Writes
+    // <skims_output>.csv and <skims_output>.bin.
+    let skims = args.optional("--skims");
+    let zone_name_attribute = args.optional("--zone_name_attribute");
+    let skim_samples = args
+        .optional_parse("--skim_samples", |s| s.parse::<usize>())
+        .unwrap_or(3);
+    let skim_max_minutes = args
+        .optional_parse("--skim_max_minutes", |s| s.parse::<usize>())
+        .unwrap_or(90);
+    let skim_depart = args
+        .optional_parse("--skim_depart", Duration::parse)
+        .unwrap_or_else(|| Duration::minutes(7 * 60));
+    let skims_output = args
+        .optional("--skims_output")
+        .unwrap_or_else(|| "skims".to_string());
     args.done();
 
+    if let Some(zones) = skims {
+        let mut timer = Timer::new("compute skims");
+        let (map, _, _) = sim_flags.load(&mut timer);
+        let zones = if zones == "neighborhoods" {
+            Zone::from_neighborhoods(&map)
+        } else {
+            let shapes = if zones.ends_with(".kml") {
+                kml::load(&zones, map.get_gps_bounds(), &mut timer)
+            } else {
+                abstutil::read_binary(&zones, &mut timer)
+            }
+            .expect(&format!("couldn't load zones from {}", zones));
+            Zone::from_shapes(shapes, zone_name_attribute, &map)
+        };
+        let skims = Skims::new(
+            &map,
+            &zones,
+            skim_samples,
+            Duration::minutes(skim_max_minutes),
+            skim_depart,
+            &mut timer,
+        );
+        skims.save_csv(&format!("{}.csv", skims_output)).unwrap();
+        abstutil::write_binary(&format!("{}.bin", skims_output), &skims).unwrap();
+        timer.done();
+        println!(
+            "Skims between {} zones saved to {}.csv and {}.bin",
+            skims.zones.len(),
+            skims_output,
+            skims_output
+        );
+        return;
+    }
+
+    if let Some(b) = isochrone_from {
+        let (map, _, _) = sim_flags.load(&mut Timer::new("setup isochrone"));
+        let (constraints, use_transit) = match isochrone_mode.as_ref() {
+            "walk" => (PathConstraints::Pedestrian, false),
+            "transit" => (PathConstraints::Pedestrian, true),
+            "bike" => (PathConstraints::Bike, false),
+            "drive" => (PathConstraints::Car, false),
+            x => panic!("Bad --isochrone_mode={}", x),
+        };
+        let reachable = Reachability::from_building(
+            BuildingID(b),
+            constraints,
+            use_transit,
+            Budget::Time(Duration::minutes(isochrone_minutes)),
+            &map,
+        )
+        .expect(&format!("Can't {} from {}", isochrone_mode, BuildingID(b)));
+        reachable.save_csv(&isochrone_output).unwrap();
+        println!(
+            "{} lanes reachable within {} minutes, saved to {}",
+            reachable.lanes.len(),
+            isochrone_minutes,
+            isochrone_output
+        );
+        return;
+    }
+
+    if dta {
+        dta::run(&sim_flags, num_agents, dta_opts);
+        return;
+    }
+
+    if let Some(corridor) = green_wave {
+        let corridor: Vec<IntersectionID> = corridor.into_iter().map(IntersectionID).collect();
+        score_green_waves(
+            &sim_flags,
+            num_agents,
+            corridor,
+            progression_speeds,
+            cycle_lengths,
+        );
+        return;
+    }
+
     let mut timer = Timer::new("setup headless");
     let (map, mut sim, mut rng) = sim_flags.load(&mut timer);
 
@@ -61,6 +212,25 @@ fn main() {
     );
     timer.done();
     println!("Done at {}", sim.time());
+    if let Some(path) = save_travel_times {
+        sim.get_analytics()
+            .travel_time_table(Duration::minutes(15))
+            .save_csv(&path)
+            .unwrap();
+        println!("Saved travel times to {}", path);
+    }
+    if let Some(path) = save_intersection_delays {
+        TurnPenalties::measured(sim.get_analytics().average_intersection_delays(sim.time()))
+            .save_measured_delays_csv(&path)
+            .unwrap();
+        println!("Saved intersection delays to {}", path);
+    }
+    if let Some(path) = save_ped_los {
+        sim.get_analytics()
+            .save_ped_los_csv(&path, Duration::minutes(15), &map)
+            .unwrap();
+        println!("Saved pedestrian level-of-service to {}", path);
+    }
     if enable_profiler && save_at.is_none() {
         #[cfg(feature = "profiler")]
         {
@@ -68,3 +238,95 @@ fn main() {
         }
     }
 }
+
+fn parse_list<T: std::str::FromStr>(s: &str) -> Result<Vec<T>, T::Err> {
+    s.split(',').map(|x| x.parse::<T>()).collect()
+}
+
+fn score_green_waves(
+    sim_flags: &SimFlags,
+    num_agents: Option<usize>,
+    corridor: Vec<IntersectionID>,
+    progression_speeds: Vec<f64>,
+    cycle_lengths: Option<Vec<f64>>,
+) {
+    let mut timer = Timer::new("setup green wave plans");
+    if !sim_flags.load.starts_with("../data/maps/")
+        && !sim_flags.load.starts_with("../data/raw_maps/")
+    {
+        panic!(
+            "--green_wave needs to start from a map, not {}",
+            sim_flags.load
+        );
+    }
+    let (mut map, _, _) = sim_flags.load(&mut timer);
+    let scenario = if let Some(n) = num_agents {
+        Scenario::scaled_run(&map, n)
+    } else {
+        Scenario::small_run(&map)
+    };
+
+    let mut plans: Vec<(String, MapEdits)> =
+        vec![("current signals".to_string(), map.get_edits().clone())];
+    let cycles: Vec<Option<Duration>> = match cycle_lengths {
+        Some(list) => list
+            .into_iter()
+            .map(|c| Some(Duration::seconds(c)))
+            .collect(),
+        None => vec![None],
+    };
+    for mph in progression_speeds {
+        for cycle in &cycles {
+            let name = match cycle {
+                Some(c) => format!("{} mph, {} cycle", mph, c),
+                None => format!("{} mph, original cycles", mph),
+            };
+            match map_model::green_wave::coordinate_signals(
+                &map,
+                &corridor,
+                Speed::miles_per_hour(mph),
+                *cycle,
+            ) {
+                Ok(edits) => plans.push((name, edits)),
+                Err(err) => println!("Skipping {}: {}", name, err),
+            }
+        }
+    }
+    timer.done();
+
+    let mut results = Vec::new();
+    for (name, edits) in plans {
+        let mut timer = Timer::new(&format!("score {}", name));
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        let mut sim = Sim::new(&map, sim_flags.opts.clone(), &mut timer);
+        let mut rng = sim_flags.make_rng();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+        timer.done();
+
+        sim.run_until_done(&map, |_, _| {}, None);
+        results.push((name, corridor_delay(&sim, &map, &corridor)));
+    }
+
+    println!("Total delay along the corridor, for each plan:");
+    results.sort_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap());
+    for (name, delay) in results {
+        println!("  {}: {}", name, delay);
+    }
+}
+
+fn corridor_delay(sim: &Sim, map: &Map, corridor: &[IntersectionID]) -> Duration {
+    let analytics = sim.get_analytics();
+    let mut total = Duration::ZERO;
+    for i in corridor {
+        if map.maybe_get_traffic_signal(*i).is_none() {
+            continue;
+        }
+        if let Some(list) = analytics.intersection_delays.get(i) {
+            for (_, delay) in list {
+                total += *delay;
+            }
+        }
+    }
+    total
+}
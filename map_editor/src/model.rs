@@ -239,6 +239,7 @@ impl Model {
             RawIntersection {
                 point,
                 intersection_type: IntersectionType::StopSign,
+                elevation: Distance::ZERO,
             },
         );
         self.intersection_added(id, prerender);
@@ -609,6 +610,7 @@ impl Model {
             LaneType::Biking => Color::rgb(15, 125, 75),
             LaneType::SharedLeftTurn => Color::YELLOW,
             LaneType::Construction => Color::rgb(255, 109, 0),
+            LaneType::LightRail => Color::rgb(145, 120, 93),
         };
         if unset {
             match color {
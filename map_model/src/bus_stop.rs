@@ -1,5 +1,8 @@
-use crate::{LaneID, Position};
+use crate::{LaneID, PathConstraints, Position};
+use geom::Duration;
+use gtfs::Weekday;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -40,9 +43,33 @@ impl BusStop {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct BusRoute {
     pub id: BusRouteID,
     pub name: String,
     pub stops: Vec<BusStopID>,
+    // Empty if there's no timetable for this route. Then the simulation just has a few buses loop
+    // around the stops forever.
+    pub timetable: Vec<ScheduledTrip>,
+    // Bus for bus routes, Train for light rail
+    pub constraints: PathConstraints,
+    // How many passengers fit in each vehicle serving this route. None means the simulation's
+    // default for the type of vehicle.
+    pub capacity: Option<usize>,
+}
+
+// One run of a bus from the timetable, serving some of the route's stops in order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledTrip {
+    pub days: BTreeSet<Weekday>,
+    pub stops: Vec<ScheduledStop>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledStop {
+    pub stop: BusStopID,
+    pub arrival: Duration,
+    pub departure: Duration,
+    // If true, a bus that's running early waits here until the scheduled departure.
+    pub timepoint: bool,
 }
@@ -0,0 +1,126 @@
+use crate::{EditCmd, IntersectionID, Map, MapEdits, Phase, RoadID, SignalMode, TurnGroupID};
+use geom::{Duration, Speed};
+
+// Coordinate the traffic signals along a corridor, so that a platoon released from the first
+// signal at the start of its green keeps hitting green as long as it travels at the progression
+// speed. The corridor is an ordered list of intersections, each connected to the next by a road.
+// Intersections without a traffic signal are just passed through.
+//
+// If cycle_length is specified, every signal's phases are scaled to it. Otherwise the signals
+// keep their own cycles, which only produces a green wave if they happen to match.
+//
+// Offsets are meaningless for actuated signals, so any of those along the corridor become
+// fixed-time. The result is the map's current edits plus a ChangeTrafficSignal for every signal
+// along the corridor; it hasn't been applied yet.
+pub fn coordinate_signals(
+    map: &Map,
+    corridor: &[IntersectionID],
+    progression_speed: Speed,
+    cycle_length: Option<Duration>,
+) -> Result<MapEdits, String> {
+    if corridor.len() < 2 {
+        return Err(format!(
+            "A corridor needs at least 2 intersections, not {}",
+            corridor.len()
+        ));
+    }
+    if progression_speed <= Speed::ZERO {
+        return Err(format!("Bad progression speed {}", progression_speed));
+    }
+    if let Some(cycle) = cycle_length {
+        if cycle <= Duration::ZERO {
+            return Err(format!("Bad cycle length {}", cycle));
+        }
+    }
+
+    let mut roads = Vec::new();
+    for pair in corridor.windows(2) {
+        roads.push(connecting_road(map, pair[0], pair[1])?);
+    }
+
+    let mut edits = map.get_edits().clone();
+    // When the platoon reaches each intersection, relative to leaving the first one
+    let mut arrival = Duration::ZERO;
+    for (idx, i) in corridor.iter().enumerate() {
+        if idx > 0 {
+            arrival += map.get_r(roads[idx - 1]).center_pts.length() / progression_speed;
+        }
+        let orig_signal = if let Some(ts) = map.maybe_get_traffic_signal(*i) {
+            ts
+        } else {
+            continue;
+        };
+
+        let mut signal = orig_signal.clone();
+        signal.mode = SignalMode::FixedTime;
+        let orig_cycle = total_duration(&signal.phases);
+        if let Some(cycle) = cycle_length {
+            for phase in signal.phases.iter_mut() {
+                phase.duration = phase.duration * (cycle / orig_cycle);
+            }
+        }
+        let cycle = cycle_length.unwrap_or(orig_cycle);
+
+        let from = if idx == 0 { None } else { Some(roads[idx - 1]) };
+        let to = roads.get(idx).cloned();
+        let phase_idx = progression_phase(&signal.phases, from, to).ok_or_else(|| {
+            format!(
+                "No phase at {} serves the movement along the corridor ({:?} to {:?})",
+                i, from, to
+            )
+        })?;
+        let green_starts = total_duration(&signal.phases[0..phase_idx]);
+
+        // At time t, the signal is (t + offset) % cycle into its cycle. Pick the offset so the
+        // progression phase starts right when the platoon arrives.
+        let offset = (green_starts - arrival) % cycle;
+        signal.offset = if offset < Duration::ZERO {
+            offset + cycle
+        } else {
+            offset
+        };
+
+        edits
+            .commands
+            .push(EditCmd::ChangeTrafficSignal(signal.validate(map)?));
+    }
+    Ok(edits)
+}
+
+fn connecting_road(map: &Map, i1: IntersectionID, i2: IntersectionID) -> Result<RoadID, String> {
+    map.get_i(i1)
+        .roads
+        .iter()
+        .find(|r| {
+            let road = map.get_r(**r);
+            (road.src_i == i1 && road.dst_i == i2) || (road.src_i == i2 && road.dst_i == i1)
+        })
+        .cloned()
+        .ok_or_else(|| format!("No road connects {} and {}", i1, i2))
+}
+
+// The first phase that lets traffic continue along the corridor, preferring protected movements.
+fn progression_phase(phases: &[Phase], from: Option<RoadID>, to: Option<RoadID>) -> Option<usize> {
+    let matches = |g: &TurnGroupID| {
+        g.crosswalk.is_none()
+            && !g.bike
+            && from.map(|r| g.from == r).unwrap_or(true)
+            && to.map(|r| g.to == r).unwrap_or(true)
+    };
+    phases
+        .iter()
+        .position(|p| p.protected_groups.iter().any(matches))
+        .or_else(|| {
+            phases
+                .iter()
+                .position(|p| p.yield_groups.iter().any(matches))
+        })
+}
+
+fn total_duration(phases: &[Phase]) -> Duration {
+    let mut total = Duration::ZERO;
+    for p in phases {
+        total += p.duration;
+    }
+    total
+}
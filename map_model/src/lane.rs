@@ -1,8 +1,8 @@
-use crate::pathfind;
 use crate::{
     osm, BuildingID, BusStopID, DirectedRoadID, IntersectionID, Map, PathConstraints, Road, RoadID,
     TurnType,
 };
+use crate::{pathfind, road};
 use geom::{Angle, Distance, Line, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
@@ -30,6 +30,7 @@ pub enum LaneType {
     Bus,
     SharedLeftTurn,
     Construction,
+    LightRail,
 }
 
 impl LaneType {
@@ -38,6 +39,7 @@ impl LaneType {
             LaneType::Driving => true,
             LaneType::Biking => true,
             LaneType::Bus => true,
+            LaneType::LightRail => true,
             LaneType::Parking => false,
             LaneType::Sidewalk => false,
             LaneType::SharedLeftTurn => false,
@@ -50,6 +52,7 @@ impl LaneType {
             LaneType::Driving => true,
             LaneType::Biking => true,
             LaneType::Bus => true,
+            LaneType::LightRail => true,
             LaneType::Parking => false,
             LaneType::Sidewalk => true,
             LaneType::SharedLeftTurn => false,
@@ -66,6 +69,7 @@ impl LaneType {
             LaneType::Sidewalk => "a sidewalk",
             LaneType::SharedLeftTurn => "a shared left-turn lane",
             LaneType::Construction => "a lane that's closed for construction",
+            LaneType::LightRail => "a light rail track",
         }
     }
 }
@@ -143,6 +147,15 @@ impl Lane {
         self.lane_center_pts.length()
     }
 
+    // Like Road::grade, but in the direction of this lane. Sidewalks are used both ways.
+    pub fn grade(&self, map: &Map) -> f64 {
+        road::grade_between(
+            map.get_i(self.src_i).elevation,
+            map.get_i(self.dst_i).elevation,
+            self.length(),
+        )
+    }
+
     pub fn dump_debug(&self) {
         println!(
             "\nlet lane_center_l{}_pts = {}",
@@ -181,6 +194,10 @@ impl Lane {
         self.lane_type == LaneType::Bus
     }
 
+    pub fn is_light_rail(&self) -> bool {
+        self.lane_type == LaneType::LightRail
+    }
+
     pub fn is_sidewalk(&self) -> bool {
         self.lane_type == LaneType::Sidewalk
     }
@@ -3,6 +3,7 @@ mod building;
 mod bus_stop;
 pub mod connectivity;
 mod edits;
+pub mod green_wave;
 mod intersection;
 mod lane;
 mod make;
@@ -19,21 +20,25 @@ mod turn;
 
 pub use crate::area::{Area, AreaID, AreaType};
 pub use crate::building::{Building, BuildingID, FrontPath, OffstreetParking};
-pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
+pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID, ScheduledStop, ScheduledTrip};
 pub use crate::edits::{EditCmd, EditEffects, MapEdits};
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::lane::{Lane, LaneID, LaneType, PARKING_SPOT_LENGTH};
 pub use crate::make::RoadSpec;
 pub use crate::map::Map;
 pub use crate::neighborhood::{FullNeighborhoodInfo, Neighborhood, NeighborhoodBuilder};
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::pathfind::{
+    AlternativeRoute, Budget, Path, PathConstraints, PathRequest, PathStep, Reachability, Reached,
+    TravelTimeTable, TurnPenalties,
+};
 pub use crate::road::{DirectedRoadID, Road, RoadID};
 pub use crate::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::traffic_signals::{ControlTrafficSignal, Phase};
+pub use crate::traffic_signals::{ControlTrafficSignal, Phase, SignalMode};
 pub use crate::traversable::{Position, Traversable};
 pub use crate::turn::{Turn, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
 use abstutil::Cloneable;
 use geom::Distance;
+pub use gtfs::Weekday;
 
 pub const LANE_THICKNESS: Distance = Distance::const_meters(2.5);
 
@@ -1,35 +1,32 @@
 use crate::make::sidewalk_finder::find_sidewalk_points;
 use crate::{
     BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints, PathRequest,
-    Position,
+    Position, ScheduledStop, ScheduledTrip,
 };
 use abstutil::{MultiMap, Timer};
-use geom::{Bounds, Distance, GPSBounds, HashablePt2D, Pt2D};
+use geom::{Bounds, Distance, FindClosest, GPSBounds, HashablePt2D, Pt2D};
 use gtfs;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 pub fn make_bus_stops(
     map: &Map,
     bus_routes: &Vec<gtfs::Route>,
+    light_rail_routes: &Vec<gtfs::Route>,
     gps_bounds: &GPSBounds,
     bounds: &Bounds,
     timer: &mut Timer,
 ) -> (BTreeMap<BusStopID, BusStop>, Vec<BusRoute>) {
     timer.start("make bus stops");
     let mut bus_stop_pts: HashSet<HashablePt2D> = HashSet::new();
-    let mut route_lookups: HashMap<String, Vec<HashablePt2D>> = HashMap::new();
     for route in bus_routes {
         for gps in &route.stops {
             if let Some(pt) = Pt2D::from_gps(*gps, gps_bounds) {
-                let hash_pt = pt.to_hashable();
-                bus_stop_pts.insert(hash_pt);
-                route_lookups
-                    .entry(route.name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(hash_pt);
+                bus_stop_pts.insert(pt.to_hashable());
             }
         }
     }
+    let station_positions = find_station_positions(map, light_rail_routes, gps_bounds, bounds);
+    bus_stop_pts.retain(|pt| !station_positions.contains_key(pt));
 
     let mut stops_per_sidewalk: MultiMap<LaneID, (Distance, HashablePt2D)> = MultiMap::new();
     for (pt, pos) in find_sidewalk_points(
@@ -40,7 +37,17 @@ pub fn make_bus_stops(
         timer,
     )
     .into_iter()
-    {
+    .chain(
+        // Platforms are often set back from the street, or in the middle of it, so look much
+        // further for a sidewalk. Walking to the platform takes a while anyway.
+        find_sidewalk_points(
+            bounds,
+            station_positions.keys().cloned().collect(),
+            map.all_lanes(),
+            Distance::meters(100.0),
+            timer,
+        ),
+    ) {
         stops_per_sidewalk.insert(pos.lane(), (pos.dist_along(), pt));
     }
     let mut point_to_stop_id: HashMap<HashablePt2D, BusStopID> = HashMap::new();
@@ -48,57 +55,129 @@ pub fn make_bus_stops(
 
     for (sidewalk_id, dists_set) in stops_per_sidewalk.consume().into_iter() {
         let road = map.get_parent(sidewalk_id);
-        if let Ok(driving_lane) =
-            road.find_closest_lane(sidewalk_id, vec![LaneType::Driving, LaneType::Bus])
-        {
-            let mut dists: Vec<(Distance, HashablePt2D)> = dists_set.into_iter().collect();
-            dists.sort_by_key(|(dist, _)| *dist);
-            for (idx, (dist_along, orig_pt)) in dists.into_iter().enumerate() {
-                let stop_id = BusStopID {
-                    sidewalk: sidewalk_id,
-                    idx,
-                };
-                point_to_stop_id.insert(orig_pt, stop_id);
-                let sidewalk_pos = Position::new(sidewalk_id, dist_along);
-                let driving_pos = sidewalk_pos.equiv_pos(driving_lane, Distance::ZERO, map);
-                bus_stops.insert(
-                    stop_id,
-                    BusStop {
-                        id: stop_id,
-                        sidewalk_pos,
-                        driving_pos,
-                    },
-                );
-            }
-        } else {
-            timer.warn(format!(
-                "Can't find driving lane next to {}: {:?} and {:?}",
-                sidewalk_id, road.children_forwards, road.children_backwards
-            ));
+        let driving_lane =
+            road.find_closest_lane(sidewalk_id, vec![LaneType::Driving, LaneType::Bus]);
+        let mut dists: Vec<(Distance, HashablePt2D)> = dists_set.into_iter().collect();
+        dists.sort_by_key(|(dist, _)| *dist);
+        let mut idx = 0;
+        for (dist_along, orig_pt) in dists {
+            let sidewalk_pos = Position::new(sidewalk_id, dist_along);
+            let driving_pos = if let Some(pos) = station_positions.get(&orig_pt) {
+                *pos
+            } else if let Ok(l) = driving_lane {
+                sidewalk_pos.equiv_pos(l, Distance::ZERO, map)
+            } else {
+                timer.warn(format!(
+                    "Can't find driving lane next to {}: {:?} and {:?}",
+                    sidewalk_id, road.children_forwards, road.children_backwards
+                ));
+                continue;
+            };
+            let stop_id = BusStopID {
+                sidewalk: sidewalk_id,
+                idx,
+            };
+            idx += 1;
+            point_to_stop_id.insert(orig_pt, stop_id);
+            bus_stops.insert(
+                stop_id,
+                BusStop {
+                    id: stop_id,
+                    sidewalk_pos,
+                    driving_pos,
+                },
+            );
         }
     }
 
     let mut routes: Vec<BusRoute> = Vec::new();
-    for route in bus_routes {
-        let route_name = route.name.to_string();
-        let stops: Vec<BusStopID> = route_lookups
-            .remove(&route_name)
-            .unwrap_or_else(Vec::new)
-            .into_iter()
-            .filter_map(|pt| point_to_stop_id.get(&pt))
+    for (route, constraints) in bus_routes.iter().map(|r| (r, PathConstraints::Bus)).chain(
+        light_rail_routes
+            .iter()
+            .map(|r| (r, PathConstraints::Train)),
+    ) {
+        let stops: Vec<BusStopID> = route
+            .stops
+            .iter()
+            .filter_map(|gps| Pt2D::from_gps(*gps, gps_bounds))
+            .filter_map(|pt| point_to_stop_id.get(&pt.to_hashable()))
             .cloned()
             .collect();
+        let timetable: Vec<ScheduledTrip> = route
+            .trips
+            .iter()
+            .filter_map(|trip| {
+                let stops: Vec<ScheduledStop> = trip
+                    .stop_times
+                    .iter()
+                    .filter_map(|st| {
+                        let pt = Pt2D::from_gps(st.stop, gps_bounds)?.to_hashable();
+                        Some(ScheduledStop {
+                            stop: *point_to_stop_id.get(&pt)?,
+                            arrival: st.arrival,
+                            departure: st.departure,
+                            timepoint: st.timepoint,
+                        })
+                    })
+                    .collect();
+                if stops.len() >= 2 {
+                    Some(ScheduledTrip {
+                        days: trip.days.clone(),
+                        stops,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
         let id = BusRouteID(routes.len());
         routes.push(BusRoute {
             id,
-            name: route_name.to_string(),
+            name: route.name.to_string(),
             stops,
+            timetable,
+            constraints,
+            capacity: None,
         });
     }
     timer.stop("make bus stops");
     (bus_stops, routes)
 }
 
+// Snap each light rail station to the closest track.
+// TODO A station has one position, but trains stop at it in both directions. If the track
+// doesn't let trains turn around, the route will have to skip some stations.
+fn find_station_positions(
+    map: &Map,
+    light_rail_routes: &Vec<gtfs::Route>,
+    gps_bounds: &GPSBounds,
+    bounds: &Bounds,
+) -> HashMap<HashablePt2D, Position> {
+    let mut results = HashMap::new();
+    if light_rail_routes.is_empty() {
+        return results;
+    }
+
+    let mut closest: FindClosest<LaneID> = FindClosest::new(bounds);
+    for l in map.all_lanes() {
+        if l.is_light_rail() {
+            closest.add(l.id, l.lane_center_pts.points());
+        }
+    }
+    for route in light_rail_routes {
+        for gps in &route.stops {
+            if let Some(pt) = Pt2D::from_gps(*gps, gps_bounds) {
+                if let Some((l, track_pt)) = closest.closest_pt(pt, Distance::meters(20.0)) {
+                    if let Some(dist_along) = map.get_l(l).dist_along_of_point(track_pt) {
+                        results.insert(pt.to_hashable(), Position::new(l, dist_along));
+                    }
+                }
+            }
+        }
+    }
+    results
+}
+
 pub fn fix_bus_route(map: &Map, r: &mut BusRoute) -> bool {
     // Trim out stops if needed; map borders sometimes mean some paths don't work.
     let mut stops = Vec::new();
@@ -106,24 +185,56 @@ pub fn fix_bus_route(map: &Map, r: &mut BusRoute) -> bool {
         if stops.is_empty() {
             stops.push(stop);
         } else {
-            if check_stops(*stops.last().unwrap(), stop, map) {
+            if check_stops(*stops.last().unwrap(), stop, r.constraints, map) {
                 stops.push(stop);
             }
         }
     }
     // Don't forget the last and first
     while stops.len() >= 2 {
-        if check_stops(*stops.last().unwrap(), stops[0], map) {
+        if check_stops(*stops.last().unwrap(), stops[0], r.constraints, map) {
             break;
         }
         // TODO Or the front one
         stops.pop();
     }
     r.stops = stops;
+
+    // Scheduled trips can only use the remaining stops, and they need to be able to get from one
+    // to the next too. Many trips share the same stops, so remember the expensive checks.
+    let route_stops: HashSet<BusStopID> = r.stops.iter().cloned().collect();
+    let mut connected: HashMap<(BusStopID, BusStopID), bool> = HashMap::new();
+    let constraints = r.constraints;
+    for trip in &mut r.timetable {
+        let mut stops: Vec<ScheduledStop> = Vec::new();
+        for stop in trip.stops.drain(..) {
+            if !route_stops.contains(&stop.stop) {
+                continue;
+            }
+            if let Some(prev) = stops.last() {
+                let pair = (prev.stop, stop.stop);
+                if !*connected
+                    .entry(pair)
+                    .or_insert_with(|| check_stops(pair.0, pair.1, constraints, map))
+                {
+                    continue;
+                }
+            }
+            stops.push(stop);
+        }
+        trip.stops = stops;
+    }
+    r.timetable.retain(|trip| trip.stops.len() >= 2);
+
     r.stops.len() >= 2
 }
 
-fn check_stops(stop1: BusStopID, stop2: BusStopID, map: &Map) -> bool {
+fn check_stops(
+    stop1: BusStopID,
+    stop2: BusStopID,
+    constraints: PathConstraints,
+    map: &Map,
+) -> bool {
     let bs1 = map.get_bs(stop1);
     let bs2 = map.get_bs(stop2);
     // This is coming up because the dist_along's are in a bad order. But why should
@@ -133,7 +244,7 @@ fn check_stops(stop1: BusStopID, stop2: BusStopID, map: &Map) -> bool {
         .pathfind(PathRequest {
             start: bs1.driving_pos,
             end: bs2.driving_pos,
-            constraints: PathConstraints::Bus,
+            constraints,
         })
         .is_some();
     ok1 && ok2
@@ -20,6 +20,10 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
     if osm_tags.get(osm::HIGHWAY) == Some(&"footway".to_string()) {
         return (vec![LaneType::Sidewalk], Vec::new());
     }
+    // Each OSM way is usually one track, but trains run both ways on it.
+    if osm::is_light_rail(osm_tags) && !osm_tags.contains_key(osm::HIGHWAY) {
+        return (vec![LaneType::LightRail], vec![LaneType::LightRail]);
+    }
 
     // TODO Reversible roads should be handled differently?
     let oneway = osm_tags.get("oneway") == Some(&"yes".to_string())
@@ -85,6 +89,11 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
     {
         fwd_side.insert(0, LaneType::SharedLeftTurn);
     }
+    // Trams running along a street get the middle of it.
+    if osm::is_light_rail(osm_tags) {
+        fwd_side.insert(0, LaneType::LightRail);
+        back_side.insert(0, LaneType::LightRail);
+    }
 
     // TODO Handle bus lanes properly.
     let has_bus_lane = osm_tags.contains_key("bus:lanes");
@@ -196,6 +205,7 @@ impl RoadSpec {
             LaneType::Bus => 'u',
             LaneType::SharedLeftTurn => 'l',
             LaneType::Construction => 'c',
+            LaneType::LightRail => 'r',
         }
     }
 
@@ -208,7 +218,47 @@ impl RoadSpec {
             'u' => Some(LaneType::Bus),
             'l' => Some(LaneType::SharedLeftTurn),
             'c' => Some(LaneType::Construction),
+            'r' => Some(LaneType::LightRail),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_lane_types, RoadSpec};
+    use std::collections::BTreeMap;
+
+    fn spec(tags: Vec<(&str, &str)>) -> String {
+        let tags: BTreeMap<String, String> = tags
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let (fwd, back) = get_lane_types(&tags);
+        RoadSpec { fwd, back }.to_string()
+    }
+
+    #[test]
+    fn light_rail() {
+        assert_eq!(spec(vec![("railway", "light_rail")]), "r/r");
+        assert_eq!(spec(vec![("railway", "tram")]), "r/r");
+        // Streetcars run down the middle of a normal street.
+        assert_eq!(
+            spec(vec![
+                ("highway", "secondary"),
+                ("railway", "tram"),
+                ("sidewalk", "both")
+            ]),
+            "rds/rds"
+        );
+        // Other railways don't turn streets into tracks.
+        assert_eq!(
+            spec(vec![
+                ("highway", "residential"),
+                ("railway", "abandoned"),
+                ("sidewalk", "both")
+            ]),
+            "ds/ds"
+        );
+    }
+}
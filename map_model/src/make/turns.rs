@@ -606,6 +606,10 @@ fn get_sidewalk<'a>(lanes: &'a Vec<Lane>, children: &Vec<(LaneID, LaneType)>) ->
 }
 
 fn filter_vehicle_lanes(lanes: &Vec<(LaneID, LaneType)>, preferred: LaneType) -> Vec<LaneID> {
+    // Trains stay on their tracks.
+    if preferred == LaneType::LightRail {
+        return filter_lanes(lanes, preferred);
+    }
     let preferred = filter_lanes(lanes, preferred);
     if !preferred.is_empty() {
         return preferred;
@@ -1,13 +1,14 @@
-use crate::pathfind::Pathfinder;
+use crate::pathfind::{alternatives, cost, pathfind_with_costs, Pathfinder};
 use crate::raw::{OriginalIntersection, OriginalRoad, RawMap};
 use crate::{
-    connectivity, make, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
+    connectivity, make, AlternativeRoute, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID,
+    BusStop, BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
     IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits, Path, PathConstraints,
-    PathRequest, Position, Road, RoadID, Turn, TurnID, LANE_THICKNESS,
+    PathRequest, Position, Road, RoadID, TravelTimeTable, Traversable, Turn, TurnID, TurnPenalties,
+    LANE_THICKNESS,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D};
+use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::io;
@@ -43,6 +44,14 @@ pub struct Map {
     // TODO Argh, hack, initialization order is hard!
     pathfinder: Option<Pathfinder>,
     pathfinder_dirty: bool,
+    // Like travel times, this is configured when the map is loaded, not saved with it. Saved maps
+    // are prepared without any turn penalties.
+    #[serde(
+        skip_serializing,
+        skip_deserializing,
+        default = "default_turn_penalties"
+    )]
+    turn_penalties: BTreeMap<PathConstraints, TurnPenalties>,
 
     name: String,
     edits: MapEdits,
@@ -82,6 +91,7 @@ impl Map {
             turn_lookup: Vec::new(),
             pathfinder: None,
             pathfinder_dirty: false,
+            turn_penalties: default_turn_penalties(),
             name: "blank".to_string(),
             edits: MapEdits::new("blank".to_string()),
         }
@@ -128,8 +138,14 @@ impl Map {
         timer.stop("setup (most of) Pathfinder");
 
         {
-            let (stops, routes) =
-                make::make_bus_stops(&m, &raw.bus_routes, &m.gps_bounds, &m.bounds, timer);
+            let (stops, routes) = make::make_bus_stops(
+                &m,
+                &raw.bus_routes,
+                &raw.light_rail_routes,
+                &m.gps_bounds,
+                &m.bounds,
+                timer,
+            );
             m.bus_stops = stops;
             // The IDs are sorted in the BTreeMap, so this order winds up correct.
             for id in m.bus_stops.keys() {
@@ -589,6 +605,68 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
+    // Cars leaving at this time of day route using the travel time table, if it's set.
+    pub fn pathfind_at(&self, req: PathRequest, depart: Duration) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .as_ref()
+            .unwrap()
+            .pathfind_at(req, self, depart)
+    }
+
+    // Up to k meaningfully different routes, best first. Only for vehicles.
+    pub fn pathfind_alternatives(&self, req: PathRequest, k: usize) -> Vec<AlternativeRoute> {
+        assert!(!self.pathfinder_dirty);
+        alternatives(&req, self, k)
+    }
+
+    pub fn get_turn_penalties(&self, constraints: PathConstraints) -> &TurnPenalties {
+        &self.turn_penalties[&constraints]
+    }
+
+    // Like edits, this doesn't take effect until recalculate_pathfinding_after_edits.
+    pub fn set_turn_penalties(&mut self, constraints: PathConstraints, penalties: TurnPenalties) {
+        assert_ne!(constraints, PathConstraints::Pedestrian);
+        self.remember_pathfinding_edges();
+        self.turn_penalties.insert(constraints, penalties);
+        self.pathfinder_dirty = true;
+    }
+
+    pub fn set_travel_times(&mut self, table: Option<TravelTimeTable>) {
+        self.pathfinder.as_mut().unwrap().set_travel_times(table);
+    }
+
+    // Observed lane travel times, if any were loaded
+    pub fn get_travel_times(&self) -> Option<&TravelTimeTable> {
+        self.pathfinder.as_ref().unwrap().get_travel_times()
+    }
+
+    // Ignores the usual pathfinding costs and contraction hierarchy; only for vehicles.
+    pub fn pathfind_with_costs<F: Fn(&Lane, &Turn) -> Option<usize>>(
+        &self,
+        req: PathRequest,
+        cost: F,
+    ) -> Option<Path> {
+        pathfind_with_costs(&req, self, cost)
+    }
+
+    // The usual pathfinding costs (including turn penalties), but never using anything avoid
+    // returns true for. Doesn't use the contraction hierarchy; only for vehicles.
+    pub fn pathfind_avoiding<F: Fn(Traversable) -> bool>(
+        &self,
+        req: PathRequest,
+        avoid: F,
+    ) -> Option<Path> {
+        let constraints = req.constraints;
+        pathfind_with_costs(&req, self, |lane, turn| {
+            if avoid(Traversable::Turn(turn.id)) || avoid(Traversable::Lane(turn.id.dst)) {
+                None
+            } else {
+                Some(cost(lane, turn, constraints, self))
+            }
+        })
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -630,6 +708,8 @@ impl Map {
         BTreeSet<TurnID>,
         BTreeSet<IntersectionID>,
     ) {
+        self.remember_pathfinding_edges();
+
         // TODO More efficient ways to do this: given two sets of edits, produce a smaller diff.
         // Simplest strategy: Remove common prefix.
         let mut effects = EditEffects::new();
@@ -691,16 +771,54 @@ impl Map {
         )
     }
 
+    // Blocks until pathfinding is fully prepared.
     pub fn recalculate_pathfinding_after_edits(&mut self, timer: &mut Timer) {
+        self.start_recalculating_pathfinding_after_edits(timer);
+        self.pathfinder.as_mut().unwrap().wait_for_rebuilding(timer);
+    }
+
+    // Returns quickly, so pathfinding can be used right away, but vehicle pathfinding is slower
+    // until finish_recalculating_pathfinding returns true. Meanwhile, routes are still shortest,
+    // but ties might break differently, so anything that needs deterministic routes (like the
+    // sim) has to use recalculate_pathfinding_after_edits instead.
+    pub fn start_recalculating_pathfinding_after_edits(&mut self, timer: &mut Timer) {
         if !self.pathfinder_dirty {
             return;
         }
 
         let mut pathfinder = self.pathfinder.take().unwrap();
-        pathfinder.apply_edits(self, timer);
+        pathfinder.start_rebuilding(self, timer);
         self.pathfinder = Some(pathfinder);
+        self.recalculate_parking_blackholes(timer);
+        self.pathfinder_dirty = false;
+    }
+
+    // Doesn't block. Returns true once everything's prepared.
+    pub fn finish_recalculating_pathfinding(&mut self) -> bool {
+        self.pathfinder.as_mut().unwrap().finish_rebuilding()
+    }
 
-        // Also recompute parking blackholes. This is cheap enough to do from scratch.
+    pub fn recalculate_pathfinding_from_scratch(&mut self, timer: &mut Timer) {
+        let mut pathfinder = self.pathfinder.take().unwrap();
+        pathfinder.rebuild_from_scratch(self, timer);
+        self.pathfinder = Some(pathfinder);
+        self.recalculate_parking_blackholes(timer);
+        self.pathfinder_dirty = false;
+    }
+
+    // Only needed once after loading, before the first change that affects pathfinding. While
+    // pathfinding is dirty, the graphs already know.
+    fn remember_pathfinding_edges(&mut self) {
+        if self.pathfinder_dirty {
+            return;
+        }
+        let mut pathfinder = self.pathfinder.take().unwrap();
+        pathfinder.remember_edges(self);
+        self.pathfinder = Some(pathfinder);
+    }
+
+    // This is cheap enough to do from scratch after any edits.
+    fn recalculate_parking_blackholes(&mut self, timer: &mut Timer) {
         timer.start("recompute parking blackholes");
         for l in self.lanes.iter_mut() {
             l.parking_blackhole = None;
@@ -709,11 +827,21 @@ impl Map {
             self.lanes[l.0].parking_blackhole = Some(redirect);
         }
         timer.stop("recompute parking blackholes");
-
-        self.pathfinder_dirty = false;
     }
 }
 
+fn default_turn_penalties() -> BTreeMap<PathConstraints, TurnPenalties> {
+    vec![
+        PathConstraints::Car,
+        PathConstraints::Bike,
+        PathConstraints::Bus,
+        PathConstraints::Train,
+    ]
+    .into_iter()
+    .map(|c| (c, TurnPenalties::none()))
+    .collect()
+}
+
 fn make_half_map(
     raw: &RawMap,
     initial_map: make::initial::InitialMap,
@@ -738,6 +866,7 @@ fn make_half_map(
         turn_lookup: Vec::new(),
         pathfinder: None,
         pathfinder_dirty: false,
+        turn_penalties: default_turn_penalties(),
         name: raw.name.clone(),
         edits: MapEdits::new(raw.name.clone()),
     };
@@ -761,6 +890,7 @@ fn make_half_map(
             // Might change later
             intersection_type: i.intersection_type,
             orig_id: i.id,
+            elevation: raw.intersections[&i.id].elevation,
             incoming_lanes: Vec::new(),
             outgoing_lanes: Vec::new(),
             roads: i.roads.iter().map(|id| road_id_mapping[id]).collect(),
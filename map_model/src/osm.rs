@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 // These are common OSM keys. Keys used in just one or two places don't really need to be defined
 // here.
 
@@ -9,6 +11,9 @@ pub const PARKING_RIGHT: &str = "parking:lane:right";
 pub const PARKING_LEFT: &str = "parking:lane:left";
 pub const PARKING_BOTH: &str = "parking:lane:both";
 pub const SIDEWALK: &str = "sidewalk";
+pub const SIDEWALK_WIDTH: &str = "sidewalk:width";
+pub const SIDEWALK_BOTH_WIDTH: &str = "sidewalk:both:width";
+pub const RAILWAY: &str = "railway";
 
 // The rest of these are all inserted by A/B Street to plumb data between different stages of map
 // construction. They could be plumbed another way, but this is the most convenient.
@@ -27,6 +32,18 @@ pub const ENDPT_BACK: &str = "abst:endpt_back";
 pub const SYNTHETIC: &str = "abst:synthetic";
 pub const SYNTHETIC_LANES: &str = "abst:synthetic_lanes";
 
+// The value of the OSM crossing tag for a pedestrian crossing near the end (fwd) or start (back)
+// of a road.
+pub const CROSSING_FWD: &str = "abst:crossing_fwd";
+pub const CROSSING_BACK: &str = "abst:crossing_back";
+
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
+
+// Only light rail and tram tracks are imported, not abandoned or disused railways. Tracks with a
+// highway tag run along a street.
+pub fn is_light_rail(tags: &BTreeMap<String, String>) -> bool {
+    tags.get(RAILWAY) == Some(&"light_rail".to_string())
+        || tags.get(RAILWAY) == Some(&"tram".to_string())
+}
@@ -0,0 +1,107 @@
+use crate::pathfind::driving::{cost, path_cost, pathfind_with_costs};
+use crate::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep};
+use geom::Distance;
+use std::collections::{BTreeSet, HashMap};
+
+// Each time a route uses a lane, make it this much more expensive for the next search.
+const PENALTY_FACTOR: f64 = 1.5;
+// An alternative sharing more than this fraction of its length with a route already found isn't
+// meaningfully different.
+const MAX_OVERLAP: f64 = 0.8;
+
+pub struct AlternativeRoute {
+    pub path: Path,
+    // The normal pathfinding cost, without any of the penalties used to find this route
+    pub cost: usize,
+    // The most of this route's length shared with any cheaper route, as a fraction
+    pub overlap: f64,
+}
+
+// The penalty method: find the best route, make every lane it uses more expensive, and search
+// again. The first result is the normal best route, and the rest are sorted by cost. Only for
+// vehicles.
+pub fn alternatives(req: &PathRequest, map: &Map, k: usize) -> Vec<AlternativeRoute> {
+    assert_ne!(req.constraints, PathConstraints::Pedestrian);
+    let mut found: Vec<(Path, BTreeSet<LaneID>)> = Vec::new();
+    let mut penalties: HashMap<LaneID, f64> = HashMap::new();
+    // Give up eventually, in case there just aren't many ways to get there.
+    for _ in 0..3 * k {
+        if found.len() == k {
+            break;
+        }
+        let path = match pathfind_with_costs(req, map, |lane, turn| {
+            let penalty = penalties.get(&lane.id).cloned().unwrap_or(1.0);
+            Some((penalty * (cost(lane, turn, req.constraints, map) as f64)).round() as usize)
+        }) {
+            Some(path) => path,
+            None => break,
+        };
+
+        let lanes = path_lanes(&path);
+        for l in &lanes {
+            *penalties.entry(*l).or_insert(1.0) *= PENALTY_FACTOR;
+        }
+
+        // Check both ways, since the routes get reordered by cost later.
+        if found.iter().any(|(_, other)| {
+            overlap(&lanes, other, map) > MAX_OVERLAP || overlap(other, &lanes, map) > MAX_OVERLAP
+        }) {
+            continue;
+        }
+        found.push((path, lanes));
+    }
+
+    // Only compare each route to the cheaper ones listed before it.
+    let mut found: Vec<(usize, Path, BTreeSet<LaneID>)> = found
+        .into_iter()
+        .map(|(path, lanes)| (path_cost(&path, req.constraints, map), path, lanes))
+        .collect();
+    found.sort_by_key(|(cost, _, _)| *cost);
+    let mut results: Vec<AlternativeRoute> = Vec::new();
+    for idx in 0..found.len() {
+        let overlap = max_overlap(
+            &found[idx].2,
+            found[..idx].iter().map(|(_, _, other)| other),
+            map,
+        );
+        results.push(AlternativeRoute {
+            cost: found[idx].0,
+            path: found[idx].1.clone(),
+            overlap,
+        });
+    }
+    results
+}
+
+fn max_overlap<'a, I: Iterator<Item = &'a BTreeSet<LaneID>>>(
+    lanes: &BTreeSet<LaneID>,
+    others: I,
+    map: &Map,
+) -> f64 {
+    others
+        .map(|other| overlap(lanes, other, map))
+        .fold(0.0, f64::max)
+}
+
+fn path_lanes(path: &Path) -> BTreeSet<LaneID> {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Lane(l) => Some(*l),
+            _ => None,
+        })
+        .collect()
+}
+
+// What fraction of the first route's length is shared with the second?
+fn overlap(lanes: &BTreeSet<LaneID>, other: &BTreeSet<LaneID>, map: &Map) -> f64 {
+    let total = total_length(lanes.iter(), map);
+    if total == Distance::ZERO {
+        return 1.0;
+    }
+    total_length(lanes.intersection(other), map) / total
+}
+
+fn total_length<'a, I: Iterator<Item = &'a LaneID>>(lanes: I, map: &Map) -> Distance {
+    lanes.fold(Distance::ZERO, |sum, l| sum + map.get_l(*l).length())
+}
@@ -2,19 +2,33 @@ use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::{
     Lane, LaneID, LaneType, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID,
 };
-use fast_paths::{FastGraph, InputGraph, PathCalculator};
-use geom::Distance;
+use fast_paths::{FastGraph, InputGraph, NodeId, PathCalculator};
+use geom::{Distance, Speed};
 use serde_derive::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Mutex;
 use thread_local::ThreadLocal;
 
+type Edges = Vec<(NodeId, NodeId, usize)>;
+
 #[derive(Serialize, Deserialize)]
 pub struct VehiclePathfinder {
     graph: FastGraph,
+    // What the graph was built from, so edits that don't affect this graph can be skipped. Not
+    // saved with the map; see remember_edges.
+    #[serde(skip_serializing, skip_deserializing)]
+    edges: Option<Edges>,
     #[serde(deserialize_with = "deserialize_nodemap")]
     nodes: NodeMap<LaneID>,
     constraints: PathConstraints,
 
+    // The graph is out-of-date while a new one is prepared in the background. Meanwhile, use
+    // Dijkstra's, which doesn't need any preparation.
+    #[serde(skip_serializing, skip_deserializing)]
+    rebuild: Option<Mutex<Receiver<(FastGraph, Edges)>>>,
     #[serde(skip_serializing, skip_deserializing)]
     path_calc: ThreadLocal<RefCell<PathCalculator>>,
 }
@@ -32,7 +46,8 @@ impl VehiclePathfinder {
         for l in map.all_lanes() {
             nodes.get_or_insert(l.id);
         }
-        let input_graph = make_input_graph(map, &nodes, constraints);
+        let edges = make_edges(map, &nodes, constraints);
+        let input_graph = make_input_graph(&edges);
 
         // All VehiclePathfinders have the same nodes (lanes), so if we're not the first being
         // built, seed from the node ordering.
@@ -45,14 +60,19 @@ impl VehiclePathfinder {
 
         VehiclePathfinder {
             graph,
+            edges: Some(edges),
             nodes,
             constraints,
+            rebuild: None,
             path_calc: ThreadLocal::new(),
         }
     }
 
     pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<Path> {
         assert!(!map.get_l(req.start.lane()).is_sidewalk());
+        if self.rebuild.is_some() {
+            return self.pathfind_without_graph(req, map);
+        }
         let mut calc = self
             .path_calc
             .get_or(|| Box::new(RefCell::new(fast_paths::create_calculator(&self.graph))))
@@ -86,23 +106,177 @@ impl VehiclePathfinder {
         Some(path)
     }
 
-    pub fn apply_edits(&mut self, map: &Map) {
+    // Finds a route just as cheap as the contraction hierarchy would, but not necessarily the
+    // same one -- ties can break differently, and if the end is behind the start on the same
+    // lane, this loops around to reach it.
+    fn pathfind_without_graph(&self, req: &PathRequest, map: &Map) -> Option<Path> {
+        let path = dijkstra(req, map, |lane, turn, _| {
+            if self.constraints.can_use(lane, map) {
+                Some(cost(lane, turn, self.constraints, map))
+            } else {
+                None
+            }
+        })?;
+        // Like the contraction hierarchy, report the cost as the length.
+        let total_cost = path_cost(&path, self.constraints, map);
+        Some(Path::new(
+            map,
+            path.get_steps().iter().cloned().collect(),
+            req.end.dist_along(),
+            Distance::centimeters(total_cost),
+        ))
+    }
+
+    // Prepare a new graph in the background, if the edits changed anything. Until it's ready,
+    // pathfinding falls back to Dijkstra's. Returns true if a rebuild is happening.
+    pub fn start_rebuild(&mut self, map: &Map) -> bool {
+        let edges = make_edges(map, &self.nodes, self.constraints);
+        // Anything already happening in the background is for older edits.
+        self.rebuild = None;
+        if self.edges.as_ref() == Some(&edges) {
+            return false;
+        }
+
         // The NodeMap is just all lanes -- it won't change. So we can also reuse the node
         // ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, self.constraints);
         let node_ordering = self.graph.get_node_ordering();
-        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            let graph =
+                fast_paths::prepare_with_order(&make_input_graph(&edges), &node_ordering).unwrap();
+            // If the receiver is gone, newer edits made this graph useless anyway.
+            let _ = tx.send((graph, edges));
+        });
+        self.rebuild = Some(Mutex::new(rx));
+        true
+    }
+
+    // Returns true once the graph is up-to-date.
+    pub fn finish_rebuild(&mut self) -> bool {
+        let result = match self.rebuild {
+            Some(ref rx) => rx.lock().unwrap().try_recv(),
+            None => {
+                return true;
+            }
+        };
+        match result {
+            Ok((graph, edges)) => {
+                self.install(graph, edges);
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => panic!(
+                "Preparing the {:?} graph in the background failed",
+                self.constraints
+            ),
+        }
+    }
+
+    pub fn wait_for_rebuild(&mut self) {
+        if let Some(rx) = self.rebuild.take() {
+            let (graph, edges) = rx.into_inner().unwrap().recv().unwrap();
+            self.install(graph, edges);
+        }
+    }
+
+    // Must be called before the map changes, if the graph was just loaded.
+    pub fn remember_edges(&mut self, map: &Map) {
+        if self.edges.is_none() {
+            self.edges = Some(make_edges(map, &self.nodes, self.constraints));
+        }
+    }
+
+    fn install(&mut self, graph: FastGraph, edges: Edges) {
+        self.graph = graph;
+        self.edges = Some(edges);
+        self.rebuild = None;
+        // The calculators were made for the old graph.
+        self.path_calc = ThreadLocal::new();
     }
 }
 
-fn make_input_graph(
+// Plain Dijkstra's, for when edge costs change too often to bother with a contraction hierarchy.
+// The caller decides the cost of going from a lane through a turn; None means the turn can't be
+// used at all.
+pub fn pathfind_with_costs<F: Fn(&Lane, &Turn) -> Option<usize>>(
+    req: &PathRequest,
     map: &Map,
-    nodes: &NodeMap<LaneID>,
-    constraints: PathConstraints,
-) -> InputGraph {
-    let mut input_graph = InputGraph::new();
+    cost: F,
+) -> Option<Path> {
+    dijkstra(req, map, |lane, turn, _| cost(lane, turn))
+}
+
+// The cost function also gets the total cost to reach the lane.
+pub(crate) fn dijkstra<F: Fn(&Lane, &Turn, usize) -> Option<usize>>(
+    req: &PathRequest,
+    map: &Map,
+    cost: F,
+) -> Option<Path> {
+    assert!(!map.get_l(req.start.lane()).is_sidewalk());
+    let start = req.start.lane();
+    let end = req.end.lane();
+    // If the end is behind the start on the same lane, the path has to loop back around to it.
+    let must_loop = start == end && req.start.dist_along() > req.end.dist_along();
+
+    let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+    let mut best_cost: HashMap<LaneID, usize> = HashMap::new();
+    let mut queue: BinaryHeap<(Reverse<usize>, LaneID)> = BinaryHeap::new();
+    // When looping, the start has to be reached again, so it doesn't get a cost up front.
+    if !must_loop {
+        best_cost.insert(start, 0);
+    }
+    queue.push((Reverse(0), start));
+    let mut leaving_start = true;
+
+    while let Some((Reverse(cost_so_far), current)) = queue.pop() {
+        if current == end && !(must_loop && leaving_start) {
+            let mut steps = vec![PathStep::Lane(end)];
+            let mut current = end;
+            while current != start || (must_loop && steps.len() == 1) {
+                let turn = backrefs[&current];
+                steps.push(PathStep::Turn(turn));
+                steps.push(PathStep::Lane(turn.src));
+                current = turn.src;
+            }
+            steps.reverse();
+            let total_length = steps.iter().fold(Distance::ZERO, |sum, step| {
+                sum + step.as_traversable().length(map)
+            });
+            return Some(Path::new(map, steps, req.end.dist_along(), total_length));
+        }
+        leaving_start = false;
+        if best_cost
+            .get(&current)
+            .map(|best| cost_so_far > *best)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        let lane = map.get_l(current);
+        for turn in map.get_turns_for(current, req.constraints) {
+            if let Some(c) = cost(lane, turn, cost_so_far) {
+                let next_cost = cost_so_far + c;
+                if best_cost
+                    .get(&turn.id.dst)
+                    .map(|prev| next_cost < *prev)
+                    .unwrap_or(true)
+                {
+                    best_cost.insert(turn.id.dst, next_cost);
+                    backrefs.insert(turn.id.dst, turn.id);
+                    queue.push((Reverse(next_cost), turn.id.dst));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn make_edges(map: &Map, nodes: &NodeMap<LaneID>, constraints: PathConstraints) -> Edges {
+    let mut edges = Vec::new();
     let num_lanes = map.all_lanes().len();
     for l in map.all_lanes() {
         let from = nodes.get(l.id);
@@ -110,11 +284,11 @@ fn make_input_graph(
         if constraints.can_use(l, map) {
             for turn in map.get_turns_for(l.id, constraints) {
                 any = true;
-                input_graph.add_edge(
+                edges.push((
                     from,
                     nodes.get(turn.id.dst),
                     cost(l, turn, constraints, map),
-                );
+                ));
             }
         }
         // The nodes in the graph MUST exactly be all of the lanes, so we can reuse node
@@ -123,27 +297,60 @@ fn make_input_graph(
         // this unused node, this won't affect results.
         // TODO Upstream a method in InputGraph to do this more clearly.
         if !any && l.id.0 == num_lanes - 1 {
-            input_graph.add_edge(from, nodes.get(LaneID(0)), 1);
+            edges.push((from, nodes.get(LaneID(0)), 1));
         }
     }
+    edges
+}
+
+fn make_input_graph(edges: &Edges) -> InputGraph {
+    let mut input_graph = InputGraph::new();
+    for (from, to, weight) in edges {
+        input_graph.add_edge(*from, *to, *weight);
+    }
     input_graph.freeze();
     input_graph
 }
 
+// The total cost of a route, the same as what pathfinding minimizes. Each turn is charged along
+// with the lane it leaves, just like the edges in the graph.
+pub(crate) fn path_cost(path: &Path, constraints: PathConstraints, map: &Map) -> usize {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Turn(t) => {
+                let turn = map.get_t(*t);
+                Some(cost(map.get_l(turn.id.src), turn, constraints, map))
+            }
+            _ => None,
+        })
+        .sum()
+}
+
+// How much further riding a bike on this grade feels. Climbing a 5% grade is about as much work
+// as riding 50% further. Going downhill is a little easier, but not by much, since bikes still
+// have to brake.
+fn bike_grade_penalty(grade: f64) -> f64 {
+    if grade > 0.0 {
+        1.0 + 10.0 * grade
+    } else {
+        (1.0 + 2.0 * grade).max(0.8)
+    }
+}
+
 pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -> usize {
-    // TODO Could cost turns differently.
+    let penalty = map.get_turn_penalties(constraints).cost(turn, map);
 
     match constraints {
         PathConstraints::Car => {
             // Prefer slightly longer route on faster roads
             let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
             let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
-            (t1 + t2).inner_seconds().round() as usize
+            (t1 + t2 + penalty).inner_seconds().round() as usize
         }
         PathConstraints::Bike => {
             // Speed limits don't matter, bikes are usually constrained by their own speed limit.
-            let dist = lane.length() + turn.geom.length();
-            // TODO Elevation gain is bad, loss is good.
+            let dist = bike_grade_penalty(lane.grade(map)) * lane.length() + turn.geom.length();
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
 
@@ -158,8 +365,11 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
                 1.5
             };
 
+            // Turn penalties are times, so count them as however far a bike would've gone.
+            let penalty_dist = penalty * Speed::miles_per_hour(10.0);
+
             // 1m resolution is fine
-            (lt_penalty * dist).inner_meters().round() as usize
+            (lt_penalty * dist + penalty_dist).inner_meters().round() as usize
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
@@ -171,7 +381,12 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
                 assert!(lane.is_driving());
                 1.1
             };
-            (lt_penalty * (t1 + t2)).inner_seconds().round() as usize
+            (lt_penalty * (t1 + t2) + penalty).inner_seconds().round() as usize
+        }
+        PathConstraints::Train => {
+            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
+            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            (t1 + t2 + penalty).inner_seconds().round() as usize
         }
         PathConstraints::Pedestrian => unreachable!(),
     }
@@ -200,3 +415,23 @@ fn check_bike_route(path: &Path, map: &Map) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::bike_grade_penalty;
+
+    fn approx(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn bikes_avoid_hills() {
+        approx(bike_grade_penalty(0.0), 1.0);
+        approx(bike_grade_penalty(0.05), 1.5);
+        approx(bike_grade_penalty(0.1), 2.0);
+        // Downhill helps a little, but never too much.
+        approx(bike_grade_penalty(-0.05), 0.9);
+        approx(bike_grade_penalty(-0.5), 0.8);
+        assert!(bike_grade_penalty(0.05) > bike_grade_penalty(-0.05));
+    }
+}
@@ -0,0 +1,348 @@
+use crate::pathfind::walking::transit_hops;
+use crate::{
+    BuildingID, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, PathConstraints, PathRequest,
+    PathStep, Position,
+};
+use geom::{Distance, Duration, Polygon, Pt2D, Speed};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::{Error, Write};
+
+// Nobody really knows the schedule, so assume this much waiting every time somebody boards.
+const TRANSIT_WAIT: Duration = Duration::const_seconds(5.0 * 60.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Budget {
+    Time(Duration),
+    Distance(Distance),
+}
+
+// How soon and how far away somebody can reach a lane. Vehicles reach the start; pedestrians
+// reach whichever end of a sidewalk is closer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reached {
+    pub time: Duration,
+    pub dist: Distance,
+    // How many times somebody got on a bus or train along the way
+    pub boardings: usize,
+}
+
+// Everywhere reachable from one place, within some budget.
+pub struct Reachability {
+    pub constraints: PathConstraints,
+    pub lanes: BTreeMap<LaneID, Reached>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Node {
+    // At the start (false) or end (true) of a lane. Vehicles always enter at the start, but
+    // pedestrians can walk either way along a sidewalk.
+    Lane(LaneID, bool),
+    // On a bus or train of this route, stopped here. Switching to another route means waiting and
+    // boarding again.
+    RideBus(BusStopID, BusRouteID),
+}
+
+impl Reached {
+    fn plus(self, dist: Distance, speed: Speed) -> Reached {
+        Reached {
+            time: self.time + dist / speed,
+            dist: self.dist + dist,
+            boardings: self.boardings,
+        }
+    }
+}
+
+impl Reachability {
+    // One-to-many Dijkstra, assuming everybody moves at the speed limit or their usual speed.
+    // use_transit only matters for pedestrians.
+    pub fn new(
+        start: Position,
+        constraints: PathConstraints,
+        use_transit: bool,
+        budget: Budget,
+        map: &Map,
+    ) -> Reachability {
+        let walking = constraints == PathConstraints::Pedestrian;
+        let use_transit = use_transit && walking;
+        let within_budget = |reached: Reached| match budget {
+            Budget::Time(t) => reached.time <= t,
+            Budget::Distance(d) => reached.dist <= d,
+        };
+        // Every ride from one stop to the next along a route, calculated once up-front
+        let mut rides: BTreeMap<BusStopID, Vec<(BusStopID, BusRouteID, Reached)>> = BTreeMap::new();
+        if use_transit {
+            for (stop1, stop2, route) in transit_hops(map) {
+                if let Some(ride) = ride_between(stop1, stop2, map.get_br(route).constraints, map) {
+                    rides
+                        .entry(stop1)
+                        .or_insert_with(Vec::new)
+                        .push((stop2, route, ride));
+                }
+            }
+        }
+        let board = |reached: Reached| Reached {
+            time: reached.time + TRANSIT_WAIT,
+            dist: reached.dist,
+            boardings: reached.boardings + 1,
+        };
+
+        let mut lanes: BTreeMap<LaneID, Reached> = BTreeMap::new();
+        let mut best: BTreeMap<Node, Reached> = BTreeMap::new();
+        // Costs are time in 0.1s, or distance in cm.
+        let cost = |reached: Reached| match budget {
+            Budget::Time(_) => (reached.time.inner_seconds() * 10.0).round() as usize,
+            Budget::Distance(_) => (reached.dist.inner_meters() * 100.0).round() as usize,
+        };
+        let mut queue: BinaryHeap<(Reverse<usize>, Node)> = BinaryHeap::new();
+
+        let zero = Reached {
+            time: Duration::ZERO,
+            dist: Distance::ZERO,
+            boardings: 0,
+        };
+        lanes.insert(start.lane(), zero);
+        // Only part of the first lane has to be crossed.
+        let start_lane = map.get_l(start.lane());
+        let start_speed = speed(start_lane, constraints, map);
+        let mut next: Vec<(Node, Reached)> = vec![(
+            Node::Lane(start_lane.id, true),
+            zero.plus(start_lane.length() - start.dist_along(), start_speed),
+        )];
+        if walking {
+            next.push((
+                Node::Lane(start_lane.id, false),
+                zero.plus(start.dist_along(), start_speed),
+            ));
+        }
+
+        loop {
+            for (node, reached) in next.drain(..) {
+                if !within_budget(reached) {
+                    continue;
+                }
+                if best
+                    .get(&node)
+                    .map(|prev| cost(reached) < cost(*prev))
+                    .unwrap_or(true)
+                {
+                    best.insert(node, reached);
+                    queue.push((Reverse(cost(reached)), node));
+                }
+            }
+
+            let (c, node) = match queue.pop() {
+                Some((Reverse(c), node)) => (c, node),
+                None => break,
+            };
+            let current = best[&node];
+            if c > cost(current) {
+                continue;
+            }
+
+            match node {
+                Node::Lane(l, at_end) => {
+                    lanes.entry(l).or_insert(current);
+                    let lane = map.get_l(l);
+                    let lane_speed = speed(lane, constraints, map);
+                    // Cross to the other end
+                    if !at_end || walking {
+                        next.push((
+                            Node::Lane(l, !at_end),
+                            current.plus(lane.length(), lane_speed),
+                        ));
+                    }
+                    // Turn from this end
+                    if at_end || walking {
+                        let here = if at_end { lane.dst_i } else { lane.src_i };
+                        for turn in map.get_turns_for(l, constraints) {
+                            if turn.id.parent != here {
+                                continue;
+                            }
+                            let dst = map.get_l(turn.id.dst);
+                            // Vehicles always enter at the start, but pedestrians might turn onto
+                            // the end of a sidewalk.
+                            let dst_at_end = walking && dst.dst_i == here;
+                            next.push((
+                                Node::Lane(dst.id, dst_at_end),
+                                current.plus(turn.geom.length(), speed(dst, constraints, map)),
+                            ));
+                        }
+                    }
+
+                    if use_transit {
+                        for stop in &lane.bus_stops {
+                            let dist_along = map.get_bs(*stop).sidewalk_pos.dist_along();
+                            let walk = if at_end {
+                                lane.length() - dist_along
+                            } else {
+                                dist_along
+                            };
+                            let boarded = board(current.plus(walk, lane_speed));
+                            for (_, route, _) in rides.get(stop).into_iter().flatten() {
+                                next.push((Node::RideBus(*stop, *route), boarded));
+                            }
+                        }
+                    }
+                }
+                Node::RideBus(stop, route) => {
+                    // Get off here and walk to either end...
+                    let pos = map.get_bs(stop).sidewalk_pos;
+                    let lane = map.get_l(pos.lane());
+                    let lane_speed = speed(lane, constraints, map);
+                    next.push((
+                        Node::Lane(lane.id, false),
+                        current.plus(pos.dist_along(), lane_speed),
+                    ));
+                    next.push((
+                        Node::Lane(lane.id, true),
+                        current.plus(lane.length() - pos.dist_along(), lane_speed),
+                    ));
+                    // ... or stay on until the next stop, or transfer to another route here.
+                    for (next_stop, next_route, ride) in rides.get(&stop).into_iter().flatten() {
+                        if *next_route == route {
+                            next.push((
+                                Node::RideBus(*next_stop, route),
+                                Reached {
+                                    time: current.time + ride.time,
+                                    dist: current.dist + ride.dist,
+                                    boardings: current.boardings,
+                                },
+                            ));
+                        } else {
+                            next.push((Node::RideBus(stop, *next_route), board(current)));
+                        }
+                    }
+                }
+            }
+        }
+
+        Reachability { constraints, lanes }
+    }
+
+    // Start from a building's front path, or the closest lane the vehicle can use.
+    pub fn from_building(
+        b: BuildingID,
+        constraints: PathConstraints,
+        use_transit: bool,
+        budget: Budget,
+        map: &Map,
+    ) -> Option<Reachability> {
+        let sidewalk_pos = map.get_b(b).front_path.sidewalk;
+        let start = match constraints {
+            PathConstraints::Pedestrian => sidewalk_pos,
+            PathConstraints::Bike => {
+                let l = map
+                    .find_closest_lane(
+                        sidewalk_pos.lane(),
+                        vec![LaneType::Biking, LaneType::Driving],
+                    )
+                    .ok()?;
+                sidewalk_pos.equiv_pos(l, Distance::ZERO, map)
+            }
+            PathConstraints::Car => {
+                let l = map
+                    .find_closest_lane(sidewalk_pos.lane(), vec![LaneType::Driving])
+                    .ok()?;
+                sidewalk_pos.equiv_pos(l, Distance::ZERO, map)
+            }
+            PathConstraints::Bus | PathConstraints::Train => {
+                return None;
+            }
+        };
+        Some(Reachability::new(
+            start,
+            constraints,
+            use_transit,
+            budget,
+            map,
+        ))
+    }
+
+    // For each time threshold, a polygon covering everywhere reachable within that time. The
+    // polygons are convex, so they cover some places that aren't actually reachable.
+    pub fn isochrones(&self, thresholds: Vec<Duration>, map: &Map) -> Vec<(Duration, Polygon)> {
+        thresholds
+            .into_iter()
+            .filter_map(|threshold| {
+                let mut pts: Vec<Pt2D> = Vec::new();
+                for (l, reached) in &self.lanes {
+                    if reached.time > threshold {
+                        continue;
+                    }
+                    let lane = map.get_l(*l);
+                    pts.push(lane.first_pt());
+                    if reached.time + lane.length() / speed(lane, self.constraints, map)
+                        <= threshold
+                    {
+                        pts.push(lane.last_pt());
+                    }
+                }
+                Some((threshold, Polygon::convex_hull(&pts)?))
+            })
+            .collect()
+    }
+
+    // One line per lane reached: lane ID, seconds and meters to reach it, and how many buses or
+    // trains were boarded.
+    pub fn save_csv(&self, path: &str) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "lane,time,distance,boardings")?;
+        for (l, reached) in &self.lanes {
+            writeln!(
+                f,
+                "{},{},{},{}",
+                l.0,
+                reached.time.inner_seconds(),
+                reached.dist.inner_meters(),
+                reached.boardings
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn speed(lane: &Lane, constraints: PathConstraints, map: &Map) -> Speed {
+    let limit = map.get_r(lane.parent).get_speed_limit();
+    match constraints {
+        PathConstraints::Pedestrian => Speed::meters_per_second(1.34),
+        PathConstraints::Bike => {
+            let bike = Speed::miles_per_hour(10.0);
+            if bike < limit {
+                bike
+            } else {
+                limit
+            }
+        }
+        PathConstraints::Car | PathConstraints::Bus | PathConstraints::Train => limit,
+    }
+}
+
+fn ride_between(
+    stop1: BusStopID,
+    stop2: BusStopID,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Option<Reached> {
+    let path = map.pathfind(PathRequest {
+        start: map.get_bs(stop1).driving_pos,
+        end: map.get_bs(stop2).driving_pos,
+        constraints,
+    })?;
+    let mut reached = Reached {
+        time: Duration::ZERO,
+        dist: Distance::ZERO,
+        boardings: 0,
+    };
+    for step in path.get_steps() {
+        let len = step.as_traversable().length(map);
+        let road = match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => map.get_parent(*l),
+            PathStep::Turn(t) => map.get_parent(t.dst),
+        };
+        reached.dist += len;
+        reached.time += len / road.get_speed_limit();
+    }
+    Some(reached)
+}
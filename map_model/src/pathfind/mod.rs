@@ -1,15 +1,23 @@
+mod alternatives;
 mod driving;
+mod isochrone;
 mod node_map;
+mod travel_times;
+mod turn_penalties;
 mod walking;
 
-pub use self::driving::cost;
+pub use self::alternatives::{alternatives, AlternativeRoute};
 use self::driving::VehiclePathfinder;
+pub use self::driving::{cost, pathfind_with_costs};
+pub use self::isochrone::{Budget, Reachability, Reached};
+pub use self::travel_times::TravelTimeTable;
+pub use self::turn_penalties::TurnPenalties;
 use self::walking::SidewalkPathfinder;
 use crate::{
     osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID,
 };
 use abstutil::Timer;
-use geom::{Distance, PolyLine};
+use geom::{Distance, Duration, PolyLine};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fmt;
@@ -170,6 +178,14 @@ impl Path {
         self.total_length += self.steps[idx].as_traversable().length(map);
     }
 
+    // Like modify_step, but for a vehicle that's switched to a parallel lane partway along the
+    // current step. Also trusting the caller.
+    pub fn modify_current_step(&mut self, step: PathStep, map: &Map) {
+        self.total_length -= self.steps[0].as_traversable().length(map);
+        self.steps[0] = step;
+        self.total_length += self.steps[0].as_traversable().length(map);
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -182,6 +198,10 @@ impl Path {
         self.steps[self.steps.len() - 1]
     }
 
+    pub fn end_dist(&self) -> Distance {
+        self.end_dist
+    }
+
     // dist_ahead is unlimited when None.
     pub fn trace(
         &self,
@@ -278,12 +298,13 @@ impl Path {
 
 // Who's asking for a path?
 // TODO This is an awful name.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PathConstraints {
     Pedestrian,
     Car,
     Bike,
     Bus,
+    Train,
 }
 
 impl PathConstraints {
@@ -294,6 +315,7 @@ impl PathConstraints {
             LaneType::Driving => PathConstraints::Car,
             LaneType::Biking => PathConstraints::Bike,
             LaneType::Bus => PathConstraints::Bus,
+            LaneType::LightRail => PathConstraints::Train,
             _ => panic!("PathConstraints::from_lt({:?}) doesn't make sense", lt),
         }
     }
@@ -316,6 +338,7 @@ impl PathConstraints {
                 }
             }
             PathConstraints::Bus => l.is_driving() || l.is_bus(),
+            PathConstraints::Train => l.is_light_rail(),
         }
     }
 }
@@ -389,9 +412,13 @@ pub struct Pathfinder {
     car_graph: VehiclePathfinder,
     bike_graph: VehiclePathfinder,
     bus_graph: VehiclePathfinder,
+    train_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     // TODO Option just during initialization! Ewww.
     walking_with_transit_graph: Option<SidewalkPathfinder>,
+    // When this is set, cars route using it instead of speed limits.
+    #[serde(skip_serializing, skip_deserializing)]
+    travel_times: Option<TravelTimeTable>,
 }
 
 impl Pathfinder {
@@ -408,6 +435,10 @@ impl Pathfinder {
         let bus_graph = VehiclePathfinder::new(map, PathConstraints::Bus, Some(&car_graph));
         timer.stop("prepare pathfinding for buses");
 
+        timer.start("prepare pathfinding for trains");
+        let train_graph = VehiclePathfinder::new(map, PathConstraints::Train, Some(&car_graph));
+        timer.stop("prepare pathfinding for trains");
+
         timer.start("prepare pathfinding for pedestrians");
         let walking_graph = SidewalkPathfinder::new(map, false);
         timer.stop("prepare pathfinding for pedestrians");
@@ -416,8 +447,10 @@ impl Pathfinder {
             car_graph,
             bike_graph,
             bus_graph,
+            train_graph,
             walking_graph,
             walking_with_transit_graph: None,
+            travel_times: None,
         }
     }
 
@@ -431,9 +464,26 @@ impl Pathfinder {
             PathConstraints::Car => self.car_graph.pathfind(&req, map),
             PathConstraints::Bike => self.bike_graph.pathfind(&req, map),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map),
+            PathConstraints::Train => self.train_graph.pathfind(&req, map),
+        }
+    }
+
+    // Like pathfind, but cars use the travel time table, if there is one.
+    pub fn pathfind_at(&self, req: PathRequest, map: &Map, depart: Duration) -> Option<Path> {
+        match (req.constraints, &self.travel_times) {
+            (PathConstraints::Car, Some(table)) => table.pathfind(&req, map, depart),
+            _ => self.pathfind(req, map),
         }
     }
 
+    pub fn set_travel_times(&mut self, table: Option<TravelTimeTable>) {
+        self.travel_times = table;
+    }
+
+    pub fn get_travel_times(&self) -> Option<&TravelTimeTable> {
+        self.travel_times.as_ref()
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -446,18 +496,60 @@ impl Pathfinder {
             .should_use_transit(map, start, end)
     }
 
-    pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
-        timer.start("apply edits to car pathfinding");
-        self.car_graph.apply_edits(map);
-        timer.stop("apply edits to car pathfinding");
+    // Slow, and only useful to check that rebuilding after edits gives the same results.
+    pub fn rebuild_from_scratch(&mut self, map: &Map, timer: &mut Timer) {
+        let travel_times = self.travel_times.take();
+        *self = Pathfinder::new_without_transit(map, timer);
+        self.setup_walking_with_transit(map);
+        self.travel_times = travel_times;
+    }
+
+    // The graphs don't save what they were built from, so after loading a map, figure that out
+    // before anything changes.
+    pub fn remember_edges(&mut self, map: &Map) {
+        self.car_graph.remember_edges(map);
+        self.bike_graph.remember_edges(map);
+        self.bus_graph.remember_edges(map);
+        self.train_graph.remember_edges(map);
+        self.walking_graph.remember_edges(map);
+        if let Some(ref mut graph) = self.walking_with_transit_graph {
+            graph.remember_edges(map);
+        }
+    }
 
-        timer.start("apply edits to bike pathfinding");
-        self.bike_graph.apply_edits(map);
-        timer.stop("apply edits to bike pathfinding");
+    pub fn wait_for_rebuilding(&mut self, timer: &mut Timer) {
+        timer.start("wait for vehicle pathfinding to be prepared");
+        self.car_graph.wait_for_rebuild();
+        self.bike_graph.wait_for_rebuild();
+        self.bus_graph.wait_for_rebuild();
+        self.train_graph.wait_for_rebuild();
+        timer.stop("wait for vehicle pathfinding to be prepared");
+    }
 
-        timer.start("apply edits to bus pathfinding");
-        self.bus_graph.apply_edits(map);
-        timer.stop("apply edits to bus pathfinding");
+    // Only the graphs affected by edits are rebuilt. Vehicle graphs are prepared in the
+    // background, and pathfinding for them is slower (and not always the same route) until
+    // finish_rebuilding returns true. Sidewalks are rarely affected by edits, so those graphs are
+    // just rebuilt immediately.
+    pub fn start_rebuilding(&mut self, map: &Map, timer: &mut Timer) {
+        let mut rebuilding = Vec::new();
+        if self.car_graph.start_rebuild(map) {
+            rebuilding.push("cars");
+        }
+        if self.bike_graph.start_rebuild(map) {
+            rebuilding.push("bikes");
+        }
+        if self.bus_graph.start_rebuild(map) {
+            rebuilding.push("buses");
+        }
+        if self.train_graph.start_rebuild(map) {
+            rebuilding.push("trains");
+        }
+        if !rebuilding.is_empty() {
+            timer.note(format!(
+                "Preparing pathfinding for {} in the background",
+                rebuilding.join(", ")
+            ));
+        }
 
         timer.start("apply edits to pedestrian pathfinding");
         self.walking_graph.apply_edits(map);
@@ -470,4 +562,14 @@ impl Pathfinder {
             .apply_edits(map);
         timer.stop("apply edits to pedestrian using transit pathfinding");
     }
+
+    // Returns true once every graph is up-to-date. Doesn't block.
+    pub fn finish_rebuilding(&mut self) -> bool {
+        // Don't short-circuit; install anything that's ready.
+        let car = self.car_graph.finish_rebuild();
+        let bike = self.bike_graph.finish_rebuild();
+        let bus = self.bus_graph.finish_rebuild();
+        let train = self.train_graph.finish_rebuild();
+        car && bike && bus && train
+    }
 }
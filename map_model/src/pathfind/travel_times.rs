@@ -0,0 +1,168 @@
+use crate::pathfind::driving::dijkstra;
+use crate::{Lane, LaneID, Map, Path, PathRequest, PathStep, Turn};
+use geom::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+
+// How long it takes to cross each lane, depending on the time of day. Usually gathered from a
+// previous simulation, or imported from somewhere else.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TravelTimeTable {
+    bucket: Duration,
+    // For each lane, the average time to cross it for somebody entering during each time bucket.
+    // This includes waiting to turn at the end. None if nobody crossed during that bucket.
+    times: BTreeMap<LaneID, Vec<Option<Duration>>>,
+}
+
+impl TravelTimeTable {
+    // Observations are (time entering the lane, lane, time to cross it)
+    pub fn from_observations<I: Iterator<Item = (Duration, LaneID, Duration)>>(
+        bucket: Duration,
+        observations: I,
+    ) -> TravelTimeTable {
+        let mut sums: BTreeMap<(LaneID, usize), (Duration, usize)> = BTreeMap::new();
+        for (time, l, dt) in observations {
+            let entry = sums
+                .entry((l, bucket_idx(time, bucket)))
+                .or_insert((Duration::ZERO, 0));
+            entry.0 += dt;
+            entry.1 += 1;
+        }
+
+        let mut table = TravelTimeTable {
+            bucket,
+            times: BTreeMap::new(),
+        };
+        for ((l, idx), (sum, cnt)) in sums {
+            table.set(l, idx, sum * (1.0 / (cnt as f64)));
+        }
+        table
+    }
+
+    fn set(&mut self, l: LaneID, idx: usize, dt: Duration) {
+        let buckets = self.times.entry(l).or_insert_with(Vec::new);
+        if buckets.len() <= idx {
+            buckets.resize(idx + 1, None);
+        }
+        buckets[idx] = Some(dt);
+    }
+
+    pub fn get(&self, l: LaneID, time: Duration) -> Option<Duration> {
+        *self.times.get(&l)?.get(bucket_idx(time, self.bucket))?
+    }
+
+    pub fn bucket_size(&self) -> Duration {
+        self.bucket
+    }
+
+    // Only for vehicles. Lanes without data for the time somebody would reach them are assumed to
+    // flow at the speed limit.
+    pub fn pathfind(&self, req: &PathRequest, map: &Map, depart: Duration) -> Option<Path> {
+        dijkstra(req, map, |lane, turn, so_far| {
+            let time = depart + Duration::seconds((so_far as f64) / 10.0);
+            let dt = self.lane_time(lane, time, map) + turn_time(turn, map);
+            // 0.1s resolution, so costs are also the time elapsed so far
+            Some((dt.inner_seconds() * 10.0).round() as usize)
+        })
+    }
+
+    // How long following a vehicle's path takes, when leaving at some time.
+    pub fn route_cost(&self, path: &Path, depart: Duration, map: &Map) -> Duration {
+        let mut time = depart;
+        for step in path.get_steps() {
+            match step {
+                PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                    time += self.lane_time(map.get_l(*l), time, map);
+                }
+                PathStep::Turn(t) => {
+                    time += turn_time(map.get_t(*t), map);
+                }
+            }
+        }
+        time - depart
+    }
+
+    fn lane_time(&self, lane: &Lane, time: Duration, map: &Map) -> Duration {
+        self.get(lane.id, time)
+            .unwrap_or_else(|| lane.length() / map.get_r(lane.parent).get_speed_limit())
+    }
+
+    // One line per lane and time bucket: lane ID, start and end of the bucket, and the average
+    // travel time. All times are in seconds.
+    pub fn save_csv(&self, path: &str) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "lane,bucket_start,bucket_end,travel_time")?;
+        for (l, buckets) in &self.times {
+            for (idx, dt) in buckets.iter().enumerate() {
+                if let Some(dt) = dt {
+                    writeln!(
+                        f,
+                        "{},{},{},{}",
+                        l.0,
+                        (self.bucket * (idx as f64)).inner_seconds(),
+                        (self.bucket * ((idx + 1) as f64)).inner_seconds(),
+                        dt.inner_seconds()
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_csv(path: &str) -> Result<TravelTimeTable, Error> {
+        let mut table: Option<TravelTimeTable> = None;
+        for (line_num, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line_num == 0 {
+                continue;
+            }
+            let bad_line = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} line {}: bad row {}", path, line_num + 1, line),
+                )
+            };
+            let parts: Vec<&str> = line.split(',').collect();
+            if parts.len() != 4 {
+                return Err(bad_line());
+            }
+            let l = LaneID(parts[0].parse::<usize>().map_err(|_| bad_line())?);
+            let mut times = Vec::new();
+            for x in &parts[1..] {
+                times.push(Duration::seconds(x.parse::<f64>().map_err(|_| bad_line())?));
+            }
+            let (start, end, dt) = (times[0], times[1], times[2]);
+            if end <= start {
+                return Err(bad_line());
+            }
+
+            let table = table.get_or_insert_with(|| TravelTimeTable {
+                bucket: end - start,
+                times: BTreeMap::new(),
+            });
+            if end - start != table.bucket {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} line {}: every time bucket must be {} long",
+                        path,
+                        line_num + 1,
+                        table.bucket
+                    ),
+                ));
+            }
+            table.set(l, bucket_idx(start, table.bucket), dt);
+        }
+        table.ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} is empty", path)))
+    }
+}
+
+fn bucket_idx(time: Duration, bucket: Duration) -> usize {
+    (time / bucket).floor() as usize
+}
+
+fn turn_time(turn: &Turn, map: &Map) -> Duration {
+    turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit()
+}
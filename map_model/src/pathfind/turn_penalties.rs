@@ -0,0 +1,166 @@
+use crate::{IntersectionID, IntersectionType, Map, PathConstraints, Turn, TurnType};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+
+// Extra time added to the cost of making a turn while pathfinding. Without these, a protected left
+// across four lanes costs the same as a right turn, and routes zig-zag through residential stop
+// signs to save a few meters. Maps start with none, so routing doesn't change unless somebody asks
+// for a profile.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TurnPenalties {
+    pub straight: Duration,
+    pub lane_change: Duration,
+    pub right: Duration,
+    pub left: Duration,
+    // A left turn back onto the same road
+    pub u_turn: Duration,
+    // Only when the turn has to stop for the sign
+    pub stop_sign: Duration,
+    pub traffic_signal: Duration,
+
+    // Also add this fraction of the delay measured at each intersection, usually from a previous
+    // simulation.
+    pub measured_delay_weight: f64,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub measured_delays: BTreeMap<IntersectionID, Duration>,
+}
+
+impl TurnPenalties {
+    pub fn none() -> TurnPenalties {
+        TurnPenalties {
+            straight: Duration::ZERO,
+            lane_change: Duration::ZERO,
+            right: Duration::ZERO,
+            left: Duration::ZERO,
+            u_turn: Duration::ZERO,
+            stop_sign: Duration::ZERO,
+            traffic_signal: Duration::ZERO,
+            measured_delay_weight: 0.0,
+            measured_delays: BTreeMap::new(),
+        }
+    }
+
+    // Rough guesses about how long each kind of turn takes
+    pub fn typical_for(constraints: PathConstraints) -> TurnPenalties {
+        match constraints {
+            PathConstraints::Car | PathConstraints::Bus => TurnPenalties {
+                straight: Duration::ZERO,
+                lane_change: Duration::seconds(1.0),
+                right: Duration::seconds(3.0),
+                left: Duration::seconds(8.0),
+                u_turn: Duration::seconds(30.0),
+                stop_sign: Duration::seconds(6.0),
+                traffic_signal: Duration::seconds(10.0),
+                measured_delay_weight: 1.0,
+                measured_delays: BTreeMap::new(),
+            },
+            // Bikes don't have to merge across traffic for most lefts, and roll through stops.
+            PathConstraints::Bike => TurnPenalties {
+                straight: Duration::ZERO,
+                lane_change: Duration::seconds(1.0),
+                right: Duration::seconds(1.0),
+                left: Duration::seconds(5.0),
+                u_turn: Duration::seconds(15.0),
+                stop_sign: Duration::seconds(3.0),
+                traffic_signal: Duration::seconds(10.0),
+                measured_delay_weight: 1.0,
+                measured_delays: BTreeMap::new(),
+            },
+            // Trains just follow their tracks.
+            PathConstraints::Train => TurnPenalties::none(),
+            PathConstraints::Pedestrian => unreachable!(),
+        }
+    }
+
+    // Only the average delay measured at each intersection, usually from
+    // Analytics::average_intersection_delays in a previous simulation
+    pub fn measured(delays: BTreeMap<IntersectionID, Duration>) -> TurnPenalties {
+        TurnPenalties {
+            measured_delay_weight: 1.0,
+            measured_delays: delays,
+            ..TurnPenalties::none()
+        }
+    }
+
+    // One line per intersection: intersection ID and the measured delay, in seconds
+    pub fn save_measured_delays_csv(&self, path: &str) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "intersection,delay")?;
+        for (i, delay) in &self.measured_delays {
+            writeln!(f, "{},{}", i.0, delay.inner_seconds())?;
+        }
+        Ok(())
+    }
+
+    // The reverse of save_measured_delays_csv, producing a measured profile
+    pub fn load_measured_delays_csv(path: &str) -> Result<TurnPenalties, Error> {
+        let mut delays = BTreeMap::new();
+        for (line_num, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line_num == 0 {
+                continue;
+            }
+            let bad_line = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} line {}: bad row {}", path, line_num + 1, line),
+                )
+            };
+            let parts: Vec<&str> = line.split(',').collect();
+            if parts.len() != 2 {
+                return Err(bad_line());
+            }
+            let i = IntersectionID(parts[0].parse::<usize>().map_err(|_| bad_line())?);
+            let delay = Duration::seconds(parts[1].parse::<f64>().map_err(|_| bad_line())?);
+            delays.insert(i, delay);
+        }
+        Ok(TurnPenalties::measured(delays))
+    }
+
+    pub fn cost(&self, turn: &Turn, map: &Map) -> Duration {
+        let mut penalty = match turn.turn_type {
+            TurnType::Straight => self.straight,
+            TurnType::LaneChangeLeft | TurnType::LaneChangeRight => self.lane_change,
+            TurnType::Right => self.right,
+            TurnType::Left => {
+                if map.get_l(turn.id.src).parent == map.get_l(turn.id.dst).parent {
+                    self.u_turn
+                } else {
+                    self.left
+                }
+            }
+            TurnType::Crosswalk | TurnType::SharedSidewalkCorner => Duration::ZERO,
+        };
+
+        let i = turn.id.parent;
+        match map.get_i(i).intersection_type {
+            IntersectionType::StopSign => {
+                let must_stop = map
+                    .get_stop_sign(i)
+                    .roads
+                    .get(&map.get_l(turn.id.src).parent)
+                    .map(|ss| ss.must_stop)
+                    .unwrap_or(false);
+                if must_stop {
+                    penalty += self.stop_sign;
+                }
+            }
+            IntersectionType::TrafficSignal => {
+                penalty += self.traffic_signal;
+            }
+            IntersectionType::Border | IntersectionType::Construction => {}
+        }
+
+        if let Some(delay) = self.measured_delays.get(&i) {
+            penalty += self.measured_delay_weight * *delay;
+        }
+        penalty
+    }
+}
@@ -3,15 +3,21 @@ use crate::{
     BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, LaneType, Map, Path,
     PathRequest, PathStep, Position,
 };
-use fast_paths::{FastGraph, InputGraph, PathCalculator};
+use fast_paths::{FastGraph, InputGraph, NodeId, PathCalculator};
 use geom::Distance;
 use serde_derive::{Deserialize, Serialize};
 use std::cell::RefCell;
 use thread_local::ThreadLocal;
 
+type Edges = Vec<(NodeId, NodeId, usize)>;
+
 #[derive(Serialize, Deserialize)]
 pub struct SidewalkPathfinder {
     graph: FastGraph,
+    // What the graph was built from, so edits that don't affect sidewalks can be skipped. Not
+    // saved with the map; see remember_edges.
+    #[serde(skip_serializing, skip_deserializing)]
+    edges: Option<Edges>,
     #[serde(deserialize_with = "deserialize_nodemap")]
     nodes: NodeMap<Node>,
     use_transit: bool,
@@ -43,9 +49,11 @@ impl SidewalkPathfinder {
             }
         }
 
-        let graph = fast_paths::prepare(&make_input_graph(map, &nodes, use_transit));
+        let edges = make_edges(map, &nodes, use_transit);
+        let graph = fast_paths::prepare(&make_input_graph(&edges));
         SidewalkPathfinder {
             graph,
+            edges: Some(edges),
             nodes,
             use_transit,
             path_calc: ThreadLocal::new(),
@@ -55,9 +63,22 @@ impl SidewalkPathfinder {
     pub fn apply_edits(&mut self, map: &Map) {
         // The NodeMap is all sidewalks and bus stops -- it won't change. So we can also reuse the
         // node ordering.
-        let input_graph = make_input_graph(map, &self.nodes, self.use_transit);
+        let edges = make_edges(map, &self.nodes, self.use_transit);
+        if self.edges.as_ref() == Some(&edges) {
+            return;
+        }
         let node_ordering = self.graph.get_node_ordering();
-        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        self.graph =
+            fast_paths::prepare_with_order(&make_input_graph(&edges), &node_ordering).unwrap();
+        self.edges = Some(edges);
+        self.path_calc = ThreadLocal::new();
+    }
+
+    // Must be called before the map changes, if the graph was just loaded.
+    pub fn remember_edges(&mut self, map: &Map) {
+        if self.edges.is_none() {
+            self.edges = Some(make_edges(map, &self.nodes, self.use_transit));
+        }
     }
 
     pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<Path> {
@@ -214,21 +235,25 @@ fn get_sidewalk(dr: DirectedRoadID, map: &Map) -> LaneID {
     panic!("{} has no sidewalk", dr);
 }
 
-fn make_input_graph(map: &Map, nodes: &NodeMap<Node>, use_transit: bool) -> InputGraph {
-    let mut input_graph = InputGraph::new();
+fn make_edges(map: &Map, nodes: &NodeMap<Node>, use_transit: bool) -> Edges {
+    let mut edges = Vec::new();
     for t in map.all_turns().values() {
         if !t.between_sidewalks() {
             continue;
         }
         // Duplicate edges in InputGraph will be removed.
-        let length = map.get_l(t.id.src).length() + t.geom.length();
+        let src = map.get_l(t.id.src);
+        // We don't know which way somebody walks along a sidewalk, and steep hills are slow both
+        // ways, so make them seem a bit longer.
+        let grade_penalty = 1.0 + 2.0 * src.grade(map).abs();
+        let length = grade_penalty * src.length() + t.geom.length();
         let length_cm = (length.inner_meters() * 100.0).round() as usize;
 
-        input_graph.add_edge(
+        edges.push((
             nodes.get(lane_to_node(t.id.src, map)),
             nodes.get(lane_to_node(t.id.dst, map)),
             length_cm,
-        );
+        ));
     }
 
     if use_transit {
@@ -237,30 +262,47 @@ fn make_input_graph(map: &Map, nodes: &NodeMap<Node>, use_transit: bool) -> Inpu
         for stop in map.all_bus_stops().values() {
             let cross_lane = nodes.get(lane_to_node(stop.sidewalk_pos.lane(), map));
             let ride_bus = nodes.get(Node::RideBus(stop.id));
-            input_graph.add_edge(cross_lane, ride_bus, 1);
-            input_graph.add_edge(ride_bus, cross_lane, 1);
+            edges.push((cross_lane, ride_bus, 1));
+            edges.push((ride_bus, cross_lane, 1));
         }
 
         // Connect each adjacent stop along a route, again with a "free" cost.
-        for route in map.get_all_bus_routes() {
-            for (stop1, stop2) in
-                route
-                    .stops
-                    .iter()
-                    .zip(route.stops.iter().skip(1))
-                    .chain(std::iter::once((
-                        route.stops.last().unwrap(),
-                        &route.stops[0],
-                    )))
-            {
-                input_graph.add_edge(
-                    nodes.get(Node::RideBus(*stop1)),
-                    nodes.get(Node::RideBus(*stop2)),
-                    1,
-                );
-            }
+        for (stop1, stop2, _) in transit_hops(map) {
+            edges.push((
+                nodes.get(Node::RideBus(stop1)),
+                nodes.get(Node::RideBus(stop2)),
+                1,
+            ));
         }
     }
+    edges
+}
+
+// Every hop between adjacent stops along every route, including looping back to the first stop.
+pub(crate) fn transit_hops(map: &Map) -> Vec<(BusStopID, BusStopID, BusRouteID)> {
+    let mut hops = Vec::new();
+    for route in map.get_all_bus_routes() {
+        for (stop1, stop2) in
+            route
+                .stops
+                .iter()
+                .zip(route.stops.iter().skip(1))
+                .chain(std::iter::once((
+                    route.stops.last().unwrap(),
+                    &route.stops[0],
+                )))
+        {
+            hops.push((*stop1, *stop2, route.id));
+        }
+    }
+    hops
+}
+
+fn make_input_graph(edges: &Edges) -> InputGraph {
+    let mut input_graph = InputGraph::new();
+    for (from, to, weight) in edges {
+        input_graph.add_edge(*from, *to, *weight);
+    }
     input_graph.freeze();
     input_graph
 }
@@ -14,6 +14,8 @@ pub struct RawMap {
     pub intersections: BTreeMap<OriginalIntersection, RawIntersection>,
     pub buildings: BTreeMap<OriginalBuilding, RawBuilding>,
     pub bus_routes: Vec<Route>,
+    // From OSM, not GTFS, so there's no timetable
+    pub light_rail_routes: Vec<Route>,
     pub areas: Vec<RawArea>,
 
     pub boundary_polygon: Polygon,
@@ -79,6 +81,7 @@ impl RawMap {
             intersections: BTreeMap::new(),
             buildings: BTreeMap::new(),
             bus_routes: Vec::new(),
+            light_rail_routes: Vec::new(),
             areas: Vec::new(),
             // Some nonsense thing
             boundary_polygon: Polygon::rectangle(
@@ -535,6 +538,8 @@ pub struct RawIntersection {
     // RawMap; roads and intersections get merged and deleted.
     pub point: Pt2D,
     pub intersection_type: IntersectionType,
+    // Above sea level. Zero if there wasn't any elevation data.
+    pub elevation: Distance,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -8,6 +8,9 @@ use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 
+// When OSM doesn't say, assume sidewalks have about this much clear walking room.
+const DEFAULT_SIDEWALK_WIDTH: Distance = Distance::const_meters(1.5);
+
 // TODO reconsider pub usize. maybe outside world shouldnt know.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct RoadID(pub usize);
@@ -227,9 +230,58 @@ impl Road {
         {
             return Speed::miles_per_hour(40.0);
         }
+        // Link light rail runs at 35mph on its own tracks. Trams along a street go as fast as the
+        // rest of the traffic.
+        if osm::is_light_rail(&self.osm_tags) && !self.osm_tags.contains_key(osm::HIGHWAY) {
+            return Speed::miles_per_hour(35.0);
+        }
         Speed::miles_per_hour(20.0)
     }
 
+    // How much room pedestrians have on each of this road's sidewalks. Widths in OSM are in meters
+    // unless they say otherwise.
+    pub fn get_sidewalk_width(&self) -> Distance {
+        for key in &[osm::SIDEWALK_WIDTH, osm::SIDEWALK_BOTH_WIDTH] {
+            if let Some(width) = self.osm_tags.get(*key) {
+                let width = width.trim();
+                let meters = width.trim_end_matches('m').trim().parse::<f64>();
+                if let Ok(m) = meters {
+                    if m > 0.0 {
+                        return Distance::meters(m);
+                    }
+                }
+            }
+        }
+        DEFAULT_SIDEWALK_WIDTH
+    }
+
+    // Is the crosswalk over this road at one intersection marked? None if OSM doesn't say.
+    pub fn is_crosswalk_marked(&self, i: IntersectionID) -> Option<bool> {
+        let key = if self.src_i == i {
+            osm::CROSSING_BACK
+        } else {
+            osm::CROSSING_FWD
+        };
+        // https://wiki.openstreetmap.org/wiki/Key:crossing
+        match self.osm_tags.get(key).map(|x| x.as_str()) {
+            Some("marked") | Some("zebra") | Some("uncontrolled") | Some("traffic_signals") => {
+                Some(true)
+            }
+            Some("unmarked") | Some("no") => Some(false),
+            _ => None,
+        }
+    }
+
+    // Rise over run from src_i to dst_i, so 0.05 is a 5% climb. Only the endpoints have elevation
+    // data, so anything in between is smoothed over.
+    pub fn grade(&self, map: &Map) -> f64 {
+        grade_between(
+            map.get_i(self.src_i).elevation,
+            map.get_i(self.dst_i).elevation,
+            self.center_pts.length(),
+        )
+    }
+
     pub fn get_zorder(&self) -> isize {
         // TODO Should probably cache this
         if let Some(layer) = self.osm_tags.get("layer") {
@@ -407,3 +459,27 @@ impl Road {
         stops
     }
 }
+
+pub(crate) fn grade_between(from: Distance, to: Distance, length: Distance) -> f64 {
+    if length == Distance::ZERO {
+        0.0
+    } else {
+        (to - from) / length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grade_between;
+    use geom::Distance;
+
+    #[test]
+    fn rise_over_run() {
+        let m = Distance::meters;
+        assert_eq!(grade_between(m(10.0), m(15.0), m(100.0)), 0.05);
+        assert_eq!(grade_between(m(15.0), m(10.0), m(100.0)), -0.05);
+        assert_eq!(grade_between(m(10.0), m(10.0), m(100.0)), 0.0);
+        // Degenerate roads are flat.
+        assert_eq!(grade_between(m(10.0), m(15.0), Distance::ZERO), 0.0);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::{IntersectionID, Map, RoadID, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
+use crate::{
+    IntersectionID, Map, RoadID, Turn, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType,
+};
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap, Timer};
-use geom::Duration;
+use geom::{Distance, Duration, Speed};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -9,6 +11,17 @@ pub struct ControlTrafficSignal {
     pub id: IntersectionID,
     pub phases: Vec<Phase>,
     pub offset: Duration,
+    // Older edits don't have this, and are fixed time.
+    #[serde(default = "default_mode")]
+    pub mode: SignalMode,
+    // Crosswalks are timed so that anybody walking at least this fast can finish crossing after
+    // the don't walk signal starts flashing. Older edits don't have this.
+    #[serde(default = "default_walking_speed")]
+    pub walking_speed: Speed,
+    // Incoming roads with a bike box (an advanced stop line). Bikes stuck behind stopped cars can
+    // move up to the front of the queue.
+    #[serde(default)]
+    pub bike_boxes: BTreeSet<RoadID>,
 
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -18,10 +31,79 @@ pub struct ControlTrafficSignal {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(from = "SavedPhase")]
 pub struct Phase {
     pub protected_groups: BTreeSet<TurnGroupID>,
     pub yield_groups: BTreeSet<TurnGroupID>,
+    // Only for SignalMode::FixedTime
     pub duration: Duration,
+    // Only for SignalMode::Actuated
+    pub min_green: Duration,
+    pub max_green: Duration,
+}
+
+// Older edits don't have min_green and max_green. Those phases always last their duration, even
+// if the signal is made actuated later.
+#[derive(Deserialize)]
+struct SavedPhase {
+    protected_groups: BTreeSet<TurnGroupID>,
+    yield_groups: BTreeSet<TurnGroupID>,
+    duration: Duration,
+    #[serde(default = "missing_green")]
+    min_green: Duration,
+    #[serde(default = "missing_green")]
+    max_green: Duration,
+}
+
+fn default_mode() -> SignalMode {
+    SignalMode::FixedTime
+}
+
+fn missing_green() -> Duration {
+    Duration::ZERO
+}
+
+impl From<SavedPhase> for Phase {
+    fn from(saved: SavedPhase) -> Phase {
+        let fill = |green| {
+            if green == Duration::ZERO {
+                saved.duration
+            } else {
+                green
+            }
+        };
+        Phase {
+            min_green: fill(saved.min_green),
+            max_green: fill(saved.max_green),
+            protected_groups: saved.protected_groups,
+            yield_groups: saved.yield_groups,
+            duration: saved.duration,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum SignalMode {
+    // Each phase lasts for its duration, then the cycle repeats.
+    FixedTime,
+    // Each phase lasts at least min_green. After that, the phase ends once nothing's been detected
+    // for gap (gapping out), or when it reaches max_green (maxing out) -- but only if some other
+    // phase has demand. Otherwise the signal rests in the current phase. Phases without demand are
+    // skipped. Vehicles are detected within detector_length of the end of an incoming lane, and
+    // anybody waiting to start a turn counts as demand.
+    Actuated {
+        gap: Duration,
+        detector_length: Distance,
+    },
+}
+
+impl SignalMode {
+    pub fn default_actuated() -> SignalMode {
+        SignalMode::Actuated {
+            gap: Duration::seconds(3.0),
+            detector_length: Distance::meters(30.0),
+        }
+    }
 }
 
 impl ControlTrafficSignal {
@@ -84,7 +166,100 @@ impl ControlTrafficSignal {
         unreachable!()
     }
 
-    pub fn validate(self) -> Result<ControlTrafficSignal, String> {
+    // How long could this phase possibly last before the signal wants to change?
+    pub fn max_phase_duration(&self, idx: usize) -> Duration {
+        match self.mode {
+            SignalMode::FixedTime => self.phases[idx].duration,
+            SignalMode::Actuated { .. } => self.phases[idx].max_green,
+        }
+    }
+
+    // The flashing don't walk interval for a crosswalk
+    pub fn crosswalk_clearance(&self, turn: &Turn) -> Duration {
+        turn.geom.length() / self.walking_speed
+    }
+
+    // The longest clearance interval of any protected crosswalk in the phase, or zero if there
+    // aren't any.
+    pub fn ped_clearance(&self, phase: &Phase, map: &Map) -> Duration {
+        let mut clearance = Duration::ZERO;
+        for g in &phase.protected_groups {
+            let group = &self.turn_groups[g];
+            if group.turn_type != TurnType::Crosswalk {
+                continue;
+            }
+            for t in &group.members {
+                let dt = self.crosswalk_clearance(map.get_t(*t));
+                if dt > clearance {
+                    clearance = dt;
+                }
+            }
+        }
+        clearance
+    }
+
+    // Lengthen phases that are too short for anybody to finish crossing.
+    pub fn with_ped_clearance(mut self, map: &Map) -> ControlTrafficSignal {
+        for idx in 0..self.phases.len() {
+            let clearance = self.ped_clearance(&self.phases[idx], map);
+            let phase = &mut self.phases[idx];
+            if phase.duration < clearance {
+                phase.duration = clearance;
+            }
+            if phase.min_green < clearance {
+                phase.min_green = clearance;
+            }
+            if phase.max_green < phase.min_green {
+                phase.max_green = phase.min_green;
+            }
+        }
+        self
+    }
+
+    pub fn validate(self, map: &Map) -> Result<ControlTrafficSignal, String> {
+        if self.walking_speed <= Speed::ZERO {
+            return Err(format!(
+                "Traffic signal {} needs a positive walking speed",
+                self.id
+            ));
+        }
+        // Pedestrians need time to finish crossing. Actuated phases could end as soon as min green
+        // is up.
+        for (idx, phase) in self.phases.iter().enumerate() {
+            let clearance = self.ped_clearance(phase, map);
+            let green = match self.mode {
+                SignalMode::FixedTime => phase.duration,
+                SignalMode::Actuated { .. } => phase.min_green,
+            };
+            if green < clearance {
+                return Err(format!(
+                    "Traffic signal {} phase {} lasts {}, but pedestrians need {} to cross",
+                    self.id, idx, green, clearance
+                ));
+            }
+        }
+
+        if let SignalMode::Actuated {
+            gap,
+            detector_length,
+        } = self.mode
+        {
+            if gap <= Duration::ZERO || detector_length <= Distance::ZERO {
+                return Err(format!(
+                    "Actuated traffic signal {} needs a positive gap and detector length",
+                    self.id
+                ));
+            }
+            for (idx, phase) in self.phases.iter().enumerate() {
+                if phase.min_green <= Duration::ZERO || phase.min_green > phase.max_green {
+                    return Err(format!(
+                        "Actuated traffic signal {} phase {} has min green {} and max green {}",
+                        self.id, idx, phase.min_green, phase.max_green
+                    ));
+                }
+            }
+        }
+
         // Does the assignment cover the correct set of groups?
         let expected_groups: BTreeSet<TurnGroupID> = self.turn_groups.keys().cloned().collect();
         let mut actual_groups: BTreeSet<TurnGroupID> = BTreeSet::new();
@@ -164,10 +339,13 @@ impl ControlTrafficSignal {
             id: intersection,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups,
         };
         // This must succeed
-        ts.validate().unwrap()
+        ts.with_ped_clearance(map).validate(map).unwrap()
     }
 
     fn degenerate(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -192,9 +370,12 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
     }
 
     fn three_way(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -239,9 +420,12 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups,
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
     }
 
     fn four_way_four_phase(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -282,9 +466,12 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
     }
 
     fn four_way_two_phase(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -324,9 +511,12 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
     }
 
     fn four_oneways(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -377,9 +567,12 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
     }
 
     fn all_walk_all_yield(map: &Map, i: IntersectionID) -> ControlTrafficSignal {
@@ -403,10 +596,13 @@ impl ControlTrafficSignal {
             id: i,
             phases: vec![all_walk, all_yield],
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups,
         };
         // This must succeed
-        ts.validate().unwrap()
+        ts.with_ped_clearance(map).validate(map).unwrap()
     }
 
     fn phase_per_road(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
@@ -440,9 +636,69 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            mode: SignalMode::FixedTime,
+            walking_speed: default_walking_speed(),
+            bike_boxes: BTreeSet::new(),
             turn_groups,
         };
-        ts.validate().ok()
+        ts.with_ped_clearance(map).validate(map).ok()
+    }
+
+    pub fn has_bike_groups(&self) -> bool {
+        self.turn_groups.keys().any(|g| g.bike)
+    }
+
+    // Give turns to or from bike lanes their own groups. Every phase keeps letting bikes go the same
+    // way as before, so this doesn't change the timing until the new groups are edited.
+    pub fn split_bike_groups(&mut self, map: &Map) {
+        let turn_groups = TurnGroup::for_i_splitting_bikes(self.id, true, map);
+        let split = |groups: &BTreeSet<TurnGroupID>| -> BTreeSet<TurnGroupID> {
+            let mut result = BTreeSet::new();
+            for g in groups {
+                for &bike in &[false, true] {
+                    let id = TurnGroupID { bike, ..*g };
+                    if turn_groups.contains_key(&id) {
+                        result.insert(id);
+                    }
+                }
+            }
+            result
+        };
+        for phase in self.phases.iter_mut() {
+            phase.protected_groups = split(&phase.protected_groups);
+            phase.yield_groups = split(&phase.yield_groups);
+        }
+        self.turn_groups = turn_groups;
+    }
+
+    // Before every phase that protects some bike movements, add a short phase that only lets
+    // those bikes go, so they can get a head start on turning cars.
+    pub fn add_leading_bike_intervals(&mut self, duration: Duration) {
+        let mut phases: Vec<Phase> = Vec::new();
+        for phase in self.phases.drain(..) {
+            let bike_groups: BTreeSet<TurnGroupID> = phase
+                .protected_groups
+                .iter()
+                .filter(|g| g.bike)
+                .cloned()
+                .collect();
+            let already_leading = phases
+                .last()
+                .map(|p| p.protected_groups == bike_groups && p.yield_groups.is_empty())
+                .unwrap_or(false);
+            // Don't add another leading interval in front of one.
+            if !bike_groups.is_empty() && bike_groups != phase.protected_groups && !already_leading
+            {
+                let mut lead = Phase::new();
+                lead.protected_groups = bike_groups;
+                lead.duration = duration;
+                lead.min_green = duration;
+                lead.max_green = duration;
+                phases.push(lead);
+            }
+            phases.push(phase);
+        }
+        self.phases = phases;
     }
 
     pub fn convert_to_ped_scramble(&mut self, map: &Map) {
@@ -484,6 +740,8 @@ impl Phase {
             protected_groups: BTreeSet::new(),
             yield_groups: BTreeSet::new(),
             duration: Duration::seconds(30.0),
+            min_green: Duration::seconds(10.0),
+            max_green: Duration::seconds(60.0),
         }
     }
 
@@ -563,6 +821,11 @@ fn expand_all_phases(phases: &mut Vec<Phase>, turn_groups: &BTreeMap<TurnGroupID
     }
 }
 
+// 1.2m/s, or about 4ft/s, is a common design speed for crosswalks.
+fn default_walking_speed() -> Speed {
+    Speed::meters_per_second(1.2)
+}
+
 const PROTECTED: bool = true;
 const YIELD: bool = false;
 
@@ -124,6 +124,11 @@ pub struct TurnGroupID {
     // If this is true, there's only one member. There are separate TurnGroups for each side of a
     // crosswalk! The TurnID is embedded here so the two crosswalks have different IDs.
     pub crosswalk: Option<TurnID>,
+    // Turns to or from bike lanes can be grouped separately, so signals can give bikes their own
+    // phases. Only signals that opt in with ControlTrafficSignal::split_bike_groups do this. Older
+    // edits don't have this.
+    #[serde(default)]
+    pub bike: bool,
 }
 
 // TODO Unclear how this plays with different lane types
@@ -140,8 +145,16 @@ pub struct TurnGroup {
 
 impl TurnGroup {
     pub(crate) fn for_i(i: IntersectionID, map: &Map) -> BTreeMap<TurnGroupID, TurnGroup> {
+        TurnGroup::for_i_splitting_bikes(i, false, map)
+    }
+
+    pub(crate) fn for_i_splitting_bikes(
+        i: IntersectionID,
+        split_bikes: bool,
+        map: &Map,
+    ) -> BTreeMap<TurnGroupID, TurnGroup> {
         let mut results = BTreeMap::new();
-        let mut groups: MultiMap<(RoadID, RoadID), TurnID> = MultiMap::new();
+        let mut groups: MultiMap<(RoadID, RoadID, bool), TurnID> = MultiMap::new();
         for turn in map.get_turns_in_intersection(i) {
             let from = map.get_l(turn.id.src).parent;
             let to = map.get_l(turn.id.dst).parent;
@@ -152,6 +165,7 @@ impl TurnGroup {
                         from,
                         to,
                         crosswalk: Some(turn.id),
+                        bike: false,
                     };
                     results.insert(
                         id,
@@ -165,11 +179,14 @@ impl TurnGroup {
                     );
                 }
                 _ => {
-                    groups.insert((from, to), turn.id);
+                    let bike = split_bikes
+                        && (map.get_l(turn.id.src).is_biking()
+                            || map.get_l(turn.id.dst).is_biking());
+                    groups.insert((from, to, bike), turn.id);
                 }
             }
         }
-        for ((from, to), members) in groups.consume() {
+        for ((from, to, bike), members) in groups.consume() {
             let geom = turn_group_geom(
                 members.iter().map(|t| &map.get_t(*t).geom).collect(),
                 from,
@@ -197,6 +214,7 @@ impl TurnGroup {
                 from,
                 to,
                 crosswalk: None,
+                bike,
             };
             results.insert(
                 id,
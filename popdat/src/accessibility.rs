@@ -0,0 +1,145 @@
+use crate::psrc::Parcel;
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{Budget, BuildingID, LaneID, LaneType, Map, PathConstraints, Reachability};
+use sim::TripMode;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Error, Write};
+
+// Cumulative-opportunity accessibility: how many jobs somebody living in each building can reach
+// within some amount of time, using each mode.
+pub struct JobAccess {
+    pub threshold: Duration,
+    // Only buildings with households
+    pub bldgs: BTreeMap<BuildingID, BTreeMap<TripMode, usize>>,
+}
+
+impl JobAccess {
+    pub fn new(
+        map: &Map,
+        parcels: &HashMap<BuildingID, Parcel>,
+        threshold: Duration,
+        timer: &mut Timer,
+    ) -> JobAccess {
+        // Where each mode has to reach to get to every workplace
+        let mut jobs: BTreeMap<TripMode, Vec<(LaneID, usize)>> = BTreeMap::new();
+        for mode in TripMode::all() {
+            let mut lanes = Vec::new();
+            for (b, parcel) in parcels {
+                if parcel.num_employees == 0 {
+                    continue;
+                }
+                if let Some(l) = destination_lane(*b, mode, map) {
+                    lanes.push((l, parcel.num_employees));
+                }
+            }
+            jobs.insert(mode, lanes);
+        }
+
+        let homes: Vec<BuildingID> = parcels
+            .iter()
+            .filter(|(_, parcel)| parcel.num_households > 0)
+            .map(|(b, _)| *b)
+            .collect();
+        let jobs = &jobs;
+        let bldgs = timer
+            .parallelize("compute job access", homes, |b| {
+                let mut per_mode = BTreeMap::new();
+                for mode in TripMode::all() {
+                    let (constraints, use_transit) = mode_constraints(mode);
+                    // A job counts if the lane in front of it can be reached in time, even if
+                    // the building is partway down that lane.
+                    let num_jobs = Reachability::from_building(
+                        b,
+                        constraints,
+                        use_transit,
+                        Budget::Time(threshold),
+                        map,
+                    )
+                    .map(|reachable| {
+                        jobs[&mode]
+                            .iter()
+                            .filter(|(l, _)| reachable.lanes.contains_key(l))
+                            .map(|(_, n)| n)
+                            .sum()
+                    })
+                    .unwrap_or(0);
+                    per_mode.insert(mode, num_jobs);
+                }
+                (b, per_mode)
+            })
+            .into_iter()
+            .collect();
+
+        JobAccess { threshold, bldgs }
+    }
+
+    // The average number of jobs each household in these buildings can reach
+    pub fn average(
+        &self,
+        bldgs: &[BuildingID],
+        parcels: &HashMap<BuildingID, Parcel>,
+        mode: TripMode,
+    ) -> f64 {
+        let mut households = 0;
+        let mut jobs = 0;
+        for b in bldgs {
+            if let Some(per_mode) = self.bldgs.get(b) {
+                let n = parcels[b].num_households;
+                households += n;
+                jobs += n * per_mode[&mode];
+            }
+        }
+        if households == 0 {
+            0.0
+        } else {
+            (jobs as f64) / (households as f64)
+        }
+    }
+
+    // Compare job access for the same buildings under two sets of map edits. One line per
+    // building and mode.
+    pub fn save_csv(
+        &self,
+        after: &JobAccess,
+        parcels: &HashMap<BuildingID, Parcel>,
+        path: &str,
+    ) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "building,households,mode,jobs_before,jobs_after")?;
+        for (b, per_mode) in &self.bldgs {
+            for (mode, jobs) in per_mode {
+                writeln!(
+                    f,
+                    "{},{},{},{},{}",
+                    b.0, parcels[b].num_households, mode, jobs, after.bldgs[b][mode]
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn mode_constraints(mode: TripMode) -> (PathConstraints, bool) {
+    match mode {
+        TripMode::Walk => (PathConstraints::Pedestrian, false),
+        TripMode::Transit => (PathConstraints::Pedestrian, true),
+        TripMode::Bike => (PathConstraints::Bike, false),
+        TripMode::Drive => (PathConstraints::Car, false),
+    }
+}
+
+// Same as where Reachability::from_building starts
+pub(crate) fn destination_lane(b: BuildingID, mode: TripMode, map: &Map) -> Option<LaneID> {
+    let sidewalk = map.get_b(b).front_path.sidewalk.lane();
+    match mode {
+        TripMode::Walk | TripMode::Transit => Some(sidewalk),
+        TripMode::Bike => map
+            .find_closest_lane(sidewalk, vec![LaneType::Biking, LaneType::Driving])
+            .ok(),
+        TripMode::Drive => map
+            .find_closest_lane(sidewalk, vec![LaneType::Driving])
+            .ok(),
+    }
+}
@@ -1,12 +1,16 @@
+mod accessibility;
 pub mod psrc;
+mod skims;
 mod trips;
 
 use abstutil::Timer;
+pub use accessibility::JobAccess;
 use geom::{GPSBounds, LonLat};
 use serde_derive::{Deserialize, Serialize};
+pub use skims::{Skim, Skims, Zone};
 use std::collections::BTreeMap;
 use std::fmt;
-pub use trips::{clip_trips, trips_to_scenario, Trip, TripEndpt};
+pub use trips::{clip_parcels, clip_trips, trips_to_scenario, Trip, TripEndpt};
 
 #[derive(Serialize, Deserialize)]
 pub struct PopDat {
@@ -34,6 +38,12 @@ pub struct Estimate {
 }
 
 impl PopDat {
+    // The file isn't versioned, so one written before trips had a person has to be regenerated
+    // with import_all. Loading it might not even fail.
+    pub fn load(timer: &mut Timer) -> PopDat {
+        abstutil::read_binary("../data/shapes/popdat.bin", timer).expect("Couldn't load popdat.bin")
+    }
+
     pub fn import_all(timer: &mut Timer) -> PopDat {
         let mut dat = PopDat {
             tracts: BTreeMap::new(),
@@ -17,6 +17,8 @@ pub struct Trip {
     pub purpose: (Purpose, Purpose),
     pub trip_time: Duration,
     pub trip_dist: Distance,
+    // (household, person number within the household)
+    pub person: (usize, usize),
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -25,7 +27,7 @@ pub struct Endpoint {
     pub osm_building: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Parcel {
     pub num_households: usize,
     pub num_employees: usize,
@@ -40,7 +42,7 @@ pub enum Mode {
     Transit,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Purpose {
     Home,
     Work,
@@ -97,6 +99,12 @@ pub fn import_trips(
         // travdist
         let trip_dist = Distance::miles(rec[24].parse::<f64>()?);
 
+        // hhno and pno
+        let person = (
+            rec[11].parse::<f64>()? as usize,
+            rec[19].parse::<f64>()? as usize,
+        );
+
         trips.push(Trip {
             from,
             to,
@@ -105,6 +113,7 @@ pub fn import_trips(
             mode,
             trip_time,
             trip_dist,
+            person,
         });
     }
     done(timer);
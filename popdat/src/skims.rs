@@ -0,0 +1,271 @@
+use crate::accessibility::{destination_lane, mode_constraints};
+use abstutil::Timer;
+use geom::{Distance, Duration, Polygon};
+use kml::ExtraShapes;
+use map_model::{
+    Budget, BuildingID, Map, Neighborhood, PathConstraints, PathRequest, Reachability,
+    TravelTimeTable,
+};
+use serde_derive::{Deserialize, Serialize};
+use sim::TripMode;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error, Write};
+
+// Something like a traffic analysis zone
+pub struct Zone {
+    pub name: String,
+    pub polygon: Polygon,
+}
+
+impl Zone {
+    pub fn from_neighborhoods(map: &Map) -> Vec<Zone> {
+        Neighborhood::load_all(map.get_name(), map.get_gps_bounds())
+            .into_iter()
+            .map(|(name, n)| Zone {
+                name,
+                polygon: n.polygon,
+            })
+            .collect()
+    }
+
+    // Shapes that aren't entirely within the map are skipped. Zones are named by some attribute,
+    // or just numbered.
+    pub fn from_shapes(
+        shapes: ExtraShapes,
+        name_attribute: Option<String>,
+        map: &Map,
+    ) -> Vec<Zone> {
+        let mut zones = Vec::new();
+        for (idx, shape) in shapes.shapes.into_iter().enumerate() {
+            if let Some(pts) = map.get_gps_bounds().try_convert(&shape.points) {
+                let name = name_attribute
+                    .as_ref()
+                    .and_then(|attrib| shape.attributes.get(attrib).cloned())
+                    .unwrap_or_else(|| idx.to_string());
+                zones.push(Zone {
+                    name,
+                    polygon: Polygon::new(&pts),
+                });
+            }
+        }
+        zones
+    }
+}
+
+// The average trip between a pair of zones
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Skim {
+    pub time: Duration,
+    pub dist: Distance,
+    // Only transit trips have these.
+    pub transfers: f64,
+}
+
+// Zone-to-zone travel time matrices for each mode
+#[derive(Serialize, Deserialize)]
+pub struct Skims {
+    pub zones: Vec<String>,
+    // Indexed by origin and then destination zone. None if no sampled building in the
+    // destination can be reached from the origin in time.
+    pub matrices: BTreeMap<TripMode, Vec<Vec<Option<Skim>>>>,
+}
+
+impl Skims {
+    // Each zone is represented by a few buildings inside of it, and each cell averages the trips
+    // between those buildings. Trips take their usual time at the speed limit, except driving
+    // uses the map's observed travel times for leaving at depart, if there are any.
+    pub fn new(
+        map: &Map,
+        zones: &[Zone],
+        samples_per_zone: usize,
+        max_time: Duration,
+        depart: Duration,
+        timer: &mut Timer,
+    ) -> Skims {
+        let samples: Vec<(usize, BuildingID)> = zones
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, zone)| {
+                sample_buildings(zone, samples_per_zone, map)
+                    .into_iter()
+                    .map(move |b| (idx, b))
+            })
+            .collect();
+        timer.note(format!(
+            "{} zones, represented by {} buildings",
+            zones.len(),
+            samples.len()
+        ));
+
+        let mut matrices = BTreeMap::new();
+        for mode in TripMode::all() {
+            let samples = &samples;
+            let trips: Vec<((usize, BuildingID), Vec<Option<Skim>>)> = timer.parallelize(
+                &format!("skim {} trips", mode),
+                samples.clone(),
+                |(zone, from)| {
+                    let trips = match (mode, map.get_travel_times()) {
+                        (TripMode::Drive, Some(table)) => samples
+                            .iter()
+                            .map(|(_, to)| drive_observed(from, *to, table, depart, map))
+                            .collect(),
+                        _ => trips_from(from, samples, mode, max_time, map),
+                    };
+                    ((zone, from), trips)
+                },
+            );
+
+            let mut totals: Vec<Vec<(Duration, Distance, f64, usize)>> =
+                vec![vec![(Duration::ZERO, Distance::ZERO, 0.0, 0); zones.len()]; zones.len()];
+            for ((origin, _), trips) in trips {
+                for ((destination, _), trip) in samples.iter().zip(trips) {
+                    if let Some(trip) = trip {
+                        let total = &mut totals[origin][*destination];
+                        total.0 += trip.time;
+                        total.1 += trip.dist;
+                        total.2 += trip.transfers;
+                        total.3 += 1;
+                    }
+                }
+            }
+            let matrix = totals
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|(time, dist, transfers, count)| {
+                            if count == 0 {
+                                None
+                            } else {
+                                let n = count as f64;
+                                Some(Skim {
+                                    time: time * (1.0 / n),
+                                    dist: dist / n,
+                                    transfers: transfers / n,
+                                })
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            matrices.insert(mode, matrix);
+        }
+
+        Skims {
+            zones: zones.iter().map(|z| z.name.clone()).collect(),
+            matrices,
+        }
+    }
+
+    pub fn get(&self, mode: TripMode, origin: usize, destination: usize) -> Option<Skim> {
+        self.matrices[&mode][origin][destination]
+    }
+
+    // One line per mode and pair of zones that's reachable. Times are in seconds, distances in
+    // meters.
+    pub fn save_csv(&self, path: &str) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "mode,origin,destination,time,distance,transfers")?;
+        for (mode, matrix) in &self.matrices {
+            for (origin, row) in matrix.iter().enumerate() {
+                for (destination, skim) in row.iter().enumerate() {
+                    if let Some(skim) = skim {
+                        writeln!(
+                            f,
+                            "{},{},{},{},{},{}",
+                            mode,
+                            self.zones[origin],
+                            self.zones[destination],
+                            skim.time.inner_seconds(),
+                            skim.dist.inner_meters(),
+                            skim.transfers
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Evenly spread out over the buildings in the zone, in ID order
+fn sample_buildings(zone: &Zone, samples_per_zone: usize, map: &Map) -> Vec<BuildingID> {
+    let bldgs: Vec<BuildingID> = map
+        .all_buildings()
+        .iter()
+        .filter(|b| zone.polygon.contains_pt(b.polygon.center()))
+        .map(|b| b.id)
+        .collect();
+    if bldgs.len() <= samples_per_zone {
+        return bldgs;
+    }
+    (0..samples_per_zone)
+        .map(|i| bldgs[i * bldgs.len() / samples_per_zone])
+        .collect()
+}
+
+// Like JobAccess, a destination counts once the lane in front of it is reached.
+fn trips_from(
+    from: BuildingID,
+    samples: &[(usize, BuildingID)],
+    mode: TripMode,
+    max_time: Duration,
+    map: &Map,
+) -> Vec<Option<Skim>> {
+    let (constraints, use_transit) = mode_constraints(mode);
+    let reachable =
+        Reachability::from_building(from, constraints, use_transit, Budget::Time(max_time), map);
+    samples
+        .iter()
+        .map(|(_, to)| {
+            let reached = reachable
+                .as_ref()?
+                .lanes
+                .get(&destination_lane(*to, mode, map)?)?;
+            Some(Skim {
+                time: reached.time,
+                dist: reached.dist,
+                transfers: reached.boardings.saturating_sub(1) as f64,
+            })
+        })
+        .collect()
+}
+
+fn drive_observed(
+    from: BuildingID,
+    to: BuildingID,
+    table: &TravelTimeTable,
+    depart: Duration,
+    map: &Map,
+) -> Option<Skim> {
+    if from == to {
+        return Some(Skim {
+            time: Duration::ZERO,
+            dist: Distance::ZERO,
+            transfers: 0.0,
+        });
+    }
+    let pos = |b: BuildingID| {
+        let sidewalk_pos = map.get_b(b).front_path.sidewalk;
+        destination_lane(b, TripMode::Drive, map)
+            .map(|l| sidewalk_pos.equiv_pos(l, Distance::ZERO, map))
+    };
+    let path = table.pathfind(
+        &PathRequest {
+            start: pos(from)?,
+            end: pos(to)?,
+            constraints: PathConstraints::Car,
+        },
+        map,
+        depart,
+    )?;
+    let mut dist = Distance::ZERO;
+    for step in path.get_steps() {
+        dist += step.as_traversable().length(map);
+    }
+    Some(Skim {
+        time: table.route_cost(&path, depart, map),
+        dist,
+        transfers: 0.0,
+    })
+}
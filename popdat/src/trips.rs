@@ -1,9 +1,10 @@
+use crate::psrc;
 use crate::psrc::{Endpoint, Mode, Parcel, Purpose};
 use crate::PopDat;
 use abstutil::Timer;
 use geom::{Distance, Duration, LonLat, Polygon, Pt2D};
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, Position};
-use sim::{DrivingGoal, Scenario, SidewalkSpot, SpawnTrip, TripSpec};
+use sim::{DrivingGoal, PersonSpec, Scenario, SidewalkSpot, SpawnTrip, TripSpec};
 use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Debug)]
@@ -16,6 +17,8 @@ pub struct Trip {
     // These are an upper bound when TripEndpt::Border is involved.
     pub trip_time: Duration,
     pub trip_dist: Distance,
+    // (household, person number within the household)
+    pub person: (usize, usize),
 }
 
 #[derive(Clone, Debug)]
@@ -168,8 +171,8 @@ impl TripEndpt {
 }
 
 pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingID, Parcel>) {
-    let popdat: PopDat = abstutil::read_binary("../data/shapes/popdat.bin", timer)
-        .expect("Couldn't load popdat.bin");
+    let mut popdat = PopDat::load(timer);
+    fix_tour_modes(&mut popdat.trips);
 
     let mut osm_id_to_bldg = HashMap::new();
     for b in map.all_buildings() {
@@ -250,6 +253,7 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
             mode: trip.mode,
             trip_time: trip.trip_time,
             trip_dist: trip.trip_dist,
+            person: trip.person,
         };
 
         match (&trip.from, &trip.to) {
@@ -269,24 +273,65 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
     });
     let trips = maybe_results.into_iter().flatten().collect();
 
+    (trips, clip_parcels(&popdat.parcels, map))
+}
+
+// Match parcels to buildings by OSM ID.
+pub fn clip_parcels(parcels: &BTreeMap<i64, Parcel>, map: &Map) -> HashMap<BuildingID, Parcel> {
     let mut bldgs = HashMap::new();
-    for (osm_id, metadata) in popdat.parcels {
-        if let Some(b) = osm_id_to_bldg.get(&osm_id) {
-            bldgs.insert(*b, metadata);
+    for b in map.all_buildings() {
+        if let Some(metadata) = parcels.get(&b.osm_way_id) {
+            bldgs.insert(b.id, metadata.clone());
+        }
+    }
+    bldgs
+}
+
+// Somebody who drives or bikes away from home has to bring the car or bike back, so every trip in a
+// tour -- from leaving a building until returning to it, or heading home -- uses the same mode as
+// the first. Assumes trips are sorted by departure time.
+fn fix_tour_modes(trips: &mut [psrc::Trip]) {
+    let mut trips_per_person: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (idx, trip) in trips.iter().enumerate() {
+        trips_per_person
+            .entry(trip.person)
+            .or_insert_with(Vec::new)
+            .push(idx);
+    }
+    for indices in trips_per_person.values() {
+        // The building where the current tour started, if it's known, and the tour's mode
+        let mut tour: Option<(Option<i64>, Mode)> = None;
+        for idx in indices {
+            let trip = &mut trips[*idx];
+            let (start, mode) = *tour.get_or_insert((trip.from.osm_building, trip.mode));
+            trip.mode = mode;
+            if trip.purpose.1 == Purpose::Home || (start.is_some() && trip.to.osm_building == start)
+            {
+                tour = None;
+            }
         }
     }
-    (trips, bldgs)
 }
 
 pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
     let (trips, _) = clip_trips(map, timer);
     // TODO Don't clone trips for parallelize
-    let individ_trips = timer
-        .parallelize("turn PSRC trips into SpawnTrips", trips.clone(), |trip| {
-            trip.to_spawn_trip(map)
-        })
+    let spawn_trips = timer.parallelize("turn PSRC trips into SpawnTrips", trips.clone(), |trip| {
+        trip.to_spawn_trip(map)
+    });
+    // The trips are still sorted by departure time, so each person's trips wind up in order.
+    let mut trips_per_person: BTreeMap<(usize, usize), Vec<SpawnTrip>> = BTreeMap::new();
+    for (trip, maybe_spawn_trip) in trips.iter().zip(spawn_trips) {
+        if let Some(spawn_trip) = maybe_spawn_trip {
+            trips_per_person
+                .entry(trip.person)
+                .or_insert_with(Vec::new)
+                .push(spawn_trip);
+        }
+    }
+    let people = trips_per_person
         .into_iter()
-        .flatten()
+        .map(|(_, trips)| PersonSpec { trips })
         .collect();
 
     // How many parked cars do we need to spawn near each building?
@@ -321,7 +366,60 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
         seed_parked_cars: Vec::new(),
         spawn_over_time: Vec::new(),
         border_spawn_over_time: Vec::new(),
-        individ_trips,
+        individ_trips: Vec::new(),
         individ_parked_cars,
+        people,
+        incidents: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fix_tour_modes;
+    use crate::psrc::{Endpoint, Mode, Purpose, Trip};
+    use geom::{Distance, Duration, LonLat};
+
+    fn trip(from: i64, to: i64, purpose: (Purpose, Purpose), mode: Mode) -> Trip {
+        let endpt = |b: i64| Endpoint {
+            pos: LonLat::new(-122.3, 47.6 + (b as f64) / 1000.0),
+            osm_building: Some(b),
+        };
+        Trip {
+            from: endpt(from),
+            to: endpt(to),
+            depart_at: Duration::ZERO,
+            mode,
+            purpose,
+            trip_time: Duration::ZERO,
+            trip_dist: Distance::ZERO,
+            person: (1, 1),
+        }
+    }
+
+    #[test]
+    fn whole_tour_uses_the_first_mode() {
+        let (home, work, lunch) = (1, 2, 3);
+        let mut trips = vec![
+            trip(home, work, (Purpose::Home, Purpose::Work), Mode::Drive),
+            trip(work, lunch, (Purpose::Work, Purpose::Meal), Mode::Walk),
+            trip(lunch, work, (Purpose::Meal, Purpose::Work), Mode::Walk),
+            trip(work, home, (Purpose::Work, Purpose::Home), Mode::Transit),
+            // A new tour can pick a different mode.
+            trip(home, lunch, (Purpose::Home, Purpose::Meal), Mode::Bike),
+            trip(lunch, home, (Purpose::Meal, Purpose::Home), Mode::Walk),
+        ];
+        fix_tour_modes(&mut trips);
+        let modes: Vec<Mode> = trips.iter().map(|t| t.mode).collect();
+        assert_eq!(
+            modes,
+            vec![
+                Mode::Drive,
+                Mode::Drive,
+                Mode::Drive,
+                Mode::Drive,
+                Mode::Bike,
+                Mode::Bike
+            ]
+        );
     }
 }
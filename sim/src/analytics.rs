@@ -1,10 +1,17 @@
-use crate::{AgentID, CarID, Event, TripID, TripMode, VehicleType};
-use abstutil::Counter;
+use crate::{
+    walkable_area, AgentID, CarID, Event, Incident, PedestrianID, TripID, TripMode, VehicleType,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use derivative::Derivative;
-use geom::{Duration, DurationHistogram};
-use map_model::{BusRouteID, BusStopID, IntersectionID, Map, RoadID, Traversable};
+use geom::{Duration, DurationHistogram, Statistic};
+use map_model::{
+    BusRouteID, BusStopID, IntersectionID, LaneID, Map, RoadID, TravelTimeTable, Traversable,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, Write};
 
 // Embed a deeper structure with its own impl when that makes sense, or feel free to just inline
 // things.
@@ -14,11 +21,58 @@ pub struct Analytics {
     #[serde(skip_serializing, skip_deserializing)]
     pub(crate) test_expectations: VecDeque<Event>,
     pub bus_arrivals: Vec<(Duration, CarID, BusRouteID, BusStopID)>,
+    // Only for buses following the timetable. The last field is the scheduled arrival time.
+    pub scheduled_bus_arrivals: Vec<(Duration, CarID, BusRouteID, BusStopID, Duration)>,
     #[serde(skip_serializing, skip_deserializing)]
     pub total_bus_passengers: Counter<BusRouteID>,
+    // When somebody was left at a stop because the bus was full
+    pub bus_passups: Vec<(Duration, BusRouteID, BusStopID)>,
+    // When a bus left a stop, how many passengers were aboard, and its capacity
+    pub bus_loads: Vec<(Duration, CarID, BusRouteID, BusStopID, usize, usize)>,
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
     pub finished_trips: Vec<(Duration, TripID, Option<TripMode>, Duration)>,
+    // For each intersection, when somebody started a turn and how long they waited to do so
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub intersection_delays: BTreeMap<IntersectionID, Vec<(Duration, Duration)>>,
+    // The same delays, grouped by time of day
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub bucketed_intersection_delays: BTreeMap<IntersectionID, BucketedDelays>,
+    // Like intersection_delays, but just for pedestrians waiting to cross at traffic signals
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub ped_signal_delays: BTreeMap<IntersectionID, Vec<(Duration, Duration)>>,
+    // When a pedestrian was still crossing after the walk signal ended
+    pub stranded_peds: Vec<(Duration, PedestrianID, IntersectionID)>,
+    // For each sidewalk, the most pedestrians on it when somebody started along it, during each
+    // SIDEWALK_PEDS_BUCKET of the day. 0 means nobody started along it.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub sidewalk_peds: BTreeMap<LaneID, Vec<usize>>,
+    // For each lane, how long cars took to get through it and start the next turn. Each entry
+    // covers the cars that entered during one LANE_TIME_BUCKET of the day: the total time they
+    // took, and how many there were.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub lane_travel_times: BTreeMap<LaneID, Vec<(Duration, usize)>>,
+    // When each incident started blocking things
+    pub incidents: Vec<(Duration, Incident)>,
+    // When a vehicle changed their path to avoid an incident
+    pub incident_reroutes: Vec<(Duration, CarID)>,
+    #[serde(skip_serializing, skip_deserializing)]
+    lane_entered: BTreeMap<CarID, (LaneID, Duration)>,
 }
 
 #[derive(Serialize, Deserialize, Derivative)]
@@ -43,8 +97,20 @@ impl Analytics {
             },
             test_expectations: VecDeque::new(),
             bus_arrivals: Vec::new(),
+            scheduled_bus_arrivals: Vec::new(),
             total_bus_passengers: Counter::new(),
+            bus_passups: Vec::new(),
+            bus_loads: Vec::new(),
             finished_trips: Vec::new(),
+            intersection_delays: BTreeMap::new(),
+            bucketed_intersection_delays: BTreeMap::new(),
+            ped_signal_delays: BTreeMap::new(),
+            stranded_peds: Vec::new(),
+            sidewalk_peds: BTreeMap::new(),
+            lane_travel_times: BTreeMap::new(),
+            incidents: Vec::new(),
+            incident_reroutes: Vec::new(),
+            lane_entered: BTreeMap::new(),
         }
     }
 
@@ -59,7 +125,7 @@ impl Analytics {
                 AgentID::Car(c) => match c.1 {
                     VehicleType::Car => TripMode::Drive,
                     VehicleType::Bike => TripMode::Bike,
-                    VehicleType::Bus => TripMode::Transit,
+                    VehicleType::Bus | VehicleType::Train => TripMode::Transit,
                 },
             };
 
@@ -82,6 +148,35 @@ impl Analytics {
             };
         }
 
+        // Lane travel times, just for cars
+        match ev {
+            Event::AgentEntersTraversable(AgentID::Car(c), to) if c.1 == VehicleType::Car => {
+                match to {
+                    Traversable::Lane(l) => {
+                        self.lane_entered.insert(c, (l, time));
+                    }
+                    Traversable::Turn(t) => {
+                        if let Some((l, entered)) = self.lane_entered.remove(&c) {
+                            if l == t.src {
+                                let idx = (entered / LANE_TIME_BUCKET).floor() as usize;
+                                let buckets =
+                                    self.lane_travel_times.entry(l).or_insert_with(Vec::new);
+                                if buckets.len() <= idx {
+                                    buckets.resize(idx + 1, (Duration::ZERO, 0));
+                                }
+                                buckets[idx].0 += time - entered;
+                                buckets[idx].1 += 1;
+                            }
+                        }
+                    }
+                }
+            }
+            Event::CarReachedParkingSpot(c, _) | Event::CarOrBikeReachedBorder(c, _) => {
+                self.lane_entered.remove(&c);
+            }
+            _ => {}
+        }
+
         // Test expectations
         if !self.test_expectations.is_empty() && &ev == self.test_expectations.front().unwrap() {
             println!("At {}, met expectation {:?}", time, ev);
@@ -92,11 +187,22 @@ impl Analytics {
         if let Event::BusArrivedAtStop(bus, route, stop) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
         }
+        if let Event::ScheduledBusArrivedAtStop(bus, route, stop, scheduled) = ev {
+            self.scheduled_bus_arrivals
+                .push((time, bus, route, stop, scheduled));
+        }
 
         // Bus passengers
         if let Event::PedEntersBus(_, _, route) = ev {
             self.total_bus_passengers.inc(route);
         }
+        if let Event::PedPassedUpByFullBus(_, _, route, stop) = ev {
+            self.bus_passups.push((time, route, stop));
+        }
+        if let Event::BusLoad(bus, route, stop, passengers, capacity) = ev {
+            self.bus_loads
+                .push((time, bus, route, stop, passengers, capacity));
+        }
 
         // Finished trips
         if let Event::TripFinished(id, mode, dt) = ev {
@@ -104,6 +210,45 @@ impl Analytics {
         } else if let Event::TripAborted(id) = ev {
             self.finished_trips.push((time, id, None, Duration::ZERO));
         }
+
+        // Intersection delays
+        if let Event::IntersectionDelayMeasured(i, delay) = ev {
+            self.intersection_delays
+                .entry(i)
+                .or_insert_with(Vec::new)
+                .push((time, delay));
+            self.bucketed_intersection_delays
+                .entry(i)
+                .or_insert_with(BucketedDelays::new)
+                .add(time, delay);
+        }
+        if let Event::PedSignalDelayMeasured(i, delay) = ev {
+            self.ped_signal_delays
+                .entry(i)
+                .or_insert_with(Vec::new)
+                .push((time, delay));
+        }
+        if let Event::PedStrandedInCrosswalk(ped, i) = ev {
+            self.stranded_peds.push((time, ped, i));
+        }
+
+        // Incidents
+        if let Event::IncidentStarted(ref incident) = ev {
+            self.incidents.push((time, incident.clone()));
+        }
+        if let Event::CarReroutedAroundIncident(car) = ev {
+            self.incident_reroutes.push((time, car));
+        }
+
+        // Sidewalk crowding
+        if let Event::PedsOnSidewalk(l, count) = ev {
+            let idx = (time / SIDEWALK_PEDS_BUCKET).floor() as usize;
+            let buckets = self.sidewalk_peds.entry(l).or_insert_with(Vec::new);
+            if buckets.len() <= idx {
+                buckets.resize(idx + 1, 0);
+            }
+            buckets[idx] = buckets[idx].max(count);
+        }
     }
 
     // TODO If these ever need to be speeded up, just cache the histogram and index in the events
@@ -122,6 +267,125 @@ impl Analytics {
         distrib
     }
 
+    pub fn intersection_delays(&self, now: Duration, i: IntersectionID) -> DurationHistogram {
+        delays_until(self.intersection_delays.get(&i), now)
+    }
+
+    pub fn ped_signal_delays(&self, now: Duration, i: IntersectionID) -> DurationHistogram {
+        delays_until(self.ped_signal_delays.get(&i), now)
+    }
+
+    pub fn num_stranded_peds(&self, now: Duration, i: IntersectionID) -> usize {
+        self.stranded_peds
+            .iter()
+            .take_while(|(t, _, _)| *t <= now)
+            .filter(|(_, _, at)| *at == i)
+            .count()
+    }
+
+    // The worst level-of-service on a sidewalk during each time bucket, up to now. Buckets where
+    // nobody started along the sidewalk are skipped. The bucket should be a multiple of
+    // SIDEWALK_PEDS_BUCKET.
+    pub fn ped_los(
+        &self,
+        now: Duration,
+        l: LaneID,
+        bucket: Duration,
+        map: &Map,
+    ) -> Vec<(Duration, usize, PedLOS)> {
+        let area = walkable_area(Traversable::Lane(l), map);
+        let mut max_per_bucket: BTreeMap<usize, usize> = BTreeMap::new();
+        for (idx, count) in self
+            .sidewalk_peds
+            .get(&l)
+            .map(|buckets| buckets.as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .enumerate()
+        {
+            let start = SIDEWALK_PEDS_BUCKET * (idx as f64);
+            if start > now {
+                break;
+            }
+            if *count > 0 {
+                let max = max_per_bucket
+                    .entry((start / bucket).floor() as usize)
+                    .or_insert(0);
+                *max = (*max).max(*count);
+            }
+        }
+        max_per_bucket
+            .into_iter()
+            .map(|(idx, count)| {
+                (
+                    bucket * (idx as f64),
+                    count,
+                    PedLOS::from_space(area / (count as f64)),
+                )
+            })
+            .collect()
+    }
+
+    // One line per sidewalk and time bucket that anybody walked along. Times are in seconds, space
+    // in square meters per pedestrian.
+    pub fn save_ped_los_csv(&self, path: &str, bucket: Duration, map: &Map) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        writeln!(f, "lane,bucket_start,bucket_end,max_peds,space_per_ped,los")?;
+        for (l, buckets) in &self.sidewalk_peds {
+            let area = walkable_area(Traversable::Lane(*l), map);
+            let last_time = SIDEWALK_PEDS_BUCKET * (buckets.len() as f64);
+            for (start, count, los) in self.ped_los(last_time, *l, bucket, map) {
+                writeln!(
+                    f,
+                    "{},{},{},{},{},{}",
+                    l.0,
+                    start.inner_seconds(),
+                    (start + bucket).inner_seconds(),
+                    count,
+                    area / (count as f64),
+                    los
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // The average delay at every intersection, to penalize turns through them while pathfinding
+    pub fn average_intersection_delays(&self, now: Duration) -> BTreeMap<IntersectionID, Duration> {
+        self.intersection_delays
+            .keys()
+            .filter_map(|i| {
+                let distrib = self.intersection_delays(now, *i);
+                if distrib.count() == 0 {
+                    None
+                } else {
+                    Some((*i, distrib.select(Statistic::Mean)))
+                }
+            })
+            .collect()
+    }
+
+    // For routing cars in a later simulation. The bucket should be a multiple of
+    // LANE_TIME_BUCKET.
+    pub fn travel_time_table(&self, bucket: Duration) -> TravelTimeTable {
+        // Each car in a bucket counts as an observation of the bucket's average, so buckets with
+        // more cars weigh more.
+        TravelTimeTable::from_observations(
+            bucket,
+            self.lane_travel_times.iter().flat_map(|(l, buckets)| {
+                buckets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, cnt))| *cnt > 0)
+                    .flat_map(move |(idx, (total, cnt))| {
+                        let avg = *total * (1.0 / (*cnt as f64));
+                        let entered = LANE_TIME_BUCKET * (idx as f64);
+                        (0..*cnt).map(move |_| (entered, *l, avg))
+                    })
+            }),
+        )
+    }
+
     // Returns (all trips except aborted, number of aborted trips, trips by mode)
     pub fn all_finished_trips(
         &self,
@@ -180,6 +444,79 @@ impl Analytics {
         delay_to_stop
     }
 
+    // For each stop, how late each bus arrived, compared to the timetable. Negative means early.
+    pub fn bus_schedule_deviation(
+        &self,
+        now: Duration,
+        r: BusRouteID,
+    ) -> BTreeMap<BusStopID, Vec<Duration>> {
+        let mut per_stop: BTreeMap<BusStopID, Vec<Duration>> = BTreeMap::new();
+        for (t, _, route, stop, scheduled) in &self.scheduled_bus_arrivals {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push(*t - *scheduled);
+            }
+        }
+        per_stop
+    }
+
+    // Returns the number of (early, on-time, late) arrivals along the route, compared to the
+    // timetable. Uses King County Metro's definition: on-time is anywhere from 1 minute early to 5
+    // minutes late.
+    pub fn bus_on_time_performance(&self, now: Duration, r: BusRouteID) -> (usize, usize, usize) {
+        let mut early = 0;
+        let mut on_time = 0;
+        let mut late = 0;
+        for deviations in self.bus_schedule_deviation(now, r).values() {
+            for dt in deviations {
+                if *dt < -Duration::minutes(1) {
+                    early += 1;
+                } else if *dt > Duration::minutes(5) {
+                    late += 1;
+                } else {
+                    on_time += 1;
+                }
+            }
+        }
+        (early, on_time, late)
+    }
+
+    // For each stop, how many times somebody was left behind by a full bus
+    pub fn bus_passups(&self, now: Duration, r: BusRouteID) -> BTreeMap<BusStopID, usize> {
+        let mut per_stop: BTreeMap<BusStopID, usize> = BTreeMap::new();
+        for (t, route, stop) in &self.bus_passups {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                *per_stop.entry(*stop).or_insert(0) += 1;
+            }
+        }
+        per_stop
+    }
+
+    // For each stop, the load factor (passengers / capacity) of every bus departing it
+    pub fn bus_load_factors(&self, now: Duration, r: BusRouteID) -> BTreeMap<BusStopID, Vec<f64>> {
+        let mut per_stop: BTreeMap<BusStopID, Vec<f64>> = BTreeMap::new();
+        for (t, _, route, stop, passengers, capacity) in &self.bus_loads {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push((*passengers as f64) / (*capacity as f64));
+            }
+        }
+        per_stop
+    }
+
     // TODO Refactor!
     // For each stop, a list of (time, delay)
     pub fn bus_arrivals_over_time(
@@ -271,3 +608,103 @@ impl Analytics {
         per_mode
     }
 }
+
+// Pedestrian level-of-service for walkways, graded by the space each pedestrian has, following the
+// Highway Capacity Manual
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PedLOS {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl PedLOS {
+    // In square meters per pedestrian
+    pub fn from_space(space: f64) -> PedLOS {
+        if space > 5.6 {
+            PedLOS::A
+        } else if space > 3.7 {
+            PedLOS::B
+        } else if space > 2.2 {
+            PedLOS::C
+        } else if space > 1.4 {
+            PedLOS::D
+        } else if space > 0.75 {
+            PedLOS::E
+        } else {
+            PedLOS::F
+        }
+    }
+}
+
+impl fmt::Display for PedLOS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+fn delays_until(list: Option<&Vec<(Duration, Duration)>>, now: Duration) -> DurationHistogram {
+    let mut distrib = DurationHistogram::new();
+    if let Some(list) = list {
+        for (t, dt) in list {
+            if *t > now {
+                break;
+            }
+            distrib.add(*dt);
+        }
+    }
+    distrib
+}
+
+const DELAY_BUCKET: Duration = Duration::const_seconds(300.0);
+const LANE_TIME_BUCKET: Duration = Duration::const_seconds(300.0);
+const SIDEWALK_PEDS_BUCKET: Duration = Duration::const_seconds(300.0);
+
+// Delays grouped into time buckets, each rounded to 0.1s, so that storing them doesn't grow with
+// the number of agents.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct BucketedDelays {
+    // For each bucket, how many times each delay (in tenths of a second) happened
+    buckets: Vec<BTreeMap<usize, usize>>,
+}
+
+impl BucketedDelays {
+    pub fn new() -> BucketedDelays {
+        Default::default()
+    }
+
+    pub fn add(&mut self, time: Duration, delay: Duration) {
+        let idx = (time / DELAY_BUCKET).floor() as usize;
+        while self.buckets.len() <= idx {
+            self.buckets.push(BTreeMap::new());
+        }
+        let key = (delay.inner_seconds() * 10.0).round() as usize;
+        *self.buckets[idx].entry(key).or_insert(0) += 1;
+    }
+
+    // Each delay in the buckets that finished by now, and how many times it happened
+    pub fn counts_until(&self, now: Duration) -> Vec<(Duration, usize)> {
+        let mut totals: BTreeMap<usize, usize> = BTreeMap::new();
+        for (idx, bucket) in self.buckets.iter().enumerate() {
+            if DELAY_BUCKET * ((idx + 1) as f64) > now {
+                break;
+            }
+            for (key, count) in bucket {
+                *totals.entry(*key).or_insert(0) += *count;
+            }
+        }
+        totals
+            .into_iter()
+            .map(|(key, count)| (Duration::seconds((key as f64) / 10.0), count))
+            .collect()
+    }
+
+    pub fn total_until(&self, now: Duration) -> Duration {
+        self.counts_until(now)
+            .into_iter()
+            .fold(Duration::ZERO, |sum, (dt, count)| sum + dt * (count as f64))
+    }
+}
@@ -1,7 +1,9 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TripID, TripMode};
+use crate::{AgentID, CarID, Incident, ParkingSpot, PedestrianID, TripID, TripMode};
 use geom::Duration;
 use map_model::{BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Traversable};
 use serde_derive::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
@@ -10,6 +12,11 @@ pub enum Event {
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
+    // Only for buses following the timetable, along with BusArrivedAtStop. The last field is when
+    // the bus was supposed to arrive.
+    ScheduledBusArrivedAtStop(CarID, BusRouteID, BusStopID, Duration),
+    // Passengers aboard when the bus left the stop, then the bus's capacity
+    BusLoad(CarID, BusRouteID, BusStopID, usize, usize),
 
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
     PedReachedBuilding(PedestrianID, BuildingID),
@@ -17,11 +24,160 @@ pub enum Event {
     PedReachedBusStop(PedestrianID, BusStopID),
     PedEntersBus(PedestrianID, CarID, BusRouteID),
     PedLeavesBus(PedestrianID, CarID, BusRouteID),
+    // The bus was too full to board, so the pedestrian keeps waiting at the stop.
+    PedPassedUpByFullBus(PedestrianID, CarID, BusRouteID, BusStopID),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    // A bike moved up to the front of the queue on this lane, waiting at a traffic signal
+    BikeEnteredBikeBox(CarID, LaneID),
+    // A car passed a bike without leaving this lane
+    CarPassedBike(CarID, CarID, LaneID),
+    // A pedestrian started along a sidewalk, and this many pedestrians are on it now
+    PedsOnSidewalk(LaneID, usize),
 
     AgentEntersTraversable(AgentID, Traversable),
+    // From one lane to another, partway along
+    CarChangedLanes(CarID, LaneID, LaneID),
+    // Somebody started a turn after waiting this long for the intersection to let them go
+    IntersectionDelayMeasured(IntersectionID, Duration),
+    // A pedestrian started crossing at a traffic signal after waiting this long for the walk
+    // signal. Also counts as an IntersectionDelayMeasured.
+    PedSignalDelayMeasured(IntersectionID, Duration),
+    // A pedestrian started crossing at a traffic signal while their crosswalk was protected, but
+    // finished after it stopped being protected
+    PedStrandedInCrosswalk(PedestrianID, IntersectionID),
 
     TripFinished(TripID, TripMode, Duration),
     TripAborted(TripID),
+
+    IncidentStarted(Incident),
+    IncidentCleared(Incident),
+    // A vehicle changed their path to avoid an incident
+    CarReroutedAroundIncident(CarID),
+    // A driver found a faster path, given how congested the map is right now
+    CarRerouted(CarID),
+}
+
+// Anything that wants to observe every Event that Sim::step produces. Sinks only get to look, so
+// they can't affect the simulation. They're called in the same order the events happen.
+pub trait EventSink {
+    fn event(&mut self, time: Duration, ev: &Event);
+
+    // Called at the end of every Sim::step.
+    fn flush(&mut self) {}
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum EventLogFormat {
+    // One JSON object per line
+    JsonLines,
+    Csv,
+}
+
+// A built-in EventSink that writes a timestamped log to a file. Unlike arbitrary EventSinks, this
+// is part of the savestate. Loading a savestate truncates the file to what had been written at
+// that point, so the log always matches the simulation that produced it.
+#[derive(Serialize, Deserialize)]
+pub struct EventLog {
+    path: String,
+    format: EventLogFormat,
+    bytes_written: u64,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    file: Option<BufWriter<File>>,
+}
+
+impl EventLog {
+    pub fn new(path: String, format: EventLogFormat) -> EventLog {
+        EventLog {
+            path,
+            format,
+            bytes_written: 0,
+            file: None,
+        }
+    }
+
+    // Guess the format from the file extension.
+    pub fn from_path(path: String) -> EventLog {
+        let format = if path.ends_with(".csv") {
+            EventLogFormat::Csv
+        } else {
+            EventLogFormat::JsonLines
+        };
+        EventLog::new(path, format)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn write(&mut self, line: String) {
+        if self.file.is_none() {
+            self.file = Some(BufWriter::new(
+                self.open()
+                    .expect(&format!("Couldn't open event log {}", self.path)),
+            ));
+            if self.bytes_written == 0 && self.format == EventLogFormat::Csv {
+                self.write("time,event,details\n".to_string());
+            }
+        }
+        self.file
+            .as_mut()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .expect(&format!("Writing to event log {} failed", self.path));
+        self.bytes_written += line.len() as u64;
+    }
+
+    fn open(&self) -> std::io::Result<File> {
+        if self.bytes_written == 0 {
+            if let Some(parent) = std::path::Path::new(&self.path).parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            return File::create(&self.path);
+        }
+        // Resuming from a savestate. Anything written past this point came from a different
+        // future.
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.set_len(self.bytes_written)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    }
+}
+
+impl EventSink for EventLog {
+    fn event(&mut self, time: Duration, ev: &Event) {
+        let line = match self.format {
+            EventLogFormat::JsonLines => format!(
+                "{{\"time\":{},\"event\":{}}}\n",
+                time.inner_seconds(),
+                serde_json::to_string(ev).unwrap()
+            ),
+            EventLogFormat::Csv => {
+                // Split "Variant(details...)" apart
+                let debug = format!("{:?}", ev);
+                let (name, details) = match debug.find('(') {
+                    Some(idx) => (
+                        debug[..idx].to_string(),
+                        debug[idx + 1..debug.len() - 1].to_string(),
+                    ),
+                    None => (debug.clone(), String::new()),
+                };
+                format!(
+                    "{},{},\"{}\"\n",
+                    time.inner_seconds(),
+                    name,
+                    details.replace("\"", "\"\"")
+                )
+            }
+        };
+        self.write(line);
+    }
+
+    fn flush(&mut self) {
+        if let Some(ref mut file) = self.file {
+            file.flush()
+                .expect(&format!("Flushing event log {} failed", self.path));
+        }
+    }
 }
@@ -9,14 +9,18 @@ mod sim;
 mod transit;
 mod trips;
 
-pub use self::analytics::Analytics;
-pub use self::events::Event;
+pub use self::analytics::{Analytics, BucketedDelays, PedLOS};
+pub use self::events::{Event, EventLog, EventLogFormat, EventSink};
 pub use self::make::{
-    ABTest, BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SimFlags,
-    SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    ABTest, Blockage, BorderSpawnOverTime, Incident, IncidentKind, OriginDestination, PersonSpec,
+    Scenario, SeedParkedCars, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
 };
 pub(crate) use self::mechanics::{
-    DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
+    walkable_area, DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
+};
+pub use self::mechanics::{
+    CrosswalkRules, IntersectionContext, IntersectionPolicy, PolicyDecision, StrictFIFOStopSign,
+    TurnRequest,
 };
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
@@ -46,11 +50,27 @@ pub const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
+// Seated and standing passengers
+pub const BUS_CAPACITY: usize = 60;
+// Link light rail runs 2-car consists. Each car is about 29m long and fits about 200 people.
+pub const TRAIN_LENGTH: Distance = Distance::const_meters(58.0);
+pub const TRAIN_CAPACITY: usize = 400;
 
 // At all speeds (including at rest), cars must be at least this far apart, measured from front of
 // one car to the back of the other.
 pub const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
 
+// Acceleration and deceleration limits, in m/s^2. Only used when SimOptions::use_acceleration is
+// set. Braking uses comfortable rates, not emergency stops.
+pub const CAR_MAX_ACCEL: f64 = 2.5;
+pub const CAR_MAX_DECEL: f64 = 3.5;
+pub const BUS_MAX_ACCEL: f64 = 1.2;
+pub const BUS_MAX_DECEL: f64 = 1.5;
+pub const TRAIN_MAX_ACCEL: f64 = 1.0;
+pub const TRAIN_MAX_DECEL: f64 = 1.3;
+pub const BIKE_MAX_ACCEL: f64 = 1.0;
+pub const BIKE_MAX_DECEL: f64 = 2.0;
+
 // The numeric ID must be globally unique, without considering VehicleType. VehicleType is bundled
 // for convenient debugging.
 // TODO Implement Eq, Hash, Ord manually to guarantee this.
@@ -67,6 +87,7 @@ impl fmt::Display for CarID {
                 VehicleType::Car => "car",
                 VehicleType::Bus => "bus",
                 VehicleType::Bike => "bike",
+                VehicleType::Train => "train",
             }
         )
     }
@@ -105,6 +126,15 @@ impl fmt::Display for AgentID {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PersonID(pub usize);
+
+impl fmt::Display for PersonID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PersonID({0})", self.0)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct TripID(pub usize);
 
@@ -119,6 +149,7 @@ pub enum VehicleType {
     Car,
     Bus,
     Bike,
+    Train,
 }
 
 impl VehicleType {
@@ -127,8 +158,14 @@ impl VehicleType {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Train => PathConstraints::Train,
         }
     }
+
+    // Buses and trains both serve a BusRoute.
+    pub fn is_transit(self) -> bool {
+        self == VehicleType::Bus || self == VehicleType::Train
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -138,6 +175,9 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // m/s^2
+    pub max_accel: f64,
+    pub max_decel: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -145,6 +185,9 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // m/s^2
+    pub max_accel: f64,
+    pub max_decel: f64,
 }
 
 impl VehicleSpec {
@@ -155,6 +198,8 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            max_accel: self.max_accel,
+            max_decel: self.max_decel,
         }
     }
 }
@@ -211,7 +256,9 @@ impl DrivingGoal {
             DrivingGoal::ParkNear(b) => match constraints {
                 PathConstraints::Car => map.find_driving_lane_near_building(*b),
                 PathConstraints::Bike => map.find_biking_lane_near_building(*b),
-                PathConstraints::Bus | PathConstraints::Pedestrian => unreachable!(),
+                PathConstraints::Bus | PathConstraints::Train | PathConstraints::Pedestrian => {
+                    unreachable!()
+                }
             },
             DrivingGoal::Border(_, l) => *l,
         };
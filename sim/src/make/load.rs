@@ -1,15 +1,24 @@
-use crate::{Scenario, Sim, SimOptions};
+use crate::{CrosswalkRules, Scenario, Sim, SimOptions, TripID};
 use abstutil::CmdArgs;
 use geom::Duration;
-use map_model::{Map, MapEdits};
+use map_model::{Map, MapEdits, Path, PathConstraints, TravelTimeTable, TurnPenalties, Weekday};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
+use std::collections::BTreeMap;
 
 #[derive(Clone)]
 pub struct SimFlags {
     pub load: String,
     pub use_map_fixes: bool,
     pub rng_seed: Option<u8>,
+    // A CSV of lane travel times per time-of-day, used to route cars
+    pub travel_times: Option<String>,
+    // How vehicles weigh turns while pathfinding: "typical", or a CSV of intersection delays
+    // measured in a previous run. By default, turns don't cost anything extra.
+    pub turn_penalties: Option<String>,
+    // Car routes saved by headless --dta --save_routes. Trips are matched up by ID, so this only
+    // makes sense for the same scenario, instantiated with the same --rng_seed.
+    pub car_routes: Option<String>,
     pub opts: SimOptions,
 }
 
@@ -21,6 +30,9 @@ impl SimFlags {
                 .unwrap_or_else(|| "../data/maps/montlake.bin".to_string()),
             use_map_fixes: !args.enabled("--nofixes"),
             rng_seed: args.optional_parse("--rng_seed", |s| s.parse()),
+            travel_times: args.optional("--travel_times"),
+            turn_penalties: args.optional("--turn_penalties"),
+            car_routes: args.optional("--car_routes"),
             opts: SimOptions {
                 run_name: args
                     .optional("--run_name")
@@ -28,7 +40,24 @@ impl SimFlags {
                 savestate_every: args.optional_parse("--savestate_every", Duration::parse),
                 use_freeform_policy_everywhere: args.enabled("--freeform_policy"),
                 disable_block_the_box: args.enabled("--disable_block_the_box"),
+                crosswalk_rules: if args.enabled("--peds_judge_gaps") {
+                    CrosswalkRules::PedsJudgeGaps
+                } else if args.enabled("--peds_have_right_of_way") {
+                    CrosswalkRules::PedsHaveRightOfWay
+                } else if args.enabled("--crosswalks_from_osm") {
+                    CrosswalkRules::FromOsmCrossings
+                } else {
+                    CrosswalkRules::StopSignDecides
+                },
                 recalc_lanechanging: !args.enabled("--dont_recalc_lc"),
+                use_acceleration: args.enabled("--use_acceleration"),
+                mid_lane_changing: args.enabled("--mid_lane_changing"),
+                pass_bikes: args.enabled("--pass_bikes"),
+                dynamic_rerouting: args.enabled("--dynamic_rerouting"),
+                event_logs: args.optional("--event_log").into_iter().collect(),
+                service_day: args
+                    .optional_parse("--service_day", Weekday::parse)
+                    .unwrap_or(Weekday::Monday),
             },
         }
     }
@@ -43,6 +72,9 @@ impl SimFlags {
             load: abstutil::path_map(map),
             use_map_fixes: true,
             rng_seed: Some(42),
+            travel_times: None,
+            turn_penalties: None,
+            car_routes: None,
             opts: SimOptions::new(run_name),
         }
     }
@@ -74,7 +106,9 @@ impl SimFlags {
                 timer,
             );
             map.mark_edits_fresh();
+            self.load_turn_penalties(&mut map, timer);
             map.recalculate_pathfinding_after_edits(timer);
+            self.load_travel_times(&mut map, timer);
 
             (map, sim, rng)
         } else if self.load.starts_with("../data/scenarios/") {
@@ -86,35 +120,47 @@ impl SimFlags {
             let scenario: Scenario =
                 abstutil::read_binary(&self.load, timer).expect("loading scenario failed");
 
-            let map: Map =
+            let mut map: Map =
                 abstutil::read_binary(&abstutil::path_map(&scenario.map_name), timer).unwrap();
+            self.load_travel_times(&mut map, timer);
+            self.load_turn_penalties(&mut map, timer);
+            map.recalculate_pathfinding_after_edits(timer);
 
             if opts.run_name == "unnamed" {
                 opts.run_name = scenario.scenario_name.clone();
             }
             let mut sim = Sim::new(&map, opts, timer);
+            self.load_car_routes(&mut sim, timer);
             scenario.instantiate(&mut sim, &map, &mut rng, timer);
 
             (map, sim, rng)
         } else if self.load.starts_with("../data/raw_maps/") {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(&self.load, self.use_map_fixes, timer)
+            let mut map = Map::new(&self.load, self.use_map_fixes, timer)
                 .expect(&format!("Couldn't load map from {}", self.load));
+            self.load_travel_times(&mut map, timer);
+            self.load_turn_penalties(&mut map, timer);
+            map.recalculate_pathfinding_after_edits(timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, opts, timer);
+            let mut sim = Sim::new(&map, opts, timer);
+            self.load_car_routes(&mut sim, timer);
             timer.stop("create sim");
 
             (map, sim, rng)
         } else if self.load.starts_with("../data/maps/") {
             timer.note(format!("Loading map {}", self.load));
 
-            let map: Map = abstutil::read_binary(&self.load, timer)
+            let mut map: Map = abstutil::read_binary(&self.load, timer)
                 .expect(&format!("Couldn't load map from {}", self.load));
+            self.load_travel_times(&mut map, timer);
+            self.load_turn_penalties(&mut map, timer);
+            map.recalculate_pathfinding_after_edits(timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, opts, timer);
+            let mut sim = Sim::new(&map, opts, timer);
+            self.load_car_routes(&mut sim, timer);
             timer.stop("create sim");
 
             (map, sim, rng)
@@ -122,4 +168,55 @@ impl SimFlags {
             panic!("Don't know how to load {}", self.load);
         }
     }
+
+    fn load_travel_times(&self, map: &mut Map, timer: &mut abstutil::Timer) {
+        if let Some(ref path) = self.travel_times {
+            timer.note(format!("Routing cars with travel times from {}", path));
+            let table = TravelTimeTable::load_csv(path)
+                .expect(&format!("Couldn't load travel times from {}", path));
+            map.set_travel_times(Some(table));
+        }
+    }
+
+    // Savestates already remember their assigned routes.
+    fn load_car_routes(&self, sim: &mut Sim, timer: &mut abstutil::Timer) {
+        if let Some(ref path) = self.car_routes {
+            timer.note(format!("Assigning car routes from {}", path));
+            let routes: BTreeMap<TripID, Path> = abstutil::read_binary(path, timer)
+                .expect(&format!("Couldn't load car routes from {}", path));
+            sim.assign_car_routes(routes);
+        }
+    }
+
+    // Like map edits, these don't take effect until the next recalculate_pathfinding_after_edits.
+    // That doesn't do anything when there aren't any penalties.
+    fn load_turn_penalties(&self, map: &mut Map, timer: &mut abstutil::Timer) {
+        let profile = if let Some(ref profile) = self.turn_penalties {
+            profile
+        } else {
+            return;
+        };
+        timer.note(format!("Penalizing turns using {}", profile));
+        let measured = if profile == "typical" {
+            None
+        } else {
+            Some(
+                TurnPenalties::load_measured_delays_csv(profile).expect(&format!(
+                    "Couldn't load intersection delays from {}",
+                    profile
+                )),
+            )
+        };
+        // Trains just follow their tracks.
+        for &constraints in &[
+            PathConstraints::Car,
+            PathConstraints::Bike,
+            PathConstraints::Bus,
+        ] {
+            let penalties = measured
+                .clone()
+                .unwrap_or_else(|| TurnPenalties::typical_for(constraints));
+            map.set_turn_penalties(constraints, penalties);
+        }
+    }
 }
@@ -6,6 +6,7 @@ mod spawner;
 pub use self::a_b_test::ABTest;
 pub use self::load::SimFlags;
 pub use self::scenario::{
-    BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime, SpawnTrip,
+    Blockage, BorderSpawnOverTime, Incident, IncidentKind, OriginDestination, PersonSpec, Scenario,
+    SeedParkedCars, SpawnOverTime, SpawnTrip,
 };
 pub use self::spawner::{TripSpawner, TripSpec};
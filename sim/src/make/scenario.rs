@@ -1,18 +1,20 @@
 use crate::{
     CarID, DrivingGoal, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec, VehicleType,
-    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    BIKE_LENGTH, BIKE_MAX_ACCEL, BIKE_MAX_DECEL, CAR_MAX_ACCEL, CAR_MAX_DECEL, MAX_CAR_LENGTH,
+    MIN_CAR_LENGTH,
 };
 use abstutil::{fork_rng, prettyprint_usize, Timer, WeightedUsizeChoice};
 use geom::{Distance, Duration, Speed};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, Map, PathConstraints,
-    Position, RoadID,
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, IntersectionID,
+    LaneID, Map, PathConstraints, Position, RoadID, Traversable,
 };
 use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Scenario {
@@ -28,6 +30,10 @@ pub struct Scenario {
     // Much more detailed
     pub individ_trips: Vec<SpawnTrip>,
     pub individ_parked_cars: BTreeMap<BuildingID, usize>,
+    pub people: Vec<PersonSpec>,
+
+    // Things that go wrong partway through the day
+    pub incidents: Vec<Incident>,
 }
 
 // SpawnOverTime and BorderSpawnOverTime should be kept separate. Agents in SpawnOverTime pick
@@ -64,6 +70,69 @@ pub struct SeedParkedCars {
     pub cars_per_building: WeightedUsizeChoice,
 }
 
+// Something that blocks part of the map for a while. Vehicles already on a blocked lane may finish
+// crossing it, but nobody new can enter. A blocked intersection doesn't let anybody start a turn,
+// including pedestrians.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub blockage: Blockage,
+    pub start_time: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum IncidentKind {
+    Crash,
+    StalledVehicle,
+    WorkZone,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Blockage {
+    Lanes(Vec<LaneID>),
+    Intersection(IntersectionID),
+}
+
+impl Incident {
+    pub fn blocks(&self, on: Traversable) -> bool {
+        match self.blockage {
+            Blockage::Lanes(ref lanes) => match on {
+                Traversable::Lane(l) => lanes.contains(&l),
+                Traversable::Turn(t) => lanes.contains(&t.dst),
+            },
+            Blockage::Intersection(i) => match on {
+                Traversable::Lane(_) => false,
+                Traversable::Turn(t) => t.parent == i,
+            },
+        }
+    }
+
+    // The intersections where somebody might be waiting to enter the blockage
+    pub fn affected_intersections(&self, map: &Map) -> BTreeSet<IntersectionID> {
+        match self.blockage {
+            Blockage::Lanes(ref lanes) => lanes
+                .iter()
+                .flat_map(|l| {
+                    let lane = map.get_l(*l);
+                    vec![lane.src_i, lane.dst_i]
+                })
+                .collect(),
+            Blockage::Intersection(i) => vec![i].into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for IncidentKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncidentKind::Crash => write!(f, "crash"),
+            IncidentKind::StalledVehicle => write!(f, "stalled vehicle"),
+            IncidentKind::WorkZone => write!(f, "work zone"),
+        }
+    }
+}
+
 impl Scenario {
     pub fn describe(&self) -> Vec<String> {
         vec![
@@ -81,6 +150,8 @@ impl Scenario {
                 prettyprint_usize(self.border_spawn_over_time.len())
             ),
             format!("{} SpawnTrip", prettyprint_usize(self.individ_trips.len())),
+            format!("{} PersonSpec", prettyprint_usize(self.people.len())),
+            format!("{} Incident", prettyprint_usize(self.incidents.len())),
         ]
     }
 
@@ -92,7 +163,11 @@ impl Scenario {
 
         if self.seed_buses {
             for route in map.get_all_bus_routes() {
-                sim.seed_bus_route(route, map, timer);
+                if route.timetable.is_empty() {
+                    sim.seed_bus_route(route, map, timer);
+                } else {
+                    sim.schedule_bus_route(route, map, timer);
+                }
             }
         }
 
@@ -155,10 +230,34 @@ impl Scenario {
             sim.schedule_trip(depart, spec, map);
         }
 
+        timer.start_iter("PersonSpec", self.people.len());
+        for p in &self.people {
+            timer.next();
+            let trips = p
+                .trips
+                .iter()
+                .map(|t| t.clone().to_trip_spec(rng))
+                .collect();
+            sim.schedule_person(trips, map);
+        }
+
+        for incident in &self.incidents {
+            sim.schedule_incident(incident.clone());
+        }
+
         sim.spawn_all_trips(map, timer, true);
         timer.stop(&format!("Instantiating {}", self.scenario_name));
     }
 
+    // Every individual trip, including the ones belonging to people
+    pub fn all_trips(&self) -> Vec<&SpawnTrip> {
+        let mut trips: Vec<&SpawnTrip> = self.individ_trips.iter().collect();
+        for p in &self.people {
+            trips.extend(p.trips.iter());
+        }
+        trips
+    }
+
     pub fn save(&self) {
         abstutil::save_binary_object(
             abstutil::SCENARIOS,
@@ -205,7 +304,9 @@ impl Scenario {
                 })
                 .collect(),
             individ_trips: Vec::new(),
+            people: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            incidents: Vec::new(),
         };
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
@@ -230,7 +331,9 @@ impl Scenario {
             spawn_over_time: Vec::new(),
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
+            people: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            incidents: Vec::new(),
         }
     }
 
@@ -257,7 +360,9 @@ impl Scenario {
             }],
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
+            people: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            incidents: Vec::new(),
         }
     }
 
@@ -267,6 +372,8 @@ impl Scenario {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            max_accel: CAR_MAX_ACCEL,
+            max_decel: CAR_MAX_DECEL,
         }
     }
 
@@ -280,6 +387,8 @@ impl Scenario {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            max_accel: BIKE_MAX_ACCEL,
+            max_decel: BIKE_MAX_DECEL,
         }
     }
 
@@ -807,6 +916,13 @@ fn rand_time(rng: &mut XorShiftRng, low: Duration, high: Duration) -> Duration {
     Duration::seconds(rng.gen_range(low.inner_seconds(), high.inner_seconds()))
 }
 
+// Somebody making a sequence of trips. The trips are in order, and each one starts once the
+// previous one is over.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PersonSpec {
+    pub trips: Vec<SpawnTrip>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum SpawnTrip {
     CarAppearing {
@@ -1,7 +1,7 @@
 use crate::{
     CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
-    VehicleSpec, MAX_CAR_LENGTH,
+    PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
+    VehicleSpec, VehicleType, MAX_CAR_LENGTH,
 };
 use abstutil::Timer;
 use geom::{Duration, Speed, EPSILON_DIST};
@@ -53,7 +53,13 @@ pub enum TripSpec {
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct TripSpawner {
     parked_cars_claimed: BTreeSet<CarID>,
-    trips: Vec<(Duration, Option<PedestrianID>, Option<CarID>, TripSpec)>,
+    trips: Vec<(
+        Duration,
+        Option<PersonID>,
+        Option<PedestrianID>,
+        Option<CarID>,
+        TripSpec,
+    )>,
 }
 
 impl TripSpawner {
@@ -67,6 +73,7 @@ impl TripSpawner {
     pub fn schedule_trip(
         &mut self,
         start_time: Duration,
+        person: Option<PersonID>,
         ped_id: Option<PedestrianID>,
         car_id: Option<CarID>,
         spec: TripSpec,
@@ -161,6 +168,7 @@ impl TripSpawner {
                         );
                         self.trips.push((
                             start_time,
+                            person,
                             ped_id,
                             None,
                             TripSpec::JustWalking {
@@ -176,11 +184,12 @@ impl TripSpawner {
             TripSpec::UsingTransit { .. } => {}
         };
 
-        self.trips.push((start_time, ped_id, car_id, spec));
+        self.trips.push((start_time, person, ped_id, car_id, spec));
     }
 
     pub fn spawn_all(
         &mut self,
+        now: Duration,
         map: &Map,
         parking: &ParkingSimState,
         trips: &mut TripManager,
@@ -192,12 +201,13 @@ impl TripSpawner {
             "calculate paths",
             std::mem::replace(&mut self.trips, Vec::new()),
             |tuple| {
-                let req = tuple.3.get_pathfinding_request(map, parking);
-                (tuple, req.clone(), map.pathfind(req))
+                let req = tuple.4.get_pathfinding_request(map, parking);
+                let path = map.pathfind_at(req.clone(), tuple.0);
+                (tuple, req, path)
             },
         );
         timer.start_iter("spawn trips", paths.len());
-        for ((start_time, ped_id, car_id, spec), req, maybe_path) in paths {
+        for ((start_time, person, ped_id, car_id, spec), req, maybe_path) in paths {
             timer.next();
             match spec {
                 TripSpec::CarAppearing {
@@ -224,22 +234,29 @@ impl TripSpawner {
                         ));
                     }
                     let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
-                    let trip = trips.new_trip(start_time, trip_start, legs);
+                    let trip = trips.new_trip(start_time, person, trip_start, legs);
+                    let maybe_path = if vehicle.vehicle_type == VehicleType::Car {
+                        trips.pick_car_route(start_time, trip, &req, || maybe_path)
+                    } else {
+                        maybe_path
+                    };
                     if let Some(path) = maybe_path {
                         let router = goal.make_router(path, map, vehicle.vehicle_type);
-                        scheduler.quick_push(
+                        trips.schedule_trip_start(
                             start_time,
+                            trip,
                             Command::SpawnCar(
                                 CreateCar::for_appearing(vehicle, start_pos, router, trip),
                                 retry_if_no_room,
                             ),
+                            scheduler,
                         );
                     } else {
                         timer.warn(format!(
                             "CarAppearing trip couldn't find the first path {}",
                             req
                         ));
-                        trips.abort_trip_failed_start(trip);
+                        trips.abort_trip_failed_start(now, trip, scheduler);
                     }
                 }
                 TripSpec::UsingParkedCar {
@@ -270,12 +287,17 @@ impl TripSpawner {
                         }
                         DrivingGoal::Border(_, _) => {}
                     }
-                    let trip =
-                        trips.new_trip(start_time, TripStart::Bldg(vehicle.owner.unwrap()), legs);
+                    let trip = trips.new_trip(
+                        start_time,
+                        person,
+                        TripStart::Bldg(vehicle.owner.unwrap()),
+                        legs,
+                    );
 
                     if let Some(path) = maybe_path {
-                        scheduler.quick_push(
+                        trips.schedule_trip_start(
                             start_time,
+                            trip,
                             Command::SpawnPed(CreatePedestrian {
                                 id: ped_id.unwrap(),
                                 speed: ped_speed,
@@ -284,13 +306,14 @@ impl TripSpawner {
                                 path,
                                 trip,
                             }),
+                            scheduler,
                         );
                     } else {
                         timer.warn(format!(
                             "UsingParkedCar trip couldn't find the first path {}",
                             req
                         ));
-                        trips.abort_trip_failed_start(trip);
+                        trips.abort_trip_failed_start(now, trip, scheduler);
                     }
                 }
                 TripSpec::MaybeUsingParkedCar {
@@ -302,10 +325,12 @@ impl TripSpawner {
                     // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
                     // the DrivingGoal, so we can expand the trip later.
                     let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
-                    let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs);
+                    let trip =
+                        trips.new_trip(start_time, person, TripStart::Bldg(start_bldg), legs);
 
-                    scheduler.quick_push(
+                    trips.schedule_trip_start(
                         start_time,
+                        trip,
                         Command::SpawnPed(CreatePedestrian {
                             id: ped_id.unwrap(),
                             speed: ped_speed,
@@ -315,6 +340,7 @@ impl TripSpawner {
                             path: maybe_path.unwrap(),
                             trip,
                         }),
+                        scheduler,
                     );
                 }
                 TripSpec::JustWalking {
@@ -324,6 +350,7 @@ impl TripSpawner {
                 } => {
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -336,8 +363,9 @@ impl TripSpawner {
                     );
 
                     if let Some(path) = maybe_path {
-                        scheduler.quick_push(
+                        trips.schedule_trip_start(
                             start_time,
+                            trip,
                             Command::SpawnPed(CreatePedestrian {
                                 id: ped_id.unwrap(),
                                 speed: ped_speed,
@@ -346,13 +374,14 @@ impl TripSpawner {
                                 path,
                                 trip,
                             }),
+                            scheduler,
                         );
                     } else {
                         timer.warn(format!(
                             "JustWalking trip couldn't find the first path {}",
                             req
                         ));
-                        trips.abort_trip_failed_start(trip);
+                        trips.abort_trip_failed_start(now, trip, scheduler);
                     }
                 }
                 TripSpec::UsingBike {
@@ -379,6 +408,7 @@ impl TripSpawner {
                     };
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -391,8 +421,9 @@ impl TripSpawner {
                     );
 
                     if let Some(path) = maybe_path {
-                        scheduler.quick_push(
+                        trips.schedule_trip_start(
                             start_time,
+                            trip,
                             Command::SpawnPed(CreatePedestrian {
                                 id: ped_id.unwrap(),
                                 speed: ped_speed,
@@ -401,13 +432,14 @@ impl TripSpawner {
                                 path,
                                 trip,
                             }),
+                            scheduler,
                         );
                     } else {
                         timer.warn(format!(
                             "UsingBike trip couldn't find the first path {}",
                             req
                         ));
-                        trips.abort_trip_failed_start(trip);
+                        trips.abort_trip_failed_start(now, trip, scheduler);
                     }
                 }
                 TripSpec::UsingTransit {
@@ -421,6 +453,7 @@ impl TripSpawner {
                     let walk_to = SidewalkSpot::bus_stop(stop1, map);
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -437,8 +470,9 @@ impl TripSpawner {
                     );
 
                     if let Some(path) = maybe_path {
-                        scheduler.quick_push(
+                        trips.schedule_trip_start(
                             start_time,
+                            trip,
                             Command::SpawnPed(CreatePedestrian {
                                 id: ped_id.unwrap(),
                                 speed: ped_speed,
@@ -447,13 +481,14 @@ impl TripSpawner {
                                 path,
                                 trip,
                             }),
+                            scheduler,
                         );
                     } else {
                         timer.warn(format!(
                             "UsingTransit trip couldn't find the first path {}",
                             req
                         ));
-                        trips.abort_trip_failed_start(trip);
+                        trips.abort_trip_failed_start(now, trip, scheduler);
                     }
                 }
             }
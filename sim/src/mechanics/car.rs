@@ -1,9 +1,10 @@
+use crate::mechanics::kinematics::SpeedProfile;
 use crate::{
     AgentMetadata, CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
 };
-use geom::{Distance, Duration, PolyLine};
-use map_model::{Map, Traversable, LANE_THICKNESS};
+use geom::{Distance, Duration, PolyLine, Speed, EPSILON_DIST};
+use map_model::{Map, Traversable, TurnPriority, LANE_THICKNESS};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -19,6 +20,15 @@ pub struct Car {
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
     pub last_steps: VecDeque<Traversable>,
+
+    // Only tracked when using acceleration. When the car last finished Crossing something, where
+    // its front was and how fast it was going.
+    pub crossing_exit: Option<(Duration, Distance, Speed)>,
+
+    // When the front of the car entered the current step. None if the car started partway along
+    // it.
+    pub entered_head_at: Option<Duration>,
+    pub last_reroute_check: Duration,
 }
 
 impl Car {
@@ -27,6 +37,7 @@ impl Car {
         &self,
         start_dist: Distance,
         start_time: Duration,
+        use_acceleration: bool,
         map: &Map,
     ) -> CarState {
         let dist_int = DistanceInterval::new_driving(
@@ -37,22 +48,119 @@ impl Car {
                 self.router.head().length(map)
             },
         );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        self.crossing_state_with_end_dist(dist_int, start_time, use_acceleration, map)
     }
 
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Duration,
+        use_acceleration: bool,
         map: &Map,
     ) -> CarState {
         let on = self.router.head();
+        let speed = self.max_speed_on(on, map);
+        if !use_acceleration {
+            let dt = (dist_int.end - dist_int.start) / speed;
+            return CarState::Crossing(
+                TimeInterval::new(start_time, start_time + dt),
+                dist_int,
+                None,
+            );
+        }
+
+        // Only slow down as much as the next step requires. Stop completely at the end of the
+        // path, if we're not going all the way to the end of this step, or if there's a stop sign
+        // or red light ahead.
+        let start_speed = self.current_speed(dist_int.start, start_time);
+        let make_profile = |end_speed| {
+            SpeedProfile::new(
+                dist_int.length(),
+                start_speed,
+                speed,
+                end_speed,
+                self.vehicle.max_accel,
+                self.vehicle.max_decel,
+            )
+        };
+        let profile = match self.router.maybe_next() {
+            Some(next) if dist_int.end == on.length(map) => {
+                let profile = make_profile(self.max_speed_on(next, map));
+                if must_stop_before(next, start_time + profile.total_time(), map) {
+                    make_profile(Speed::ZERO)
+                } else {
+                    profile
+                }
+            }
+            _ => make_profile(Speed::ZERO),
+        };
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + profile.total_time()),
+            dist_int,
+            Some(profile),
+        )
+    }
+
+    pub fn max_speed_on(&self, on: Traversable, map: &Map) -> Speed {
         let mut speed = on.speed_limit(map);
-        if let Some(s) = self.vehicle.max_speed {
+        if let Some(mut s) = self.vehicle.max_speed {
+            if let (VehicleType::Bike, Traversable::Lane(l)) = (self.vehicle.vehicle_type, on) {
+                s = bike_speed_on_grade(s, map.get_l(l).grade(map));
+            }
             speed = speed.min(s);
         }
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        speed
+    }
+
+    // Only meaningful when using acceleration. How fast is the car going right now, if its front
+    // is at this distance?
+    fn current_speed(&self, front: Distance, now: Duration) -> Speed {
+        match self.state {
+            CarState::Crossing(ref time_int, _, Some(ref profile)) => {
+                // If somebody in front has been holding us back, assume we were stopped behind
+                // them.
+                if front + EPSILON_DIST < self.state.crossing_front(now) {
+                    Speed::ZERO
+                } else {
+                    profile.speed_after(now - time_int.start)
+                }
+            }
+            // Only keep our momentum if we didn't have to wait at all.
+            CarState::WaitingToAdvance => match self.crossing_exit {
+                Some((t, _, speed)) if t == now => speed,
+                _ => Speed::ZERO,
+            },
+            CarState::Queued => match self.crossing_exit {
+                Some((t, dist, speed)) if t == now && dist == front => speed,
+                _ => Speed::ZERO,
+            },
+            CarState::Crossing(_, _, None)
+            | CarState::Unparking(_, _, _)
+            | CarState::Parking(_, _, _)
+            | CarState::Idling(_, _) => Speed::ZERO,
+        }
+    }
+
+    // If nothing gets in the way, when will the front of the car reach end_dist along the current
+    // step?
+    pub fn predict_time_to_reach(
+        &self,
+        start_dist: Distance,
+        end_dist: Distance,
+        now: Duration,
+        use_acceleration: bool,
+        map: &Map,
+    ) -> Duration {
+        if let CarState::Crossing(ref time_int, ref dist_int, Some(ref profile)) = self.state {
+            return time_int.start + profile.time_to_reach(end_dist - dist_int.start);
+        }
+        self.crossing_state_with_end_dist(
+            DistanceInterval::new_driving(start_dist, end_dist),
+            now,
+            use_acceleration,
+            map,
+        )
+        .get_end_time()
     }
 
     pub fn get_draw_car(
@@ -156,7 +264,7 @@ impl Car {
             status: match self.state {
                 CarState::Queued => CarStatus::Moving,
                 CarState::WaitingToAdvance => CarStatus::Moving,
-                CarState::Crossing(_, _) => CarStatus::Moving,
+                CarState::Crossing(_, _, _) => CarStatus::Moving,
                 // Eh they're technically moving, but this is a bit easier to spot
                 CarState::Unparking(_, _, _) => CarStatus::Parked,
                 CarState::Parking(_, _, _) => CarStatus::Parked,
@@ -164,7 +272,7 @@ impl Car {
                 CarState::Idling(_, _) => CarStatus::Parked,
             },
             on: self.router.head(),
-            label: if self.vehicle.vehicle_type == VehicleType::Bus {
+            label: if self.vehicle.vehicle_type.is_transit() {
                 Some(
                     map.get_br(transit.bus_route(self.vehicle.id))
                         .name
@@ -194,7 +302,8 @@ impl Car {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum CarState {
-    Crossing(TimeInterval, DistanceInterval),
+    // The SpeedProfile is only used with acceleration; otherwise the speed is constant.
+    Crossing(TimeInterval, DistanceInterval, Option<SpeedProfile>),
     Queued,
     WaitingToAdvance,
     // Where's the front of the car while this is happening?
@@ -206,7 +315,7 @@ pub enum CarState {
 impl CarState {
     pub fn get_end_time(&self) -> Duration {
         match self {
-            CarState::Crossing(ref time_int, _) => time_int.end,
+            CarState::Crossing(ref time_int, _, _) => time_int.end,
             CarState::Queued => unreachable!(),
             CarState::WaitingToAdvance => unreachable!(),
             CarState::Unparking(_, _, ref time_int) => time_int.end,
@@ -214,4 +323,68 @@ impl CarState {
             CarState::Idling(_, ref time_int) => time_int.end,
         }
     }
+
+    // Where's the front of the car while Crossing, ignoring anybody in the way?
+    pub fn crossing_front(&self, now: Duration) -> Distance {
+        match self {
+            // TODO Why percent_clamp_end? We process car updates in any order, so we might
+            // calculate this before moving this car from Crossing to another state.
+            CarState::Crossing(ref time_int, ref dist_int, None) => {
+                dist_int.lerp(time_int.percent_clamp_end(now))
+            }
+            CarState::Crossing(ref time_int, ref dist_int, Some(ref profile)) => {
+                if now >= time_int.end {
+                    dist_int.end
+                } else {
+                    (dist_int.start + profile.dist_after(now - time_int.start)).min(dist_int.end)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Will a driver arriving at the end of their lane at this time have to stop before the next turn?
+// Drivers only see the stop sign or the signal's fixed timing, so this doesn't know about
+// actuated signals extending a phase or other intersection policies.
+fn must_stop_before(next: Traversable, arrival: Duration, map: &Map) -> bool {
+    if let Traversable::Turn(t) = next {
+        if let Some(signal) = map.maybe_get_traffic_signal(t.parent) {
+            let (_, phase, _) = signal.current_phase_and_remaining_time(arrival);
+            return phase.get_priority_of_turn(t, signal) == TurnPriority::Banned;
+        }
+        if let Some(sign) = map.maybe_get_stop_sign(t.parent) {
+            return sign.get_priority(t, map) != TurnPriority::Protected;
+        }
+    }
+    false
+}
+
+// Bikes slow down going uphill. Assume they ride as fast as usual on the way down.
+fn bike_speed_on_grade(speed: Speed, grade: f64) -> Speed {
+    if grade > 0.0 {
+        speed * (1.0 / (1.0 + 10.0 * grade))
+    } else {
+        speed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bike_speed_on_grade;
+    use geom::Speed;
+
+    fn approx(a: Speed, b: f64) {
+        let a = a.inner_meters_per_second();
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn bikes_slow_down_uphill() {
+        let usual = Speed::meters_per_second(4.0);
+        approx(bike_speed_on_grade(usual, 0.0), 4.0);
+        approx(bike_speed_on_grade(usual, 0.05), 4.0 / 1.5);
+        approx(bike_speed_on_grade(usual, 0.1), 2.0);
+        approx(bike_speed_on_grade(usual, -0.1), 4.0);
+    }
 }
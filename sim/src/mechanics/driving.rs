@@ -1,24 +1,36 @@
 use crate::mechanics::car::{Car, CarState};
+use crate::mechanics::travel_times::LiveTravelTimes;
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
     Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
-    TransitSimState, TripManager, TripPositions, UnzoomedAgent, WalkingSimState,
+    TransitSimState, TripManager, TripPositions, UnzoomedAgent, VehicleType, WalkingSimState,
     FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine};
-use map_model::{BuildingID, LaneID, Map, Path, PathStep, Traversable};
+use map_model::{BuildingID, LaneID, LaneType, Map, Path, PathStep, Traversable};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
 const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
-const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
 pub(crate) const BLIND_RETRY_TO_REACH_END_DIST: Duration = Duration::const_seconds(5.0);
+const RETRY_LANE_CHANGE: Duration = Duration::const_seconds(2.0);
+// Oncoming traffic has to be at least this far away before a car passes a bike in the same lane
+// How long a bus that can't reach its next stop waits before trying again
+const RETRY_BUS_DEPARTURE: Duration = Duration::const_seconds(60.0);
+const TIME_TO_PASS_BIKE: Duration = Duration::const_seconds(5.0);
+// How often a driver reconsiders their route
+const REROUTE_CHECK_INTERVAL: Duration = Duration::const_seconds(120.0);
+// Looking for a new route searches the whole map, so only this many drivers get to do it in the
+// same minute.
+const MAX_REROUTES_PER_MINUTE: usize = 50;
+// Roughly how long each stopped car takes to get moving once the way ahead clears
+const QUEUE_DISCHARGE_HEADWAY: Duration = Duration::const_seconds(2.0);
 
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct DrivingSimState {
@@ -33,17 +45,37 @@ pub struct DrivingSimState {
     )]
     queues: BTreeMap<Traversable, Queue>,
     events: Vec<Event>,
+    travel_times: LiveTravelTimes,
+    // The start of the current minute, and how many drivers looked for a new route during it
+    reroutes_this_minute: (Duration, usize),
 
     recalc_lanechanging: bool,
+    use_acceleration: bool,
+    mid_lane_changing: bool,
+    pass_bikes: bool,
+    dynamic_rerouting: bool,
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(
+        map: &Map,
+        recalc_lanechanging: bool,
+        use_acceleration: bool,
+        mid_lane_changing: bool,
+        pass_bikes: bool,
+        dynamic_rerouting: bool,
+    ) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
+            travel_times: LiveTravelTimes::new(),
+            reroutes_this_minute: (Duration::ZERO, 0),
             recalc_lanechanging,
+            use_acceleration,
+            mid_lane_changing,
+            pass_bikes,
+            dynamic_rerouting,
         };
 
         for l in map.all_lanes() {
@@ -93,7 +125,16 @@ impl DrivingSimState {
                 blocked_since: None,
                 started_at: now,
                 trip: params.trip,
+                crossing_exit: None,
+                entered_head_at: None,
+                last_reroute_check: now,
             };
+            // Cars starting on a blocked lane are treated like they were already there, but new
+            // cars shouldn't head into an incident.
+            if route_around_incidents(&mut car, params.start_dist, intersections, map) {
+                self.events
+                    .push(Event::CarReroutedAroundIncident(car.vehicle.id));
+            }
             if let Some(p) = params.maybe_parked_car {
                 car.state = CarState::Unparking(
                     params.start_dist,
@@ -121,7 +162,7 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(params.start_dist, now, map);
+                car.state = car.crossing_state(params.start_dist, now, self.use_acceleration, map);
             }
             scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             {
@@ -238,13 +279,21 @@ impl DrivingSimState {
         scheduler: &mut Scheduler,
     ) -> bool {
         match car.state {
-            CarState::Crossing(_, _) => {
+            CarState::Crossing(_, ref dist_int, ref profile) => {
+                if let Some(ref profile) = profile {
+                    car.crossing_exit = Some((now, dist_int.end, profile.end_speed()));
+                }
                 car.state = CarState::Queued;
                 car.blocked_since = Some(now);
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
                     return true;
                 }
+                if car.vehicle.vehicle_type == VehicleType::Bike
+                    && self.enter_bike_box(car, now, map, intersections, scheduler)
+                {
+                    return false;
+                }
                 let queue = &self.queues[&car.router.head()];
                 if queue.cars[0] == car.vehicle.id && queue.laggy_head.is_none() {
                     // Want to re-run, but no urgency about it happening immediately.
@@ -253,6 +302,11 @@ impl DrivingSimState {
                         car.router.opportunistically_lanechange(&self.queues, map);
                     }
                     scheduler.push(now, Command::UpdateCar(car.vehicle.id));
+                } else if (self.mid_lane_changing || self.pass_bikes)
+                    && queue.cars[0] != car.vehicle.id
+                {
+                    // Stuck behind somebody. Once the car is back in place, maybe go around them.
+                    scheduler.update(now, Command::TryLaneChange(car.vehicle.id));
                 }
             }
             CarState::Unparking(front, _, _) => {
@@ -264,12 +318,27 @@ impl DrivingSimState {
                     car.router
                         .maybe_handle_end(front, &car.vehicle, parking, map);
                 }
-                car.state = car.crossing_state(front, now, map);
+                car.state = car.crossing_state(front, now, self.use_acceleration, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
             CarState::Idling(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id);
-                car.state = car.crossing_state(dist, now, map);
+                match transit.bus_departed_from_stop(car.vehicle.id, map) {
+                    Ok(router) => {
+                        car.router = router;
+                    }
+                    Err(err) => {
+                        // Maybe the map edits that cut off the next stop get reverted.
+                        println!("{}. Trying again later.", err);
+                        car.state = CarState::Idling(
+                            dist,
+                            TimeInterval::new(now, now + RETRY_BUS_DEPARTURE),
+                        );
+                        scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        return false;
+                    }
+                }
+                car.state = car.crossing_state(dist, now, self.use_acceleration, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
 
                 // Update our follower, so they know we stopped idling.
@@ -290,6 +359,7 @@ impl DrivingSimState {
                                     // Since the follower was Queued, this must be where they are.
                                     dist - car.vehicle.length - FOLLOWING_DISTANCE,
                                     now,
+                                    self.use_acceleration,
                                     map,
                                 );
                                 follower.blocked_since = None;
@@ -302,7 +372,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could jump
                         // forwards here; the leader is still in front of them.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::Idling(_, _) => {}
@@ -348,7 +418,44 @@ impl DrivingSimState {
                 // way, until laggy_head is None.
 
                 let last_step = car.router.advance(&car.vehicle, parking, map);
-                car.state = car.crossing_state(Distance::ZERO, now, map);
+                // Only rerouting looks at observed travel times.
+                if self.dynamic_rerouting {
+                    if let Some(t) = car.entered_head_at {
+                        self.travel_times.observe(last_step, now - t, now);
+                    }
+                    car.entered_head_at = Some(now);
+                }
+                if self.dynamic_rerouting
+                    && goto.maybe_lane().is_some()
+                    && car.vehicle.vehicle_type == VehicleType::Car
+                    && now - car.last_reroute_check >= REROUTE_CHECK_INTERVAL
+                    && !car.router.last_step()
+                {
+                    if !car
+                        .router
+                        .path_is_congested(|on| self.live_travel_time(on, now, map), map)
+                    {
+                        car.last_reroute_check = now;
+                    } else if self.use_reroute_budget(now) {
+                        car.last_reroute_check = now;
+                        if car.router.maybe_reroute(
+                            |on| self.live_travel_time(on, now, map),
+                            Distance::ZERO,
+                            map,
+                        ) {
+                            self.events.push(Event::CarRerouted(car.vehicle.id));
+                        }
+                    }
+                    // Otherwise too many drivers already looked this minute, so try again after
+                    // the next lane.
+                }
+                if goto.maybe_lane().is_some()
+                    && route_around_incidents(car, Distance::ZERO, intersections, map)
+                {
+                    self.events
+                        .push(Event::CarReroutedAroundIncident(car.vehicle.id));
+                }
+                car.state = car.crossing_state(Distance::ZERO, now, self.use_acceleration, map);
                 car.blocked_since = None;
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
@@ -363,15 +470,13 @@ impl DrivingSimState {
                     // late, and the car actually crosses an entire new traversable in the
                     // meantime.
                     scheduler.update(
-                        car.crossing_state_with_end_dist(
-                            DistanceInterval::new_driving(
-                                Distance::ZERO,
-                                car.vehicle.length + FOLLOWING_DISTANCE,
-                            ),
+                        car.predict_time_to_reach(
+                            Distance::ZERO,
+                            car.vehicle.length + FOLLOWING_DISTANCE,
                             now,
+                            self.use_acceleration,
                             map,
-                        )
-                        .get_end_time(),
+                        ),
                         Command::UpdateLaggyHead(car.vehicle.id),
                     );
                 }
@@ -414,7 +519,7 @@ impl DrivingSimState {
         // Just two cases here. In all cases, we leave the Queued state.
         car.blocked_since = None;
         match car.state {
-            CarState::Crossing(_, _)
+            CarState::Crossing(_, _, _)
             | CarState::Unparking(_, _, _)
             | CarState::Idling(_, _)
             | CarState::WaitingToAdvance => unreachable!(),
@@ -424,10 +529,10 @@ impl DrivingSimState {
                     .maybe_handle_end(our_dist, &car.vehicle, parking, map)
                 {
                     Some(ActionAtEnd::VanishAtBorder(i)) => {
-                        trips.car_or_bike_reached_border(now, car.vehicle.id, i);
+                        trips.car_or_bike_reached_border(now, car.vehicle.id, i, scheduler);
                     }
                     Some(ActionAtEnd::AbortTrip) => {
-                        trips.abort_trip_impossible_parking(car.vehicle.id);
+                        trips.abort_trip_impossible_parking(now, car.vehicle.id, scheduler);
                     }
                     Some(ActionAtEnd::StartParking(spot)) => {
                         car.state = CarState::Parking(
@@ -444,7 +549,7 @@ impl DrivingSimState {
                         return true;
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
-                        car.state = car.crossing_state(our_dist, now, map);
+                        car.state = car.crossing_state(our_dist, now, self.use_acceleration, map);
                         scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         return true;
@@ -453,21 +558,21 @@ impl DrivingSimState {
                         trips.bike_reached_end(now, car.vehicle.id, bike_rack, map, scheduler);
                     }
                     Some(ActionAtEnd::BusAtStop) => {
-                        transit.bus_arrived_at_stop(
+                        if let Some(wait) = transit.bus_arrived_at_stop(
                             now,
                             car.vehicle.id,
                             trips,
                             walking,
                             scheduler,
                             map,
-                        );
-                        car.state = CarState::Idling(
-                            our_dist,
-                            TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
-                        );
-                        scheduler
-                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                        return true;
+                        ) {
+                            car.state =
+                                CarState::Idling(our_dist, TimeInterval::new(now, now + wait));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            return true;
+                        }
+                        trips.bus_finished_trip(now, car.vehicle.id);
                     }
                     None => {
                         scheduler.push(
@@ -479,8 +584,8 @@ impl DrivingSimState {
                         // to be slower otherwise. :(
                         /*
                         // If this car wasn't blocked at all, when would it reach its goal?
-                        let ideal_end_time = match car.crossing_state(our_dist, now, map) {
-                            CarState::Crossing(time_int, _) => time_int.end,
+                        let ideal_end_time = match car.crossing_state(our_dist, now, self.use_acceleration, map) {
+                            CarState::Crossing(time_int, _, _) => time_int.end,
                             _ => unreachable!(),
                         };
                         if ideal_end_time == now {
@@ -569,7 +674,20 @@ impl DrivingSimState {
 
         // We might've scheduled one of those using BLIND_RETRY_TO_CREEP_FORWARDS.
         scheduler.cancel(Command::UpdateLaggyHead(car.vehicle.id));
+        scheduler.cancel(Command::TryLaneChange(car.vehicle.id));
 
+        self.update_follower(&dists, idx, now, map, scheduler);
+    }
+
+    // Call after the car at dists[idx] leaves the queue.
+    fn update_follower(
+        &mut self,
+        dists: &[(CarID, Distance)],
+        idx: usize,
+        now: Duration,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
         // Update the follower so that they don't suddenly jump forwards.
         if idx != dists.len() - 1 {
             let (follower_id, follower_dist) = dists[idx + 1];
@@ -578,12 +696,13 @@ impl DrivingSimState {
             // lead car's back is still sticking out. Need to still be bound by them, even
             // though they don't exist! If the leader just parked, then we're fine.
             match follower.state {
-                CarState::Queued | CarState::Crossing(_, _) => {
+                CarState::Queued | CarState::Crossing(_, _, _) => {
                     // If the follower was still Crossing, they might not've been blocked
                     // by leader yet. In that case, recalculating their Crossing state is a
                     // no-op. But if they were blocked, then this will prevent them from
                     // jumping forwards.
-                    follower.state = follower.crossing_state(follower_dist, now, map);
+                    follower.state =
+                        follower.crossing_state(follower_dist, now, self.use_acceleration, map);
                     follower.blocked_since = None;
                     scheduler.update(
                         follower.state.get_end_time(),
@@ -622,13 +741,8 @@ impl DrivingSimState {
             let our_dist = dists.last().unwrap().1;
             let car = &self.cars[&id];
             if our_dist < our_len {
-                let retry_at = car
-                    .crossing_state_with_end_dist(
-                        DistanceInterval::new_driving(our_dist, our_len),
-                        now,
-                        map,
-                    )
-                    .get_end_time();
+                let retry_at =
+                    car.predict_time_to_reach(our_dist, our_len, now, self.use_acceleration, map);
                 // Sometimes due to rounding, retry_at will be exactly time, but we really need to
                 // wait a bit longer.
                 // TODO Smarter retry based on states and stuckness?
@@ -654,6 +768,296 @@ impl DrivingSimState {
         self.cars.insert(id, car);
     }
 
+    // Only called when mid_lane_changing or pass_bikes is enabled. If this car is stuck behind
+    // something slow or stopped partway along a lane, move to an adjacent lane if there's a big
+    // enough gap there, or pass a bike without leaving the lane.
+    pub fn try_lane_change(
+        &mut self,
+        id: CarID,
+        now: Duration,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let from = self.cars[&id].router.head();
+        let dists = self.queues[&from].get_car_positions(now, &self.cars, &self.queues);
+        let idx = dists.iter().position(|(c, _)| *c == id).unwrap();
+        let our_dist = dists[idx].1;
+        let car = &self.cars[&id];
+
+        // The car might not be stuck anymore. Don't bother with the last step; the goal is
+        // somewhere along this lane. And only change lanes when the car is entirely on this lane,
+        // so laggy heads and turns don't need to be fixed up.
+        if car.state != CarState::Queued
+            || from.maybe_turn().is_some()
+            || car.router.last_step()
+            || idx == 0
+            || !car.last_steps.is_empty()
+            || our_dist < car.vehicle.length + FOLLOWING_DISTANCE
+        {
+            return;
+        }
+        if !worth_passing(car, &self.cars[&dists[idx - 1].0]) {
+            return;
+        }
+
+        // Gap acceptance: there has to be room in front of and behind the car on the other lane
+        // right now.
+        let candidates = if self.mid_lane_changing {
+            car.router.mid_lane_change_candidates(map)
+        } else {
+            Vec::new()
+        };
+        // Nobody new can enter a lane or turn blocked by an incident, even from the side.
+        let target = candidates.into_iter().find_map(|(l, turn)| {
+            if intersections.is_blocked(Traversable::Lane(l))
+                || intersections.is_blocked(Traversable::Turn(turn))
+            {
+                return None;
+            }
+            let queue = &self.queues[&Traversable::Lane(l)];
+            if our_dist > queue.geom_len || !queue.room_for_car(car) {
+                return None;
+            }
+            queue
+                .get_idx_to_insert_car(our_dist, car.vehicle.length, now, &self.cars, &self.queues)
+                .map(|insert_idx| (l, turn, insert_idx))
+        });
+        let (new_lane, new_turn, insert_idx) = match target {
+            Some(x) => x,
+            None => {
+                if self.pass_bikes && self.try_passing_bike(id, &dists, idx, now, map, scheduler) {
+                    return;
+                }
+                scheduler.update(now + RETRY_LANE_CHANGE, Command::TryLaneChange(id));
+                return;
+            }
+        };
+
+        let mut car = self.cars.remove(&id).unwrap();
+        {
+            let queue = self.queues.get_mut(&from).unwrap();
+            assert_eq!(queue.cars.remove(idx).unwrap(), id);
+            queue.free_reserved_space(&car);
+            intersections.space_freed(now, map.get_l(from.as_lane()).src_i, scheduler, map);
+        }
+        self.update_follower(&dists, idx, now, map, scheduler);
+
+        car.router.change_current_lane(new_lane, new_turn, map);
+        car.state = car.crossing_state(our_dist, now, self.use_acceleration, map);
+        car.blocked_since = None;
+        scheduler.push(car.state.get_end_time(), Command::UpdateCar(id));
+        {
+            let queue = self.queues.get_mut(&Traversable::Lane(new_lane)).unwrap();
+            queue.cars.insert(insert_idx, id);
+            queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+        }
+        self.events
+            .push(Event::CarChangedLanes(id, from.as_lane(), new_lane));
+        self.cars.insert(id, car);
+    }
+
+    // The car directly behind a bike jumps ahead of it, staying in the same lane. There has to be
+    // room in front of the bike, and the oncoming lane has to be clear. Returns true if the car
+    // passed.
+    fn try_passing_bike(
+        &mut self,
+        id: CarID,
+        dists: &[(CarID, Distance)],
+        idx: usize,
+        now: Duration,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let (bike, bike_dist) = dists[idx - 1];
+        let our_dist = dists[idx].1;
+        let car = &self.cars[&id];
+        if car.vehicle.vehicle_type != VehicleType::Car
+            || self.cars[&bike].vehicle.vehicle_type != VehicleType::Bike
+        {
+            return false;
+        }
+
+        let from = car.router.head();
+        let new_dist = bike_dist + FOLLOWING_DISTANCE + car.vehicle.length;
+        let queue = &self.queues[&from];
+        if new_dist > queue.geom_len
+            || queue.get_idx_to_insert_car(
+                new_dist,
+                car.vehicle.length,
+                now,
+                &self.cars,
+                &self.queues,
+            ) != Some(idx - 1)
+        {
+            return false;
+        }
+        if !self.oncoming_clear(
+            from.as_lane(),
+            our_dist - car.vehicle.length,
+            new_dist,
+            now,
+            map,
+        ) {
+            return false;
+        }
+
+        let mut car = self.cars.remove(&id).unwrap();
+        {
+            let queue = self.queues.get_mut(&from).unwrap();
+            assert_eq!(queue.cars.remove(idx).unwrap(), id);
+            queue.cars.insert(idx - 1, id);
+        }
+        self.update_follower(dists, idx, now, map, scheduler);
+
+        car.state = car.crossing_state(new_dist, now, self.use_acceleration, map);
+        // Moving ahead in the queue happens immediately, but driving around the bike doesn't.
+        // Don't reach the end of the lane any sooner than covering the extra distance allows.
+        let pass_time = (new_dist - our_dist) / car.max_speed_on(from, map);
+        if let CarState::Crossing(ref mut time_int, _, _) = car.state {
+            time_int.end += pass_time;
+        }
+        car.blocked_since = None;
+        scheduler.push(car.state.get_end_time(), Command::UpdateCar(id));
+        self.cars.insert(id, car);
+        self.events
+            .push(Event::CarPassedBike(id, bike, from.as_lane()));
+        true
+    }
+
+    // Is the centermost lane going the other way clear alongside [start, end] of this lane, and
+    // far enough past end that oncoming traffic can't get there mid-pass? Lanes on opposite sides
+    // of a road are close enough in length to just flip distances.
+    fn oncoming_clear(
+        &self,
+        l: LaneID,
+        start: Distance,
+        end: Distance,
+        now: Duration,
+        map: &Map,
+    ) -> bool {
+        let road = map.get_parent(l);
+        let other_side = if road.is_forwards(l) {
+            &road.children_backwards
+        } else {
+            &road.children_forwards
+        };
+        let oncoming = match other_side.first() {
+            // Nobody drives in a center turn lane.
+            Some((_, LaneType::SharedLeftTurn)) => {
+                return true;
+            }
+            Some((id, LaneType::Driving)) | Some((id, LaneType::Bus)) => *id,
+            // Don't pass on one-ways or into bike or parking lanes.
+            _ => {
+                return false;
+            }
+        };
+
+        let queue = &self.queues[&Traversable::Lane(oncoming)];
+        let sight = road.get_speed_limit() * TIME_TO_PASS_BIKE;
+        queue
+            .get_car_positions(now, &self.cars, &self.queues)
+            .into_iter()
+            .all(|(c, front)| {
+                let near = queue.geom_len - front;
+                let far = near + self.cars[&c].vehicle.length;
+                far < start || near > end + sight
+            })
+    }
+
+    // A bike stopped behind cars waiting at a signal with a bike box moves up to the front of the
+    // queue, behind any other bikes already in the box. The cars it passes scoot back into the space
+    // the bike left, so nobody behind the bike moves. Returns true if the bike entered the box.
+    fn enter_bike_box(
+        &mut self,
+        bike: &mut Car,
+        now: Duration,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let lane = match bike.router.head() {
+            Traversable::Lane(l) => map.get_l(l),
+            Traversable::Turn(_) => {
+                return false;
+            }
+        };
+        let has_box = map
+            .maybe_get_traffic_signal(lane.dst_i)
+            .map(|ts| ts.bike_boxes.contains(&lane.parent))
+            .unwrap_or(false);
+        let queue = &self.queues[&Traversable::Lane(lane.id)];
+        if !has_box || queue.laggy_head.is_some() {
+            return false;
+        }
+
+        // The bike itself isn't in self.cars right now.
+        let idx = queue
+            .cars
+            .iter()
+            .position(|c| *c == bike.vehicle.id)
+            .unwrap();
+        let box_idx = queue
+            .cars
+            .iter()
+            .take(idx)
+            .take_while(|c| self.cars[*c].vehicle.vehicle_type == VehicleType::Bike)
+            .count();
+        if box_idx == idx {
+            return false;
+        }
+        // Everybody ahead has to be stopped and waiting for the intersection.
+        let all_stopped = queue.cars.iter().take(idx).all(|c| {
+            let car = &self.cars[c];
+            !car.router.last_step()
+                && (car.state == CarState::Queued || car.state == CarState::WaitingToAdvance)
+        });
+        if !all_stopped || self.cars[&queue.cars[0]].state != CarState::WaitingToAdvance {
+            return false;
+        }
+
+        // Everybody's stopped bumper-to-bumper, so the bike has to ride past the vehicles between
+        // the box and itself. It moves ahead in the queue immediately, but doesn't reach its spot
+        // in the box until it's covered that distance.
+        let spacing = |c: &CarID| self.cars[c].vehicle.length + FOLLOWING_DISTANCE;
+        let box_dist = queue.geom_len
+            - queue
+                .cars
+                .iter()
+                .take(box_idx)
+                .fold(Distance::ZERO, |sum, c| sum + spacing(c));
+        let ride = queue
+            .cars
+            .iter()
+            .skip(box_idx)
+            .take(idx - box_idx)
+            .fold(Distance::ZERO, |sum, c| sum + spacing(c));
+        if box_idx == 0 {
+            // The car at the front has to wait behind the bike now.
+            let front = self.cars.get_mut(&queue.cars[0]).unwrap();
+            front.state = CarState::Queued;
+            if let Some(Traversable::Turn(t)) = front.router.maybe_next() {
+                intersections.cancel_request(AgentID::Car(front.vehicle.id), t);
+            }
+            scheduler.cancel(Command::UpdateCar(front.vehicle.id));
+        }
+        let arrive = now + ride / bike.max_speed_on(Traversable::Lane(lane.id), map);
+        bike.state = CarState::Crossing(
+            TimeInterval::new(now, arrive),
+            DistanceInterval::new_driving(box_dist, box_dist),
+            None,
+        );
+        scheduler.push(arrive, Command::UpdateCar(bike.vehicle.id));
+
+        let queue = self.queues.get_mut(&Traversable::Lane(lane.id)).unwrap();
+        assert_eq!(queue.cars.remove(idx).unwrap(), bike.vehicle.id);
+        queue.cars.insert(box_idx, bike.vehicle.id);
+        self.events
+            .push(Event::BikeEnteredBikeBox(bike.vehicle.id, lane.id));
+        true
+    }
+
     fn clear_last_steps(
         &mut self,
         now: Duration,
@@ -710,7 +1114,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could jump
                         // forwards here; the leader vanished from the end of the traversable.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::Idling(_, _) => {}
@@ -722,6 +1126,29 @@ impl DrivingSimState {
         }
     }
 
+    // Which of these lanes have a vehicle within detector_length of the end?
+    pub fn detect_vehicles(
+        &self,
+        lanes: &[LaneID],
+        detector_length: Distance,
+        now: Duration,
+    ) -> BTreeSet<LaneID> {
+        let mut detected = BTreeSet::new();
+        for l in lanes {
+            if let Some(queue) = self.queues.get(&Traversable::Lane(*l)) {
+                if queue.cars.is_empty() {
+                    continue;
+                }
+                // Only need to check the lead vehicle.
+                let (_, front) = queue.get_car_positions(now, &self.cars, &self.queues)[0];
+                if front >= queue.geom_len - detector_length {
+                    detected.insert(*l);
+                }
+            }
+        }
+        detected
+    }
+
     pub fn get_unzoomed_agents(&self, now: Duration, map: &Map) -> Vec<UnzoomedAgent> {
         let mut result = Vec::new();
 
@@ -948,4 +1375,112 @@ impl DrivingSimState {
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
+
+    // An incident just started. Everybody who hasn't started their next turn yet gets a chance
+    // to avoid it; anybody who can't find a way around waits for it to clear.
+    pub fn incident_started(
+        &mut self,
+        now: Duration,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let mut fronts: BTreeMap<CarID, Distance> = BTreeMap::new();
+        for (on, queue) in &self.queues {
+            if on.maybe_lane().is_some() {
+                fronts.extend(queue.get_car_positions(now, &self.cars, &self.queues));
+            }
+        }
+        for car in self.cars.values_mut() {
+            let old_next = car.router.maybe_next();
+            // Cars in the middle of a turn don't have a front on a lane, but they can't route
+            // around anything yet anyway.
+            let front = fronts
+                .get(&car.vehicle.id)
+                .cloned()
+                .unwrap_or(Distance::ZERO);
+            if !route_around_incidents(car, front, intersections, map) {
+                continue;
+            }
+            self.events
+                .push(Event::CarReroutedAroundIncident(car.vehicle.id));
+            // They already asked to start the old turn.
+            if car.state == CarState::WaitingToAdvance {
+                if let Some(Traversable::Turn(t)) = old_next {
+                    intersections.cancel_request(AgentID::Car(car.vehicle.id), t);
+                }
+                scheduler.update(now, Command::UpdateCar(car.vehicle.id));
+            }
+        }
+    }
+
+    // How long it'll take to cross something right now. Recent observations only describe cars
+    // that already made it across, so also look at everybody stopped there now. Anybody who's
+    // been stuck a while, like in gridlock, means it takes at least that long, and the whole
+    // queue has to clear before a newcomer gets through.
+    fn live_travel_time(&self, on: Traversable, now: Duration, map: &Map) -> Duration {
+        let dt = self.travel_times.get(on, now, map);
+        let queue = if let Some(q) = self.queues.get(&on) {
+            q
+        } else {
+            return dt;
+        };
+        let mut stuck = Duration::ZERO;
+        let mut num_stopped = 0;
+        for id in &queue.cars {
+            if let Some(t) = self.cars.get(id).and_then(|car| car.blocked_since) {
+                stuck = stuck.max(now - t);
+                num_stopped += 1;
+            }
+        }
+        let free_flow = on.length(map) / on.speed_limit(map);
+        dt.max(stuck)
+            .max(free_flow + QUEUE_DISCHARGE_HEADWAY * (num_stopped as f64))
+    }
+
+    // Returns false if too many drivers already looked for a new route this minute.
+    fn use_reroute_budget(&mut self, now: Duration) -> bool {
+        let minute = Duration::minutes((now.inner_seconds() / 60.0) as usize);
+        if self.reroutes_this_minute.0 != minute {
+            self.reroutes_this_minute = (minute, 0);
+        }
+        if self.reroutes_this_minute.1 >= MAX_REROUTES_PER_MINUTE {
+            return false;
+        }
+        self.reroutes_this_minute.1 += 1;
+        true
+    }
+}
+
+// True if the path changed
+fn route_around_incidents(
+    car: &mut Car,
+    front: Distance,
+    intersections: &IntersectionSimState,
+    map: &Map,
+) -> bool {
+    if !intersections.any_active_incidents() {
+        return false;
+    }
+    car.router.route_around(
+        |on| intersections.is_blocked(on),
+        car.vehicle.vehicle_type.to_constraints(),
+        front,
+        map,
+    )
+}
+
+// Is the leader going to hold this car up for a while?
+fn worth_passing(car: &Car, leader: &Car) -> bool {
+    match leader.state {
+        // Buses at a stop and cars getting in or out of parking spots
+        CarState::Idling(_, _) | CarState::Parking(_, _, _) | CarState::Unparking(_, _, _) => true,
+        // Something like a bike that's still moving. If the leader is stuck too, there's no point.
+        CarState::Crossing(_, _, _) => match (leader.vehicle.max_speed, car.vehicle.max_speed) {
+            (Some(s1), Some(s2)) => s1 < s2,
+            (Some(_), None) => true,
+            (None, _) => false,
+        },
+        CarState::Queued | CarState::WaitingToAdvance => false,
+    }
 }
@@ -1,40 +1,154 @@
 use crate::mechanics::car::Car;
 use crate::mechanics::Queue;
-use crate::{AgentID, Command, Scheduler, Speed};
+use crate::{AgentID, Command, Event, Incident, Scheduler, Speed};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use derivative::Derivative;
-use geom::{Duration, DurationHistogram};
+use geom::Duration;
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, TurnID, TurnPriority,
-    TurnType,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, Phase, SignalMode,
+    Traversable, Turn, TurnID, TurnPriority, TurnType,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
+// Pedestrians judging gaps in traffic want this much time on top of how long crossing takes. This
+// is the start-up and end clearance time from the Highway Capacity Manual.
+const PED_START_UP_TIME: Duration = Duration::const_seconds(2.0);
+// Pedestrians judging gaps in traffic give up waiting for one after this long. Then vehicles have
+// to yield to them, so a steady stream of traffic can't keep them waiting forever.
+const MAX_WAIT_FOR_GAP: Duration = Duration::const_seconds(30.0);
+// How often actuated traffic signals check their detectors
+const ACTUATED_SIGNAL_TICK: Duration = Duration::const_seconds(1.0);
 
-#[derive(Serialize, Deserialize, PartialEq)]
+#[derive(Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq)]
 pub struct IntersectionSimState {
     state: BTreeMap<IntersectionID, State>,
     use_freeform_policy_everywhere: bool,
     force_queue_entry: bool,
+    crosswalk_rules: CrosswalkRules,
+    events: Vec<Event>,
+    // Every incident scheduled so far, and which are blocking things right now
+    incidents: Vec<Incident>,
+    active_incidents: BTreeSet<usize>,
+    // Overrides the built-in policies. Not part of savestates; callers have to assign these again
+    // after loading.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    policies: BTreeMap<IntersectionID, Box<dyn IntersectionPolicy>>,
+    // Where policies have been assigned. This is saved, so a savestate loaded without its policies
+    // fails loudly instead of quietly switching back to the built-in ones.
+    custom_policies: BTreeSet<IntersectionID>,
 }
 
-#[derive(Serialize, Deserialize, Derivative)]
-#[derivative(PartialEq)]
+// Decides who gets to start turns through one intersection. The built-in policies handle stop
+// signs and traffic signals; anything else can be assigned per intersection with
+// Sim::set_intersection_policy.
+pub trait IntersectionPolicy {
+    // Can this agent start their turn right now? Only called when no accepted turn conflicts with
+    // this one, so policies can't let conflicting turns happen at once. If the answer is Wait, the
+    // agent won't ask again until wakeup_priority lets them.
+    fn maybe_start_turn(
+        &mut self,
+        req: &TurnRequest,
+        speed: Speed,
+        ctx: &IntersectionContext,
+    ) -> PolicyDecision;
+
+    // After a turn finishes or space frees up, waiting agents with Protected priority retry first,
+    // then Yield. Banned agents don't retry.
+    fn wakeup_priority(&self, _req: &TurnRequest, _ctx: &IntersectionContext) -> TurnPriority {
+        TurnPriority::Protected
+    }
+
+    // Called when an accepted turn finishes.
+    fn turn_finished(&mut self, _req: &TurnRequest, _ctx: &IntersectionContext) {}
+}
+
+pub enum PolicyDecision {
+    Go,
+    Wait,
+    // Don't go yet, but ask again at this time.
+    RetryAt(Duration),
+}
+
+// What a policy can see about an intersection
+pub struct IntersectionContext<'a> {
+    pub id: IntersectionID,
+    pub now: Duration,
+    pub map: &'a Map,
+    // Turns that have started but not finished yet
+    pub accepted: &'a BTreeSet<TurnRequest>,
+    // When each agent asking to start a turn first asked
+    pub waiting: &'a BTreeMap<TurnRequest, Duration>,
+}
+
+// How pedestrians and vehicles share crosswalks at stop signs and uncontrolled intersections
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CrosswalkRules {
+    // The stop sign alone decides, so pedestrians and vehicles take turns like any other
+    // conflicting movements.
+    StopSignDecides,
+    // Like marked crosswalks: vehicles yield to anybody waiting to cross in front of them. When
+    // lots of people are crossing, vehicles back up.
+    PedsHaveRightOfWay,
+    // Like unmarked or mid-block crossings: vehicles have the right of way. Pedestrians only start
+    // crossing once nobody's waiting to drive through the crosswalk and the last vehicle went
+    // through long enough ago to cross in the gap. If there's no gap for too long, they go anyway.
+    PedsJudgeGaps,
+    // Decided separately for each crosswalk from OSM crossing tags: PedsHaveRightOfWay for marked
+    // crosswalks and PedsJudgeGaps for unmarked ones. StopSignDecides if OSM doesn't say.
+    FromOsmCrossings,
+}
+
+impl CrosswalkRules {
+    fn for_crosswalk(self, crosswalk: &Turn, map: &Map) -> CrosswalkRules {
+        if self != CrosswalkRules::FromOsmCrossings {
+            return self;
+        }
+        // Only crosswalks over one road have any OSM data.
+        let r = map.get_parent(crosswalk.id.src);
+        if r.id != map.get_parent(crosswalk.id.dst).id {
+            return CrosswalkRules::StopSignDecides;
+        }
+        match r.is_crosswalk_marked(crosswalk.id.parent) {
+            Some(true) => CrosswalkRules::PedsHaveRightOfWay,
+            Some(false) => CrosswalkRules::PedsJudgeGaps,
+            None => CrosswalkRules::StopSignDecides,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct ActuatedSignalState {
+    phase: usize,
+    phase_started: Duration,
+    last_demand: Duration,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
 struct State {
     id: IntersectionID,
-    accepted: BTreeSet<Request>,
+    accepted: BTreeSet<TurnRequest>,
     // Track when a request is first made.
     #[serde(
         serialize_with = "serialize_btreemap",
         deserialize_with = "deserialize_btreemap"
     )]
-    waiting: BTreeMap<Request, Duration>,
-    // TODO Can we move this to analytics?
-    #[derivative(PartialEq = "ignore")]
-    #[serde(skip_serializing, skip_deserializing)]
-    delays: DurationHistogram,
+    waiting: BTreeMap<TurnRequest, Duration>,
+    // Only for actuated traffic signals. Created the first time the signal updates.
+    actuated: Option<ActuatedSignalState>,
+    // Only for CrosswalkRules::PedsJudgeGaps and FromOsmCrossings. When a vehicle last started
+    // each turn.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicle_turns_started: BTreeMap<TurnID, Duration>,
+    // Pedestrians who started crossing at a traffic signal while their crosswalk was protected.
+    // Anybody else was yielding, so they can't be stranded when the walk signal ends.
+    started_on_walk: BTreeSet<TurnRequest>,
 }
 
 impl IntersectionSimState {
@@ -43,11 +157,18 @@ impl IntersectionSimState {
         scheduler: &mut Scheduler,
         use_freeform_policy_everywhere: bool,
         disable_block_the_box: bool,
+        crosswalk_rules: CrosswalkRules,
     ) -> IntersectionSimState {
         let mut sim = IntersectionSimState {
             state: BTreeMap::new(),
             use_freeform_policy_everywhere,
             force_queue_entry: disable_block_the_box,
+            crosswalk_rules,
+            events: Vec::new(),
+            incidents: Vec::new(),
+            active_incidents: BTreeSet::new(),
+            policies: BTreeMap::new(),
+            custom_policies: BTreeSet::new(),
         };
         for i in map.all_intersections() {
             sim.state.insert(
@@ -56,11 +177,13 @@ impl IntersectionSimState {
                     id: i.id,
                     accepted: BTreeSet::new(),
                     waiting: BTreeMap::new(),
-                    delays: DurationHistogram::new(),
+                    actuated: None,
+                    vehicle_turns_started: BTreeMap::new(),
+                    started_on_walk: BTreeSet::new(),
                 },
             );
             if i.is_traffic_signal() && !use_freeform_policy_everywhere {
-                sim.update_intersection(Duration::ZERO, i.id, map, scheduler);
+                sim.update_intersection(Duration::ZERO, i.id, map, scheduler, &BTreeSet::new());
             }
         }
         sim
@@ -82,7 +205,23 @@ impl IntersectionSimState {
         map: &Map,
     ) {
         let state = self.state.get_mut(&turn.parent).unwrap();
-        assert!(state.accepted.remove(&Request { agent, turn }));
+        let req = TurnRequest { agent, turn };
+        assert!(state.accepted.remove(&req));
+        let started_on_walk = state.started_on_walk.remove(&req);
+        if let Some(policy) = self.policies.get_mut(&turn.parent) {
+            policy.turn_finished(&req, &state.context(now, map));
+        } else if let (AgentID::Pedestrian(ped), Some(signal)) =
+            (agent, map.maybe_get_traffic_signal(turn.parent))
+        {
+            // Did the walk signal end while they were still crossing?
+            if started_on_walk {
+                let (_, phase, _) = state.signal_phase(signal, now);
+                if phase.get_priority_of_turn(turn, signal) != TurnPriority::Protected {
+                    self.events
+                        .push(Event::PedStrandedInCrosswalk(ped, turn.parent));
+                }
+            }
+        }
         if map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
@@ -91,7 +230,7 @@ impl IntersectionSimState {
     // For deleting cars
     pub fn cancel_request(&mut self, agent: AgentID, turn: TurnID) {
         let state = self.state.get_mut(&turn.parent).unwrap();
-        state.waiting.remove(&Request { agent, turn });
+        state.waiting.remove(&TurnRequest { agent, turn });
     }
 
     pub fn space_freed(
@@ -114,7 +253,7 @@ impl IntersectionSimState {
         /*if i == IntersectionID(64) {
             println!("at {}: wakeup_waiting -----------------", now);
         }*/
-        let mut all: Vec<(Request, Duration)> = self.state[&i]
+        let mut all: Vec<(TurnRequest, Duration)> = self.state[&i]
             .waiting
             .iter()
             .map(|(r, t)| (r.clone(), *t))
@@ -128,12 +267,25 @@ impl IntersectionSimState {
         let mut protected = Vec::new();
         let mut yielding = Vec::new();
 
-        if self.use_freeform_policy_everywhere {
+        if let Some(policy) = self.policies.get(&i) {
+            let ctx = self.state[&i].context(now, map);
+            for (req, _) in all {
+                match policy.wakeup_priority(&req, &ctx) {
+                    TurnPriority::Protected => {
+                        protected.push(req);
+                    }
+                    TurnPriority::Yield => {
+                        yielding.push(req);
+                    }
+                    TurnPriority::Banned => {}
+                }
+            }
+        } else if self.use_freeform_policy_everywhere {
             for (req, _) in all {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let (_, phase, _) = signal.current_phase_and_remaining_time(now);
+            let (_, phase, _) = self.state[&i].signal_phase(signal, now);
             for (req, _) in all {
                 match phase.get_priority_of_turn(req.turn, signal) {
                     TurnPriority::Protected => {
@@ -175,21 +327,46 @@ impl IntersectionSimState {
         }
     }
 
-    // This is only triggered for traffic signals.
+    // This is only triggered for traffic signals. detected is the set of incoming lanes with a
+    // vehicle over the detector, and only matters for actuated signals.
     pub fn update_intersection(
-        &self,
+        &mut self,
         now: Duration,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
+        detected: &BTreeSet<LaneID>,
     ) {
+        let signal = map.get_traffic_signal(id);
+        if let SignalMode::Actuated { gap, .. } = signal.mode {
+            if self
+                .state
+                .get_mut(&id)
+                .unwrap()
+                .update_actuated_signal(signal, gap, now, detected)
+            {
+                self.wakeup_waiting(now, id, scheduler, map);
+            }
+            scheduler.push(now + ACTUATED_SIGNAL_TICK, Command::UpdateIntersection(id));
+            return;
+        }
+
         self.wakeup_waiting(now, id, scheduler, map);
-        let (_, _, remaining) = map
-            .get_traffic_signal(id)
-            .current_phase_and_remaining_time(now);
+        let (_, _, remaining) = signal.current_phase_and_remaining_time(now);
         scheduler.push(now + remaining, Command::UpdateIntersection(id));
     }
 
+    // Like ControlTrafficSignal::current_phase_and_remaining_time, but also handles actuated
+    // signals. For those, the remaining time is until the phase maxes out; it could end sooner.
+    pub fn current_phase_and_remaining_time<'a>(
+        &self,
+        signal: &'a ControlTrafficSignal,
+        now: Duration,
+    ) -> (usize, &'a Phase, Duration) {
+        let (idx, phase, remaining) = self.state[&signal.id].signal_phase(signal, now);
+        (idx, phase, remaining.unwrap_or(Duration::ZERO))
+    }
+
     // For cars: The head car calls this when they're at the end of the lane WaitingToAdvance. If
     // this returns true, then the head car MUST actually start this turn.
     // For peds: Likewise -- only called when the ped is at the start of the turn. They must
@@ -208,16 +385,42 @@ impl IntersectionSimState {
         maybe_car_and_target_queue: Option<(&mut Queue, &Car)>,
     ) -> bool {
         //let debug = turn.parent == IntersectionID(64);
-        let req = Request { agent, turn };
+        if self.custom_policies.contains(&turn.parent) && !self.policies.contains_key(&turn.parent)
+        {
+            panic!(
+                "{} had a custom IntersectionPolicy, but it isn't part of the savestate. Call \
+                 Sim::set_intersection_policy again after loading.",
+                turn.parent
+            );
+        }
+        let req = TurnRequest { agent, turn };
+        let blocked = self.is_blocked(Traversable::Turn(turn));
         let state = self.state.get_mut(&turn.parent).unwrap();
         state.waiting.entry(req.clone()).or_insert(now);
 
-        let allowed = if self.use_freeform_policy_everywhere {
+        // Clearing the incident wakes everybody up again.
+        let allowed = if blocked {
+            false
+        } else if let Some(policy) = self.policies.get_mut(&turn.parent) {
+            if state.any_accepted_conflict_with(turn, map) {
+                false
+            } else {
+                match policy.maybe_start_turn(&req, speed, &state.context(now, map)) {
+                    PolicyDecision::Go => true,
+                    PolicyDecision::Wait => false,
+                    PolicyDecision::RetryAt(time) => {
+                        scheduler.push(time, Command::update_agent(agent));
+                        false
+                    }
+                }
+            }
+        } else if self.use_freeform_policy_everywhere {
             state.freeform_policy(&req, map)
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(state.id) {
             state.traffic_signal_policy(signal, &req, speed, now, map)
         } else if let Some(ref sign) = map.maybe_get_stop_sign(state.id) {
-            state.stop_sign_policy(sign, &req, now, map, scheduler)
+            state.crosswalk_policy(self.crosswalk_rules, &req, speed, now, map, scheduler)
+                && state.stop_sign_policy(sign, &req, now, map, scheduler)
         } else {
             unreachable!()
         };
@@ -239,7 +442,32 @@ impl IntersectionSimState {
         }
 
         assert!(!state.any_accepted_conflict_with(turn, map));
-        state.delays.add(now - state.waiting.remove(&req).unwrap());
+        let delay = now - state.waiting.remove(&req).unwrap();
+        self.events
+            .push(Event::IntersectionDelayMeasured(turn.parent, delay));
+        if let AgentID::Pedestrian(_) = agent {
+            if let Some(signal) = map.maybe_get_traffic_signal(turn.parent) {
+                if map.get_t(turn).turn_type == TurnType::Crosswalk {
+                    self.events
+                        .push(Event::PedSignalDelayMeasured(turn.parent, delay));
+                    let (_, phase, _) = state.signal_phase(signal, now);
+                    if !self.use_freeform_policy_everywhere
+                        && !self.policies.contains_key(&turn.parent)
+                        && phase.get_priority_of_turn(turn, signal) == TurnPriority::Protected
+                    {
+                        state.started_on_walk.insert(req.clone());
+                    }
+                }
+            }
+        }
+        if let AgentID::Car(_) = agent {
+            if (self.crosswalk_rules == CrosswalkRules::PedsJudgeGaps
+                || self.crosswalk_rules == CrosswalkRules::FromOsmCrossings)
+                && map.maybe_get_stop_sign(turn.parent).is_some()
+            {
+                state.vehicle_turns_started.insert(turn, now);
+            }
+        }
         state.accepted.insert(req);
         /*if debug {
             println!("{}: {} going!", now, agent)
@@ -256,7 +484,6 @@ impl IntersectionSimState {
         } else {
             println!("Border");
         }
-        println!("Delays: {}", self.state[&id].delays.describe());
     }
 
     pub fn get_accepted_agents(&self, id: IntersectionID) -> HashSet<AgentID> {
@@ -267,12 +494,198 @@ impl IntersectionSimState {
             .collect()
     }
 
-    pub fn get_intersection_delays(&self, id: IntersectionID) -> &DurationHistogram {
-        &self.state[&id].delays
+    pub fn get_waiting_requests(&self, id: IntersectionID) -> Vec<TurnRequest> {
+        self.state[&id].waiting.keys().cloned().collect()
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::replace(&mut self.events, Vec::new())
+    }
+
+    pub fn set_policy(&mut self, i: IntersectionID, policy: Box<dyn IntersectionPolicy>) {
+        self.policies.insert(i, policy);
+        self.custom_policies.insert(i);
+    }
+
+    // Returns the index to use for StartIncident and ClearIncident.
+    pub fn add_incident(&mut self, incident: Incident) -> usize {
+        self.incidents.push(incident);
+        self.incidents.len() - 1
+    }
+
+    pub fn start_incident(&mut self, idx: usize) {
+        self.active_incidents.insert(idx);
+        self.events
+            .push(Event::IncidentStarted(self.incidents[idx].clone()));
+    }
+
+    pub fn clear_incident(
+        &mut self,
+        now: Duration,
+        idx: usize,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        self.active_incidents.remove(&idx);
+        let incident = &self.incidents[idx];
+        self.events.push(Event::IncidentCleared(incident.clone()));
+        for i in incident.affected_intersections(map) {
+            self.wakeup_waiting(now, i, scheduler, map);
+        }
+    }
+
+    pub fn is_blocked(&self, on: Traversable) -> bool {
+        self.active_incidents
+            .iter()
+            .any(|idx| self.incidents[*idx].blocks(on))
+    }
+
+    pub fn any_active_incidents(&self) -> bool {
+        !self.active_incidents.is_empty()
+    }
+
+    pub fn get_incident(&self, idx: usize) -> &Incident {
+        &self.incidents[idx]
+    }
+
+    pub fn get_active_incidents(&self) -> Vec<&Incident> {
+        self.active_incidents
+            .iter()
+            .map(|idx| &self.incidents[*idx])
+            .collect()
     }
 }
 
 impl State {
+    fn context<'a>(&'a self, now: Duration, map: &'a Map) -> IntersectionContext<'a> {
+        IntersectionContext {
+            id: self.id,
+            now,
+            map,
+            accepted: &self.accepted,
+            waiting: &self.waiting,
+        }
+    }
+
+    // The remaining time is None when an actuated signal is resting in a phase, because nobody
+    // else wants to go.
+    fn signal_phase<'a>(
+        &self,
+        signal: &'a ControlTrafficSignal,
+        now: Duration,
+    ) -> (usize, &'a Phase, Option<Duration>) {
+        if let Some(ref actuated) = self.actuated {
+            // The signal might've been edited to have fewer phases.
+            if let (SignalMode::Actuated { .. }, Some(phase)) =
+                (&signal.mode, signal.phases.get(actuated.phase))
+            {
+                let elapsed = now - actuated.phase_started;
+                let remaining = if elapsed < phase.max_green {
+                    Some(phase.max_green - elapsed)
+                } else {
+                    None
+                };
+                return (actuated.phase, phase, remaining);
+            }
+        }
+        let (idx, phase, remaining) = signal.current_phase_and_remaining_time(now);
+        (idx, phase, Some(remaining))
+    }
+
+    // How much longer the current phase is sure to last. Actuated signals could change as soon as
+    // min green is up.
+    fn guaranteed_phase_time(&self, signal: &ControlTrafficSignal, now: Duration) -> Duration {
+        let (_, phase, remaining) = self.signal_phase(signal, now);
+        match (&signal.mode, &self.actuated) {
+            (SignalMode::Actuated { .. }, Some(actuated)) => {
+                let elapsed = now - actuated.phase_started;
+                if elapsed < phase.min_green {
+                    phase.min_green - elapsed
+                } else {
+                    Duration::ZERO
+                }
+            }
+            _ => remaining.unwrap_or(Duration::ZERO),
+        }
+    }
+
+    // Returns true if the phase changed.
+    fn update_actuated_signal(
+        &mut self,
+        signal: &ControlTrafficSignal,
+        gap: Duration,
+        now: Duration,
+        detected: &BTreeSet<LaneID>,
+    ) -> bool {
+        let waiting_turns: BTreeSet<TurnID> = self.waiting.keys().map(|req| req.turn).collect();
+        let has_demand = |idx: usize| {
+            let phase = &signal.phases[idx];
+            phase
+                .protected_groups
+                .iter()
+                .chain(phase.yield_groups.iter())
+                .flat_map(|g| signal.turn_groups[g].members.iter())
+                .any(|t| detected.contains(&t.src) || waiting_turns.contains(t))
+        };
+
+        let current = match self.actuated {
+            Some(ref mut actuated) if actuated.phase < signal.phases.len() => actuated,
+            _ => {
+                self.actuated = Some(ActuatedSignalState {
+                    phase: 0,
+                    phase_started: now,
+                    last_demand: now,
+                });
+                return true;
+            }
+        };
+        if has_demand(current.phase) {
+            current.last_demand = now;
+        }
+
+        let phase = &signal.phases[current.phase];
+        let elapsed = now - current.phase_started;
+        if elapsed < phase.min_green {
+            return false;
+        }
+        let num_phases = signal.phases.len();
+
+        // Pedestrians who showed up after the don't walk signal started flashing might still be
+        // waiting. If nobody else wants a turn, serve this phase again so they can cross.
+        let ped_waiting = phase
+            .protected_groups
+            .iter()
+            .map(|g| &signal.turn_groups[g])
+            .filter(|g| g.turn_type == TurnType::Crosswalk)
+            .flat_map(|g| g.members.iter())
+            .any(|t| waiting_turns.contains(t));
+        if ped_waiting
+            && !(1..num_phases).any(|offset| has_demand((current.phase + offset) % num_phases))
+        {
+            current.phase_started = now;
+            current.last_demand = now;
+            return true;
+        }
+
+        let max_out = elapsed >= phase.max_green;
+        let gap_out = now - current.last_demand >= gap;
+        if !max_out && !gap_out {
+            return false;
+        }
+
+        // Skip phases that nobody wants.
+        for offset in 1..num_phases {
+            let idx = (current.phase + offset) % num_phases;
+            if has_demand(idx) {
+                current.phase = idx;
+                current.phase_started = now;
+                current.last_demand = now;
+                return true;
+            }
+        }
+        false
+    }
+
     fn any_accepted_conflict_with(&self, t: TurnID, map: &Map) -> bool {
         let turn = map.get_t(t);
         self.accepted
@@ -280,7 +693,7 @@ impl State {
             .any(|req| map.get_t(req.turn).conflicts_with(turn))
     }
 
-    fn freeform_policy(&self, req: &Request, map: &Map) -> bool {
+    fn freeform_policy(&self, req: &TurnRequest, map: &Map) -> bool {
         // Allow concurrent turns that don't conflict
         if self.any_accepted_conflict_with(req.turn, map) {
             return false;
@@ -289,10 +702,80 @@ impl State {
         true
     }
 
+    // Who goes first when a vehicle's turn crosses a crosswalk at a stop sign
+    fn crosswalk_policy(
+        &self,
+        rules: CrosswalkRules,
+        req: &TurnRequest,
+        speed: Speed,
+        now: Duration,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let turn = map.get_t(req.turn);
+        let conflicting_requests = |want_peds: bool| {
+            self.waiting.iter().filter(move |(r, _)| {
+                let is_ped = match r.agent {
+                    AgentID::Pedestrian(_) => true,
+                    AgentID::Car(_) => false,
+                };
+                is_ped == want_peds && map.get_t(r.turn).conflicts_with(turn)
+            })
+        };
+
+        match req.agent {
+            // Yield to anybody with the right of way, or who's given up waiting for a gap.
+            AgentID::Car(_) => !conflicting_requests(true).any(|(r, since)| {
+                match rules.for_crosswalk(map.get_t(r.turn), map) {
+                    CrosswalkRules::PedsHaveRightOfWay => true,
+                    CrosswalkRules::PedsJudgeGaps => now >= *since + MAX_WAIT_FOR_GAP,
+                    _ => false,
+                }
+            }),
+            AgentID::Pedestrian(_)
+                if turn.turn_type == TurnType::Crosswalk
+                    && rules.for_crosswalk(turn, map) == CrosswalkRules::PedsJudgeGaps =>
+            {
+                let give_up = self.waiting[req] + MAX_WAIT_FOR_GAP;
+                if now >= give_up {
+                    return true;
+                }
+                if conflicting_requests(false).next().is_some() {
+                    // Since we have "ownership" of scheduling for req.agent, don't need to use
+                    // scheduler.update.
+                    scheduler.push(give_up, Command::update_agent(req.agent));
+                    return false;
+                }
+                let mut last_vehicle: Option<Duration> = None;
+                for (t, started) in &self.vehicle_turns_started {
+                    if map.get_t(*t).conflicts_with(turn)
+                        && last_vehicle.map(|last| *started > last).unwrap_or(true)
+                    {
+                        last_vehicle = Some(*started);
+                    }
+                }
+                let gap = turn.geom.length() / speed + PED_START_UP_TIME;
+                match last_vehicle {
+                    Some(last) if now < last + gap => {
+                        let retry = if last + gap < give_up {
+                            last + gap
+                        } else {
+                            give_up
+                        };
+                        scheduler.push(retry, Command::update_agent(req.agent));
+                        false
+                    }
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+
     fn stop_sign_policy(
         &self,
         sign: &ControlStopSign,
-        req: &Request,
+        req: &TurnRequest,
         now: Duration,
         map: &Map,
         scheduler: &mut Scheduler,
@@ -337,7 +820,7 @@ impl State {
     fn traffic_signal_policy(
         &self,
         signal: &ControlTrafficSignal,
-        new_req: &Request,
+        new_req: &TurnRequest,
         speed: Speed,
         now: Duration,
         map: &Map,
@@ -349,7 +832,7 @@ impl State {
             return true;
         }
 
-        let (_, phase, remaining_phase_time) = signal.current_phase_and_remaining_time(now);
+        let (phase_idx, phase, remaining_phase_time) = self.signal_phase(signal, now);
 
         // Can't go at all this phase.
         if phase.get_priority_of_turn(new_req.turn, signal) == TurnPriority::Banned {
@@ -361,6 +844,14 @@ impl State {
             return false;
         }
 
+        // Once the don't walk signal starts flashing, nobody new starts crossing, even if they're
+        // fast enough to make it.
+        if turn.turn_type == TurnType::Crosswalk
+            && self.guaranteed_phase_time(signal, now) < signal.crosswalk_clearance(turn)
+        {
+            return false;
+        }
+
         // Previously: A yield loses to a conflicting Priority turn.
         // But similar to the description in stop_sign_policy, this caused unnecessary gridlock.
         // Priority vehicles getting scheduled first just requires a little tweak in
@@ -372,13 +863,18 @@ impl State {
         // Optimistically if nobody else is in the way, this is how long it'll take to finish the
         // turn. Don't start the turn if we won't finish by the time the light changes. If we get
         // it wrong, that's fine -- block the box a bit.
+        // An actuated signal resting in this phase will only change once somebody else shows up,
+        // so go ahead.
         let time_to_cross = turn.geom.length() / speed;
-        if time_to_cross > remaining_phase_time {
-            // Actually, we might have bigger problems...
-            if time_to_cross > phase.duration {
-                println!("OYYY! {:?} is impossible to fit into phase duration of {}. Allowing, but fix the policy!", new_req, phase.duration);
-            } else {
-                return false;
+        if let Some(remaining_phase_time) = remaining_phase_time {
+            if time_to_cross > remaining_phase_time {
+                // Actually, we might have bigger problems...
+                let max_duration = signal.max_phase_duration(phase_idx);
+                if time_to_cross > max_duration {
+                    println!("OYYY! {:?} is impossible to fit into phase duration of {}. Allowing, but fix the policy!", new_req, max_duration);
+                } else {
+                    return false;
+                }
             }
         }
 
@@ -387,7 +883,49 @@ impl State {
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
-struct Request {
-    agent: AgentID,
-    turn: TurnID,
+pub struct TurnRequest {
+    pub agent: AgentID,
+    pub turn: TurnID,
+}
+
+// An all-way stop where vehicles go one at a time, strictly in the order they arrived, no matter
+// which turns they're doing. Pedestrians cross whenever nothing conflicts.
+pub struct StrictFIFOStopSign;
+
+impl IntersectionPolicy for StrictFIFOStopSign {
+    fn maybe_start_turn(
+        &mut self,
+        req: &TurnRequest,
+        _: Speed,
+        ctx: &IntersectionContext,
+    ) -> PolicyDecision {
+        if let AgentID::Pedestrian(_) = req.agent {
+            return PolicyDecision::Go;
+        }
+        let arrived = ctx.waiting[req];
+        if ctx.now < arrived + WAIT_AT_STOP_SIGN {
+            return PolicyDecision::RetryAt(arrived + WAIT_AT_STOP_SIGN);
+        }
+        if ctx.accepted.iter().any(|r| is_vehicle(r.agent)) {
+            return PolicyDecision::Wait;
+        }
+        let first = ctx
+            .waiting
+            .iter()
+            .filter(|(r, _)| is_vehicle(r.agent))
+            .min_by(|(r1, t1), (r2, t2)| t1.partial_cmp(t2).unwrap().then_with(|| r1.cmp(r2)))
+            .map(|(r, _)| r);
+        if first == Some(req) {
+            PolicyDecision::Go
+        } else {
+            PolicyDecision::Wait
+        }
+    }
+}
+
+fn is_vehicle(agent: AgentID) -> bool {
+    match agent {
+        AgentID::Car(_) => true,
+        AgentID::Pedestrian(_) => false,
+    }
 }
@@ -0,0 +1,281 @@
+use geom::{Distance, Duration, Speed};
+use serde_derive::{Deserialize, Serialize};
+
+// How a vehicle's speed changes while it crosses some distance. This is a trapezoid: accelerate
+// from the starting speed up to a peak, cruise at that peak, then brake to the end speed. Short
+// distances might not have any cruising, or might not even be long enough to reach the desired
+// end speed.
+//
+// Everything here is relative to the start of the crossing; distances are how far we've moved
+// along the DistanceInterval, not the absolute distance along the Traversable.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SpeedProfile {
+    // All in meters and seconds
+    start_speed: f64,
+    peak_speed: f64,
+    end_speed: f64,
+    accel: f64,
+    decel: f64,
+
+    accel_time: f64,
+    cruise_time: f64,
+    decel_time: f64,
+    total_dist: f64,
+}
+
+impl SpeedProfile {
+    // accel and decel are in m/s^2 and must be positive.
+    pub fn new(
+        dist: Distance,
+        start_speed: Speed,
+        max_speed: Speed,
+        end_speed: Speed,
+        accel: f64,
+        decel: f64,
+    ) -> SpeedProfile {
+        assert!(accel > 0.0 && decel > 0.0);
+        let d = dist.inner_meters();
+        let vm = max_speed.inner_meters_per_second();
+        assert!(vm > 0.0);
+        let v0 = start_speed.inner_meters_per_second().max(0.0).min(vm);
+        let ve = end_speed.inner_meters_per_second().max(0.0).min(vm);
+
+        let mut profile = SpeedProfile {
+            start_speed: v0,
+            peak_speed: v0,
+            end_speed: v0,
+            accel,
+            decel,
+            accel_time: 0.0,
+            cruise_time: 0.0,
+            decel_time: 0.0,
+            total_dist: d.max(0.0),
+        };
+        if d <= 0.0 {
+            return profile;
+        }
+
+        let dist_to_accel = (vm * vm - v0 * v0) / (2.0 * accel);
+        let dist_to_brake = (vm * vm - ve * ve) / (2.0 * decel);
+        if dist_to_accel + dist_to_brake <= d {
+            profile.peak_speed = vm;
+            profile.end_speed = ve;
+            profile.accel_time = (vm - v0) / accel;
+            profile.cruise_time = (d - dist_to_accel - dist_to_brake) / vm;
+            profile.decel_time = (vm - ve) / decel;
+            return profile;
+        }
+
+        // No room to cruise. Where do the acceleration and braking curves meet?
+        let peak = ((2.0 * d * accel * decel + v0 * v0 * decel + ve * ve * accel)
+            / (accel + decel))
+            .sqrt();
+        if peak < v0 {
+            // Can't brake hard enough to reach the end speed, so just brake the whole way.
+            let exit = (v0 * v0 - 2.0 * decel * d).max(0.0).sqrt();
+            profile.end_speed = exit;
+            profile.decel_time = (v0 - exit) / decel;
+        } else if peak < ve {
+            // Can't speed up enough to reach the end speed, so accelerate the whole way.
+            let exit = (v0 * v0 + 2.0 * accel * d).sqrt();
+            profile.peak_speed = exit;
+            profile.end_speed = exit;
+            profile.accel_time = (exit - v0) / accel;
+        } else {
+            profile.peak_speed = peak;
+            profile.end_speed = ve;
+            profile.accel_time = (peak - v0) / accel;
+            profile.decel_time = (peak - ve) / decel;
+        }
+        profile
+    }
+
+    pub fn total_time(&self) -> Duration {
+        Duration::seconds(self.accel_time + self.cruise_time + self.decel_time)
+    }
+
+    pub fn end_speed(&self) -> Speed {
+        Speed::meters_per_second(self.end_speed)
+    }
+
+    fn accel_dist(&self) -> f64 {
+        self.start_speed * self.accel_time + 0.5 * self.accel * self.accel_time * self.accel_time
+    }
+
+    fn cruise_dist(&self) -> f64 {
+        self.peak_speed * self.cruise_time
+    }
+
+    // How far along are we after this much time?
+    pub fn dist_after(&self, dt: Duration) -> Distance {
+        let t = dt.inner_seconds().max(0.0);
+        let dist = if t <= self.accel_time {
+            self.start_speed * t + 0.5 * self.accel * t * t
+        } else if t <= self.accel_time + self.cruise_time {
+            self.accel_dist() + self.peak_speed * (t - self.accel_time)
+        } else if t <= self.accel_time + self.cruise_time + self.decel_time {
+            let braking = t - self.accel_time - self.cruise_time;
+            self.accel_dist() + self.cruise_dist() + self.peak_speed * braking
+                - 0.5 * self.decel * braking * braking
+        } else {
+            self.total_dist
+        };
+        Distance::meters(dist.max(0.0).min(self.total_dist))
+    }
+
+    pub fn speed_after(&self, dt: Duration) -> Speed {
+        let t = dt.inner_seconds().max(0.0);
+        let speed = if t <= self.accel_time {
+            self.start_speed + self.accel * t
+        } else if t <= self.accel_time + self.cruise_time {
+            self.peak_speed
+        } else if t <= self.accel_time + self.cruise_time + self.decel_time {
+            self.peak_speed - self.decel * (t - self.accel_time - self.cruise_time)
+        } else {
+            self.end_speed
+        };
+        Speed::meters_per_second(speed.max(0.0))
+    }
+
+    // The inverse of dist_after.
+    pub fn time_to_reach(&self, dist: Distance) -> Duration {
+        let x = dist.inner_meters();
+        if x >= self.total_dist {
+            return self.total_time();
+        }
+        if x <= 0.0 {
+            return Duration::ZERO;
+        }
+        let accel_dist = self.accel_dist();
+        let cruise_dist = self.cruise_dist();
+        let t = if x <= accel_dist {
+            (-self.start_speed
+                + (self.start_speed * self.start_speed + 2.0 * self.accel * x).sqrt())
+                / self.accel
+        } else if x <= accel_dist + cruise_dist {
+            self.accel_time + (x - accel_dist) / self.peak_speed
+        } else {
+            let y = x - accel_dist - cruise_dist;
+            let braking = (self.peak_speed
+                - (self.peak_speed * self.peak_speed - 2.0 * self.decel * y)
+                    .max(0.0)
+                    .sqrt())
+                / self.decel;
+            self.accel_time + self.cruise_time + braking
+        };
+        Duration::seconds(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpeedProfile;
+    use geom::{Distance, Duration, Speed};
+
+    fn approx(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn accelerate_cruise_brake() {
+        // 2 m/s^2 up to 10 m/s takes 5s and 25m. Braking at 5 m/s^2 back to 0 takes 2s and 10m.
+        let p = SpeedProfile::new(
+            Distance::meters(100.0),
+            Speed::ZERO,
+            Speed::meters_per_second(10.0),
+            Speed::ZERO,
+            2.0,
+            5.0,
+        );
+        approx(p.total_time().inner_seconds(), 5.0 + 6.5 + 2.0);
+        approx(p.dist_after(Duration::seconds(5.0)).inner_meters(), 25.0);
+        approx(p.dist_after(Duration::seconds(11.5)).inner_meters(), 90.0);
+        approx(p.dist_after(p.total_time()).inner_meters(), 100.0);
+        approx(
+            p.speed_after(Duration::seconds(2.5))
+                .inner_meters_per_second(),
+            5.0,
+        );
+        approx(
+            p.speed_after(Duration::seconds(8.0))
+                .inner_meters_per_second(),
+            10.0,
+        );
+        approx(
+            p.speed_after(Duration::seconds(12.5))
+                .inner_meters_per_second(),
+            5.0,
+        );
+        approx(p.end_speed().inner_meters_per_second(), 0.0);
+    }
+
+    #[test]
+    fn time_to_reach_inverts_dist_after() {
+        let p = SpeedProfile::new(
+            Distance::meters(100.0),
+            Speed::meters_per_second(3.0),
+            Speed::meters_per_second(10.0),
+            Speed::meters_per_second(4.0),
+            2.0,
+            5.0,
+        );
+        for x in &[0.0, 5.0, 25.0, 50.0, 90.0, 99.0, 100.0] {
+            let t = p.time_to_reach(Distance::meters(*x));
+            approx(p.dist_after(t).inner_meters(), *x);
+        }
+    }
+
+    #[test]
+    fn too_short_to_cruise() {
+        // The acceleration and braking curves meet at sqrt(2 * 20 * 2 * 2 / 4) = sqrt(40) m/s.
+        let p = SpeedProfile::new(
+            Distance::meters(20.0),
+            Speed::ZERO,
+            Speed::meters_per_second(10.0),
+            Speed::ZERO,
+            2.0,
+            2.0,
+        );
+        let peak = 40.0_f64.sqrt();
+        approx(p.total_time().inner_seconds(), 2.0 * peak / 2.0);
+        approx(
+            p.speed_after(Duration::seconds(peak / 2.0))
+                .inner_meters_per_second(),
+            peak,
+        );
+        approx(
+            p.dist_after(Duration::seconds(peak / 2.0)).inner_meters(),
+            10.0,
+        );
+    }
+
+    #[test]
+    fn cant_brake_in_time() {
+        // From 10 m/s braking at 2 m/s^2, 16m only slows down to sqrt(100 - 64) = 6 m/s.
+        let p = SpeedProfile::new(
+            Distance::meters(16.0),
+            Speed::meters_per_second(10.0),
+            Speed::meters_per_second(10.0),
+            Speed::ZERO,
+            2.0,
+            2.0,
+        );
+        approx(p.end_speed().inner_meters_per_second(), 6.0);
+        approx(p.total_time().inner_seconds(), 2.0);
+    }
+
+    #[test]
+    fn cant_speed_up_in_time() {
+        // From a stop accelerating at 2 m/s^2, 4m only gets up to 4 m/s.
+        let p = SpeedProfile::new(
+            Distance::meters(4.0),
+            Speed::ZERO,
+            Speed::meters_per_second(10.0),
+            Speed::meters_per_second(10.0),
+            2.0,
+            2.0,
+        );
+        approx(p.end_speed().inner_meters_per_second(), 4.0);
+        approx(p.total_time().inner_seconds(), 2.0);
+    }
+}
@@ -1,12 +1,17 @@
 mod car;
 mod driving;
 mod intersection;
+mod kinematics;
 mod parking;
 mod queue;
+mod travel_times;
 mod walking;
 
 pub use self::driving::DrivingSimState;
-pub use self::intersection::IntersectionSimState;
+pub use self::intersection::{
+    CrosswalkRules, IntersectionContext, IntersectionPolicy, IntersectionSimState, PolicyDecision,
+    StrictFIFOStopSign, TurnRequest,
+};
 pub use self::parking::ParkingSimState;
 pub use self::queue::Queue;
-pub use self::walking::WalkingSimState;
+pub use self::walking::{walkable_area, WalkingSimState};
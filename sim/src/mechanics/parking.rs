@@ -145,6 +145,15 @@ impl ParkingSimState {
         None
     }
 
+    pub fn dynamically_reserve_car_by_id(&mut self, id: CarID) -> Option<ParkedCar> {
+        let p = self.parked_cars.get(&id)?;
+        if self.dynamically_reserved_cars.contains(&id) {
+            return None;
+        }
+        self.dynamically_reserved_cars.insert(id);
+        Some(p.clone())
+    }
+
     pub fn dynamically_return_car(&mut self, p: ParkedCar) {
         self.dynamically_reserved_cars.remove(&p.vehicle.id);
     }
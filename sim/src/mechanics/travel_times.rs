@@ -0,0 +1,48 @@
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::Duration;
+use map_model::{Map, Traversable};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// How much each new observation moves the average
+const SMOOTHING: f64 = 0.25;
+// Whatever caused an old observation might be over by now, so forget it.
+const STALE_AFTER: Duration = Duration::const_seconds(15.0 * 60.0);
+
+// Moving averages of how long vehicles recently took to cross lanes and turns. Anything nobody
+// has crossed lately is assumed to flow freely.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct LiveTravelTimes {
+    // The average, and when it was last updated
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    observed: BTreeMap<Traversable, (Duration, Duration)>,
+}
+
+impl LiveTravelTimes {
+    pub fn new() -> LiveTravelTimes {
+        LiveTravelTimes {
+            observed: BTreeMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, on: Traversable, dt: Duration, now: Duration) {
+        let avg = match self.observed.get(&on) {
+            Some((avg, updated)) if now - *updated < STALE_AFTER => {
+                *avg * (1.0 - SMOOTHING) + dt * SMOOTHING
+            }
+            _ => dt,
+        };
+        self.observed.insert(on, (avg, now));
+    }
+
+    pub fn get(&self, on: Traversable, now: Duration, map: &Map) -> Duration {
+        let free_flow = on.length(map) / on.speed_limit(map);
+        match self.observed.get(&on) {
+            Some((avg, updated)) if now - *updated < STALE_AFTER => (*avg).max(free_flow),
+            _ => free_flow,
+        }
+    }
+}
@@ -6,12 +6,19 @@ use crate::{
 };
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed};
-use map_model::{BuildingID, BusRouteID, Map, Path, PathStep, Traversable, LANE_THICKNESS};
+use map_model::{
+    BuildingID, BusRouteID, Map, Path, PathStep, Traversable, TurnType, LANE_THICKNESS,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
+// Marked crosswalks are usually about 10 feet wide.
+const CROSSWALK_WIDTH: Distance = Distance::const_meters(3.0);
+// Pedestrians per square meter where nobody can move, and how slow the crowd can get before then
+const JAM_DENSITY: f64 = 5.4;
+const MIN_CROWDED_SPEED_FRACTION: f64 = 0.1;
 
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct WalkingSimState {
@@ -74,15 +81,22 @@ impl WalkingSimState {
                 Line::new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
                 TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
             ),
-            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map),
+            _ => ped.crossing_state(
+                params.start.sidewalk_pos.dist_along(),
+                now,
+                map,
+                &self.peds_per_traversable,
+            ),
         };
 
         scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
         self.peds.insert(ped.id, ped);
-        self.peds_per_traversable.insert(
-            Traversable::Lane(params.start.sidewalk_pos.lane()),
-            params.id,
-        );
+        let on = Traversable::Lane(params.start.sidewalk_pos.lane());
+        self.peds_per_traversable.insert(on, params.id);
+        self.events.push(Event::PedsOnSidewalk(
+            start_lane,
+            self.peds_per_traversable.get(on).len(),
+        ));
     }
 
     pub fn get_draw_ped(
@@ -150,7 +164,7 @@ impl WalkingSimState {
                         SidewalkPOI::Border(i) => {
                             self.peds_per_traversable
                                 .remove(ped.path.current_step().as_traversable(), ped.id);
-                            trips.ped_reached_border(now, ped.id, i, map);
+                            trips.ped_reached_border(now, ped.id, i, map, scheduler);
                             self.peds.remove(&id);
                         }
                         SidewalkPOI::BikeRack(driving_pos) => {
@@ -208,14 +222,18 @@ impl WalkingSimState {
                 }
             }
             PedState::LeavingBuilding(b, _) => {
-                ped.state =
-                    ped.crossing_state(map.get_b(b).front_path.sidewalk.dist_along(), now, map);
+                ped.state = ped.crossing_state(
+                    map.get_b(b).front_path.sidewalk.dist_along(),
+                    now,
+                    map,
+                    &self.peds_per_traversable,
+                );
                 scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
             PedState::EnteringBuilding(bldg, _) => {
                 self.peds_per_traversable
                     .remove(ped.path.current_step().as_traversable(), ped.id);
-                trips.ped_reached_building(now, ped.id, bldg, map);
+                trips.ped_reached_building(now, ped.id, bldg, map, scheduler);
                 self.peds.remove(&id);
             }
             PedState::StartingToBike(ref spot, _, _) => {
@@ -225,7 +243,12 @@ impl WalkingSimState {
                 self.peds.remove(&id);
             }
             PedState::FinishingBiking(ref spot, _, _) => {
-                ped.state = ped.crossing_state(spot.sidewalk_pos.dist_along(), now, map);
+                ped.state = ped.crossing_state(
+                    spot.sidewalk_pos.dist_along(),
+                    now,
+                    map,
+                    &self.peds_per_traversable,
+                );
                 scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
             PedState::WaitingForBus(_) => unreachable!(),
@@ -421,6 +444,10 @@ impl WalkingSimState {
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
+
+    pub fn num_peds_on(&self, on: Traversable) -> usize {
+        self.peds_per_traversable.get(on).len()
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -438,7 +465,15 @@ struct Pedestrian {
 }
 
 impl Pedestrian {
-    fn crossing_state(&self, start_dist: Distance, start_time: Duration, map: &Map) -> PedState {
+    // Pedestrians pick their pace when they start along a sidewalk or crosswalk, based on how
+    // crowded it is right then.
+    fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Duration,
+        map: &Map,
+        peds_per_traversable: &MultiMap<Traversable, PedestrianID>,
+    ) -> PedState {
         let end_dist = if self.path.is_last_step() {
             self.goal.sidewalk_pos.dist_along()
         } else {
@@ -449,8 +484,16 @@ impl Pedestrian {
                 PathStep::Turn(t) => map.get_t(t).geom.length(),
             }
         };
+        let on = self.path.current_step().as_traversable();
+        let others = peds_per_traversable
+            .get(on)
+            .iter()
+            .filter(|id| **id != self.id)
+            .count();
+        let speed = crowded_speed(self.speed, others + 1, walkable_area(on, map));
+
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / self.speed);
+        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
         PedState::Crossing(dist_int, time_int)
     }
 
@@ -593,12 +636,16 @@ impl Pedestrian {
             PathStep::ContraflowLane(l) => map.get_l(l).length(),
             PathStep::Turn(_) => Distance::ZERO,
         };
-        self.state = self.crossing_state(start_dist, now, map);
-        peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
+        self.state = self.crossing_state(start_dist, now, map, peds_per_traversable);
+        let on = self.path.current_step().as_traversable();
+        peds_per_traversable.insert(on, self.id);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
-            self.path.current_step().as_traversable(),
+            on,
         ));
+        if let Traversable::Lane(l) = on {
+            events.push(Event::PedsOnSidewalk(l, peds_per_traversable.get(on).len()));
+        }
         true
     }
 }
@@ -629,6 +676,31 @@ impl PedState {
     }
 }
 
+// How much room pedestrians have on a sidewalk, corner, or crosswalk, in square meters
+pub fn walkable_area(on: Traversable, map: &Map) -> f64 {
+    let width = match on {
+        Traversable::Lane(l) => map.get_parent(l).get_sidewalk_width(),
+        Traversable::Turn(t) => {
+            if map.get_t(t).turn_type == TurnType::Crosswalk {
+                CROSSWALK_WIDTH
+            } else {
+                map.get_parent(t.src).get_sidewalk_width()
+            }
+        }
+    };
+    // Corners can be tiny.
+    let length = on.length(map).max(Distance::meters(1.0));
+    length.inner_meters() * width.inner_meters()
+}
+
+// Weidmann's speed-density relationship. Pedestrians barely notice a few other people, but slow
+// to a shuffle as the crowd approaches the jam density.
+fn crowded_speed(free_speed: Speed, num_peds: usize, area: f64) -> Speed {
+    let density = (num_peds as f64) / area;
+    let fraction = 1.0 - (-1.913 * (1.0 / density - 1.0 / JAM_DENSITY)).exp();
+    free_speed * fraction.max(MIN_CROWDED_SPEED_FRACTION)
+}
+
 // The crowds returned here may have low/high values extending up to radius past the real geometry.
 fn find_crowds(
     input: Vec<(PedestrianID, Distance)>,
@@ -673,3 +745,40 @@ fn find_crowds(
 
     (loners, crowds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::crowded_speed;
+    use geom::Speed;
+
+    fn approx(a: Speed, b: f64) {
+        let a = a.inner_meters_per_second();
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn crowds_slow_pedestrians_down() {
+        let free = Speed::meters_per_second(1.34);
+        // 50m of a 2m wide sidewalk
+        let area = 100.0;
+
+        // Alone, or with just a few other people around
+        approx(crowded_speed(free, 1, area), 1.34);
+        approx(crowded_speed(free, 10, area), 1.34);
+
+        let mut last = free;
+        for num_peds in &[50, 100, 200, 400, 540] {
+            let speed = crowded_speed(free, *num_peds, area);
+            assert!(
+                speed < last,
+                "{} people move at {}, but fewer moved at {}",
+                num_peds,
+                speed,
+                last
+            );
+            last = speed;
+        }
+        // At the jam density, everybody shuffles along at the minimum.
+        approx(last, 1.34 * 0.1);
+    }
+}
@@ -1,9 +1,9 @@
 use crate::mechanics::Queue;
 use crate::{ParkingSimState, ParkingSpot, SidewalkSpot, Vehicle};
-use geom::Distance;
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathStep, Position,
-    Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
+    Position, Traversable, TurnID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, VecDeque};
@@ -304,6 +304,167 @@ impl Router {
         self.path.modify_step(2, PathStep::Lane(best_lane), map);
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
+
+    // Which lanes could the vehicle move to partway along the current lane, and still make the
+    // same turn at the end? Only immediately adjacent lanes of the same type, trying the left
+    // (passing) side first.
+    pub fn mid_lane_change_candidates(&self, map: &Map) -> Vec<(LaneID, TurnID)> {
+        let (current_lane, current_turn) = {
+            let steps = self.path.get_steps();
+            if steps.len() < 2 {
+                return Vec::new();
+            }
+            match (steps[0], steps[1]) {
+                (PathStep::Lane(l), PathStep::Turn(t)) => (l, t),
+                _ => {
+                    return Vec::new();
+                }
+            }
+        };
+
+        let parent = map.get_parent(current_lane);
+        let (fwds, idx) = parent.dir_and_offset(current_lane);
+        let siblings = if fwds {
+            &parent.children_forwards
+        } else {
+            &parent.children_backwards
+        };
+        let lt = siblings[idx].1;
+
+        let mut candidates = Vec::new();
+        for other_idx in [idx.checked_sub(1), Some(idx + 1)].iter().flatten() {
+            if let Some((l, other_lt)) = siblings.get(*other_idx) {
+                if *other_lt != lt {
+                    continue;
+                }
+                let turn = TurnID {
+                    parent: current_turn.parent,
+                    src: *l,
+                    dst: current_turn.dst,
+                };
+                if map.maybe_get_t(turn).is_some() {
+                    candidates.push((*l, turn));
+                }
+            }
+        }
+        candidates
+    }
+
+    // Must be one of the mid_lane_change_candidates.
+    pub fn change_current_lane(&mut self, lane: LaneID, turn: TurnID, map: &Map) {
+        self.path.modify_current_step(PathStep::Lane(lane), map);
+        self.path.modify_step(1, PathStep::Turn(turn), map);
+    }
+}
+
+// Only switch to a new path if it's at least this much faster than the rest of the current one.
+const MIN_REROUTE_IMPROVEMENT: f64 = 0.2;
+
+impl Router {
+    // Is the rest of the path slow enough right now that another route might be worth looking for?
+    // Pathfinding with live costs is expensive, and if the current path flows freely, nothing
+    // else is likely to be much faster.
+    pub fn path_is_congested<F: Fn(Traversable) -> Duration>(&self, cost: F, map: &Map) -> bool {
+        let mut live = Duration::ZERO;
+        let mut free_flow = Duration::ZERO;
+        for step in self.path.get_steps() {
+            let on = step.as_traversable();
+            live += cost(on);
+            free_flow += on.length(map) / on.speed_limit(map);
+        }
+        live > free_flow * (1.0 + MIN_REROUTE_IMPROVEMENT)
+    }
+
+    // Look for a faster way to the same end, given the current estimate of how long it takes to
+    // cross each lane and turn. front is how far along the current lane the vehicle is. Returns
+    // true if the path changed.
+    pub fn maybe_reroute<F: Fn(Traversable) -> Duration>(
+        &mut self,
+        cost: F,
+        front: Distance,
+        map: &Map,
+    ) -> bool {
+        let current_lane = self.head().as_lane();
+        let end_lane = self.path.last_step().as_lane();
+        if current_lane == end_lane {
+            return false;
+        }
+        match self.goal {
+            Goal::ParkNearBuilding { .. } | Goal::EndAtBorder { .. } => {}
+            // Bikes and buses stick to their route.
+            Goal::BikeThenStop { .. } | Goal::FollowBusRoute { .. } => {
+                return false;
+            }
+        }
+
+        let total_cost = |path: &Path| {
+            path.get_steps().iter().fold(Duration::ZERO, |sum, step| {
+                sum + cost(step.as_traversable())
+            })
+        };
+        let new_path = if let Some(p) = map.pathfind_with_costs(
+            PathRequest {
+                start: Position::new(current_lane, front),
+                end: Position::new(end_lane, self.path.end_dist()),
+                constraints: PathConstraints::Car,
+            },
+            |lane, turn| {
+                let dt = cost(Traversable::Lane(lane.id)) + cost(Traversable::Turn(turn.id));
+                // 0.1s resolution is plenty
+                Some((dt.inner_seconds() * 10.0).round() as usize)
+            },
+        ) {
+            p
+        } else {
+            return false;
+        };
+        if total_cost(&new_path) < total_cost(&self.path) * (1.0 - MIN_REROUTE_IMPROVEMENT) {
+            self.path = new_path;
+            return true;
+        }
+        false
+    }
+
+    // An incident is blocking part of the map. If the rest of the path goes through it, find a
+    // way around to the same end. front is how far along the current lane the vehicle is. Returns
+    // true if the path changed.
+    pub fn route_around<F: Fn(Traversable) -> bool>(
+        &mut self,
+        blocked: F,
+        constraints: PathConstraints,
+        front: Distance,
+        map: &Map,
+    ) -> bool {
+        if self.head().maybe_lane().is_none() || self.last_step() {
+            return false;
+        }
+        if let Goal::FollowBusRoute { .. } = self.goal {
+            return false;
+        }
+        if !self
+            .path
+            .get_steps()
+            .iter()
+            .skip(1)
+            .any(|step| blocked(step.as_traversable()))
+        {
+            return false;
+        }
+
+        let end_lane = self.path.last_step().as_lane();
+        if let Some(p) = map.pathfind_avoiding(
+            PathRequest {
+                start: Position::new(self.head().as_lane(), front),
+                end: Position::new(end_lane, self.path.end_dist()),
+                constraints,
+            },
+            blocked,
+        ) {
+            self.path = p;
+            return true;
+        }
+        false
+    }
 }
 
 // Unrealistically assumes the driver has knowledge of currently free parking spots, even if
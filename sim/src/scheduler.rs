@@ -14,8 +14,13 @@ pub enum Command {
     UpdateCar(CarID),
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
+    // Stuck behind something slow partway along a lane; see if there's room to go around.
+    TryLaneChange(CarID),
     UpdatePed(PedestrianID),
     UpdateIntersection(IntersectionID),
+    // Index into the scheduled incidents
+    StartIncident(usize),
+    ClearIncident(usize),
     Savestate(Duration),
 }
 
@@ -33,8 +38,12 @@ impl Command {
             Command::SpawnPed(ref create) => CommandType::Ped(create.id),
             Command::UpdateCar(id) => CommandType::Car(*id),
             Command::UpdateLaggyHead(id) => CommandType::CarLaggyHead(*id),
+            Command::TryLaneChange(id) => CommandType::CarLaneChange(*id),
             Command::UpdatePed(id) => CommandType::Ped(*id),
             Command::UpdateIntersection(id) => CommandType::Intersection(*id),
+            Command::StartIncident(idx) | Command::ClearIncident(idx) => {
+                CommandType::Incident(*idx)
+            }
             Command::Savestate(_) => CommandType::Savestate,
         }
     }
@@ -46,8 +55,10 @@ impl Command {
 pub enum CommandType {
     Car(CarID),
     CarLaggyHead(CarID),
+    CarLaneChange(CarID),
     Ped(PedestrianID),
     Intersection(IntersectionID),
+    Incident(usize),
     Savestate,
 }
 
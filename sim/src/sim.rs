@@ -1,25 +1,31 @@
 use crate::{
-    AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, FinishedTrips, GetDrawAgents,
-    IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Router, Scheduler,
-    SidewalkPOI, SidewalkSpot, TransitSimState, TripCount, TripID, TripLeg, TripManager,
-    TripPositions, TripResult, TripSpawner, TripSpec, TripStart, TripStatus, UnzoomedAgent,
-    VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
+    walkable_area, AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, CrosswalkRules,
+    DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, DrivingGoal, DrivingSimState, Event,
+    EventLog, EventSink, FinishedTrips, GetDrawAgents, Incident, IntersectionPolicy,
+    IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedLOS, PedestrianID, PersonID,
+    Router, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripCount, TripID, TripLeg,
+    TripManager, TripPositions, TripResult, TripSpawner, TripSpec, TripStart, TripStatus,
+    TurnRequest, UnzoomedAgent, VehicleSpec, VehicleType, WalkingSimState, BUS_CAPACITY,
+    BUS_LENGTH, BUS_MAX_ACCEL, BUS_MAX_DECEL, TRAIN_CAPACITY, TRAIN_LENGTH, TRAIN_MAX_ACCEL,
+    TRAIN_MAX_DECEL,
 };
 use abstutil::{elapsed_seconds, Timer};
 use derivative::Derivative;
 use geom::{Distance, Duration, DurationHistogram, PolyLine, Pt2D};
 use map_model::{
     BuildingID, BusRoute, BusRouteID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    PathRequest, PathStep, Traversable,
+    PathRequest, PathStep, Phase, Position, SignalMode, Traversable, Weekday,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::panic;
 use std::time::Instant;
 
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
+// Scheduled buses appear this much earlier than they need to, in case there's no room to spawn
+// right away.
+const SCHEDULED_BUS_SLACK: Duration = Duration::const_seconds(30.0);
 
 #[derive(Serialize, Deserialize, Derivative)]
 #[derivative(PartialEq)]
@@ -52,6 +58,14 @@ pub struct Sim {
     // TODO Maybe the buffered events in child objects should also have this.
     #[derivative(PartialEq = "ignore")]
     analytics: Analytics,
+
+    // These don't affect the simulation, just observe it.
+    #[derivative(PartialEq = "ignore")]
+    event_logs: Vec<EventLog>,
+    // Not part of savestates; callers have to add these again after loading.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_sinks: Vec<Box<dyn EventSink>>,
 }
 
 #[derive(Clone)]
@@ -60,7 +74,22 @@ pub struct SimOptions {
     pub savestate_every: Option<Duration>,
     pub use_freeform_policy_everywhere: bool,
     pub disable_block_the_box: bool,
+    pub crosswalk_rules: CrosswalkRules,
     pub recalc_lanechanging: bool,
+    // Vehicles speed up and slow down gradually, instead of instantly changing speed.
+    pub use_acceleration: bool,
+    // Vehicles stuck behind something slow or stopped can move to an adjacent lane partway along
+    // a road.
+    pub mid_lane_changing: bool,
+    // Cars stuck behind a bike can pass it without leaving the lane, once there's a big enough gap
+    // in oncoming traffic.
+    pub pass_bikes: bool,
+    // Drivers periodically look for a faster route, using travel times observed so far.
+    pub dynamic_rerouting: bool,
+    // Write every Event to these files. The format is picked by extension: .csv or JSON lines.
+    pub event_logs: Vec<String>,
+    // Which day of the week to run bus timetables for
+    pub service_day: Weekday,
 }
 
 impl SimOptions {
@@ -70,7 +99,14 @@ impl SimOptions {
             savestate_every: None,
             use_freeform_policy_everywhere: false,
             disable_block_the_box: false,
+            crosswalk_rules: CrosswalkRules::StopSignDecides,
             recalc_lanechanging: true,
+            use_acceleration: false,
+            mid_lane_changing: false,
+            pass_bikes: false,
+            dynamic_rerouting: false,
+            event_logs: Vec::new(),
+            service_day: Weekday::Monday,
         }
     }
 }
@@ -83,7 +119,14 @@ impl Sim {
             scheduler.push(d, Command::Savestate(d));
         }
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(
+                map,
+                opts.recalc_lanechanging,
+                opts.use_acceleration,
+                opts.mid_lane_changing,
+                opts.pass_bikes,
+                opts.dynamic_rerouting,
+            ),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
@@ -91,8 +134,9 @@ impl Sim {
                 &mut scheduler,
                 opts.use_freeform_policy_everywhere,
                 opts.disable_block_the_box,
+                opts.crosswalk_rules,
             ),
-            transit: TransitSimState::new(),
+            transit: TransitSimState::new(opts.service_day),
             trips: TripManager::new(),
             spawner: TripSpawner::new(),
             scheduler,
@@ -108,6 +152,12 @@ impl Sim {
             trip_positions: None,
 
             analytics: Analytics::new(),
+            event_logs: opts
+                .event_logs
+                .into_iter()
+                .map(EventLog::from_path)
+                .collect(),
+            event_sinks: Vec::new(),
         }
     }
 
@@ -153,12 +203,81 @@ impl Sim {
         };
 
         self.spawner
-            .schedule_trip(start_time, ped_id, car_id, spec, map, &self.parking);
+            .schedule_trip(start_time, None, ped_id, car_id, spec, map, &self.parking);
         (ped_id, car_id)
     }
 
+    // The trips must be in order. The same pedestrian and bike are used for every trip, and each
+    // trip only begins after the previous one is over.
+    pub fn schedule_person(&mut self, trips: Vec<(Duration, TripSpec)>, map: &Map) -> PersonID {
+        let person = self.trips.new_person();
+        let ped = PedestrianID(self.ped_id_counter);
+        self.ped_id_counter += 1;
+        let mut bike: Option<(CarID, VehicleSpec)> = None;
+
+        for (start_time, mut spec) in trips {
+            let car_id = match spec {
+                TripSpec::CarAppearing {
+                    ref mut vehicle_spec,
+                    ..
+                } => {
+                    if vehicle_spec.vehicle_type == VehicleType::Bike {
+                        if let Some((id, ref bike_spec)) = bike {
+                            *vehicle_spec = bike_spec.clone();
+                            Some(id)
+                        } else {
+                            let id = CarID(self.car_id_counter, VehicleType::Bike);
+                            self.car_id_counter += 1;
+                            bike = Some((id, vehicle_spec.clone()));
+                            Some(id)
+                        }
+                    } else {
+                        let id = CarID(self.car_id_counter, vehicle_spec.vehicle_type);
+                        self.car_id_counter += 1;
+                        Some(id)
+                    }
+                }
+                TripSpec::UsingBike {
+                    ref mut vehicle, ..
+                } => {
+                    if let Some((id, ref bike_spec)) = bike {
+                        *vehicle = bike_spec.clone();
+                        Some(id)
+                    } else {
+                        let id = CarID(self.car_id_counter, VehicleType::Bike);
+                        self.car_id_counter += 1;
+                        bike = Some((id, vehicle.clone()));
+                        Some(id)
+                    }
+                }
+                TripSpec::UsingParkedCar { .. }
+                | TripSpec::MaybeUsingParkedCar { .. }
+                | TripSpec::JustWalking { .. }
+                | TripSpec::UsingTransit { .. } => None,
+            };
+            let ped_id = match spec {
+                TripSpec::CarAppearing {
+                    goal: DrivingGoal::Border(_, _),
+                    ..
+                } => None,
+                _ => Some(ped),
+            };
+            self.spawner.schedule_trip(
+                start_time,
+                Some(person),
+                ped_id,
+                car_id,
+                spec,
+                map,
+                &self.parking,
+            );
+        }
+        person
+    }
+
     pub fn spawn_all_trips(&mut self, map: &Map, timer: &mut Timer, retry_if_no_room: bool) {
         self.spawner.spawn_all(
+            self.time,
             map,
             &self.parking,
             &mut self.trips,
@@ -214,23 +333,18 @@ impl Sim {
         for (next_stop_idx, mut path, end_dist) in
             self.transit.create_empty_route(route, map).into_iter()
         {
-            let id = CarID(self.car_id_counter, VehicleType::Bus);
+            let (spec, capacity) = transit_vehicle_spec(route);
+            let id = CarID(self.car_id_counter, spec.vehicle_type);
             self.car_id_counter += 1;
 
-            // For now, no desire for randomness. Caller can pass in list of specs if that ever
-            // changes.
-            let vehicle = VehicleSpec {
-                vehicle_type: VehicleType::Bus,
-                length: BUS_LENGTH,
-                max_speed: None,
-            }
-            .make(id, None);
+            let vehicle = spec.make(id, None);
 
             // TODO The path analytics (total dist, dist crossed so far) will be wrong for the
             // first round of buses.
             // Same for this TripStart, though it doesn't matter too much.
             let trip = self.trips.new_trip(
                 self.time,
+                None,
                 TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
                 vec![TripLeg::ServeBusRoute(id, route.id)],
             );
@@ -241,7 +355,8 @@ impl Sim {
                         "Giving up on seeding a bus headed towards stop {} of {} ({})",
                         next_stop_idx, route.name, route.id
                     ));
-                    self.trips.abort_trip_failed_start(trip);
+                    self.trips
+                        .abort_trip_failed_start(self.time, trip, &mut self.scheduler);
                     break;
                 }
                 let start_lane = if let PathStep::Lane(l) = path.current_step() {
@@ -271,7 +386,8 @@ impl Sim {
                     &mut self.scheduler,
                 ) {
                     self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
-                    self.transit.bus_created(id, route.id, next_stop_idx);
+                    self.transit
+                        .bus_created(id, route.id, next_stop_idx, capacity);
                     results.push(id);
                     return results;
                 } else {
@@ -286,9 +402,110 @@ impl Sim {
         results
     }
 
+    // Unlike seed_bus_route, buses follow the route's timetable for the service day. Each
+    // scheduled trip gets its own bus, appearing in time to reach the first stop before it's
+    // scheduled to depart and vanishing after the last.
+    pub fn schedule_bus_route(
+        &mut self,
+        route: &BusRoute,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Vec<CarID> {
+        let mut results: Vec<CarID> = Vec::new();
+        for trip_idx in self.transit.create_scheduled_route(route) {
+            let first_stop = &route.timetable[trip_idx].stops[0];
+            let pos = map.get_bs(first_stop.stop).driving_pos;
+            let lane = map.get_l(pos.lane());
+
+            let (spec, capacity) = transit_vehicle_spec(route);
+            let id = CarID(self.car_id_counter, spec.vehicle_type);
+            self.car_id_counter += 1;
+            let vehicle = spec.make(id, None);
+
+            // The bus appears at the start of the first stop's lane. If it doesn't fit behind the
+            // stop, pretend the stop is a bit further along.
+            let end_dist = pos.dist_along().max(vehicle.length + Distance::meters(1.0));
+            if end_dist > lane.length() {
+                timer.warn(format!(
+                    "Skipping scheduled trip {} of {} ({}), because the bus doesn't fit at the \
+                     first stop",
+                    trip_idx, route.name, route.id
+                ));
+                continue;
+            }
+            let path = map
+                .pathfind(PathRequest {
+                    start: Position::new(lane.id, vehicle.length),
+                    end: Position::new(lane.id, end_dist),
+                    constraints: route.constraints,
+                })
+                .unwrap();
+
+            // Buses wait at the first stop until it's time to depart, so showing up early is fine.
+            let speed_limit = map.get_parent(lane.id).get_speed_limit();
+            let speed = match vehicle.max_speed {
+                Some(s) => s.min(speed_limit),
+                None => speed_limit,
+            };
+            let lead = (end_dist - vehicle.length) / speed + SCHEDULED_BUS_SLACK;
+            let spawn_time = if first_stop.arrival > lead {
+                first_stop.arrival - lead
+            } else {
+                Duration::ZERO
+            };
+
+            let trip = self.trips.new_trip(
+                spawn_time,
+                None,
+                TripStart::BusStop(first_stop.stop),
+                vec![TripLeg::ServeBusRoute(id, route.id)],
+            );
+            self.transit.bus_scheduled(id, route.id, trip_idx, capacity);
+            self.scheduler.push(
+                spawn_time,
+                Command::SpawnCar(
+                    CreateCar {
+                        start_dist: vehicle.length,
+                        vehicle,
+                        router: Router::follow_bus_route(path, end_dist),
+                        maybe_parked_car: None,
+                        trip,
+                    },
+                    true,
+                ),
+            );
+            results.push(id);
+        }
+        results
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.run_name = name;
     }
+
+    pub fn add_event_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.event_sinks.push(sink);
+    }
+
+    // Replace the usual policy for one intersection. Best done before the simulation starts.
+    // Like event sinks, these aren't part of savestates. After loading a savestate, assign the same
+    // policies again before stepping; otherwise the first agent to reach one of those
+    // intersections panics.
+    pub fn set_intersection_policy(
+        &mut self,
+        i: IntersectionID,
+        policy: Box<dyn IntersectionPolicy>,
+    ) {
+        self.intersections.set_policy(i, policy);
+    }
+
+    // Block part of the map later. Scenarios call this for their incidents.
+    pub fn schedule_incident(&mut self, mut incident: Incident) {
+        incident.start_time = incident.start_time.max(self.time);
+        let start = incident.start_time;
+        let idx = self.intersections.add_incident(incident);
+        self.scheduler.push(start, Command::StartIncident(idx));
+    }
 }
 
 // Drawing
@@ -365,6 +582,7 @@ impl Sim {
             if let Some(t) = savestate_at {
                 if time > t {
                     self.time = t;
+                    self.flush_event_sinks();
                     self.save();
                     savestate_at = None;
                 }
@@ -385,6 +603,9 @@ impl Sim {
                             AgentID::Car(create_car.vehicle.id),
                             create_car.trip,
                         );
+                        if create_car.vehicle.vehicle_type.is_transit() {
+                            self.transit.bus_spawned(create_car.vehicle.id);
+                        }
                         if let Some(parked_car) = create_car.maybe_parked_car {
                             self.parking.remove_parked_car(parked_car);
                         }
@@ -398,14 +619,27 @@ impl Sim {
                             "No room to spawn car for {}. Not retrying!",
                             create_car.trip
                         );
-                        self.trips.abort_trip_failed_start(create_car.trip);
+                        self.trips.abort_trip_failed_start(
+                            self.time,
+                            create_car.trip,
+                            &mut self.scheduler,
+                        );
                     }
                 }
                 Command::SpawnPed(mut create_ped) => {
                     let ok = if let SidewalkPOI::DeferredParkingSpot(b, driving_goal) =
                         create_ped.goal.connection.clone()
                     {
-                        if let Some(parked_car) = self.parking.dynamically_reserve_car(b) {
+                        // Somebody who drove somewhere earlier goes back to their own car.
+                        let maybe_parked_car =
+                            if let Some(car) = self.trips.get_person_car(create_ped.trip) {
+                                self.parking.dynamically_reserve_car_by_id(car)
+                            } else {
+                                None
+                            };
+                        if let Some(parked_car) =
+                            maybe_parked_car.or_else(|| self.parking.dynamically_reserve_car(b))
+                        {
                             create_ped.goal =
                                 SidewalkSpot::parking_spot(parked_car.spot, map, &self.parking);
                             if let Some(path) = map.pathfind(PathRequest {
@@ -488,7 +722,11 @@ impl Sim {
                             }
                         }
                     } else {
-                        self.trips.abort_trip_failed_start(create_ped.trip);
+                        self.trips.abort_trip_failed_start(
+                            self.time,
+                            create_ped.trip,
+                            &mut self.scheduler,
+                        );
                     }
                 }
                 Command::UpdateCar(car) => {
@@ -513,6 +751,15 @@ impl Sim {
                         &mut self.scheduler,
                     );
                 }
+                Command::TryLaneChange(car) => {
+                    self.driving.try_lane_change(
+                        car,
+                        self.time,
+                        map,
+                        &mut self.intersections,
+                        &mut self.scheduler,
+                    );
+                }
                 Command::UpdatePed(ped) => {
                     self.walking.update_ped(
                         ped,
@@ -526,8 +773,39 @@ impl Sim {
                     );
                 }
                 Command::UpdateIntersection(i) => {
+                    let detected = match map.get_traffic_signal(i).mode {
+                        SignalMode::Actuated {
+                            detector_length, ..
+                        } => self.driving.detect_vehicles(
+                            &map.get_i(i).incoming_lanes,
+                            detector_length,
+                            self.time,
+                        ),
+                        SignalMode::FixedTime => BTreeSet::new(),
+                    };
+                    self.intersections.update_intersection(
+                        self.time,
+                        i,
+                        map,
+                        &mut self.scheduler,
+                        &detected,
+                    );
+                }
+                Command::StartIncident(idx) => {
+                    self.intersections.start_incident(idx);
+                    self.driving.incident_started(
+                        self.time,
+                        map,
+                        &mut self.intersections,
+                        &mut self.scheduler,
+                    );
+                    let duration = self.intersections.get_incident(idx).duration;
+                    self.scheduler
+                        .push(self.time + duration, Command::ClearIncident(idx));
+                }
+                Command::ClearIncident(idx) => {
                     self.intersections
-                        .update_intersection(self.time, i, map, &mut self.scheduler);
+                        .clear_incident(self.time, idx, &mut self.scheduler, map);
                 }
                 Command::Savestate(frequency) => {
                     self.scheduler
@@ -543,15 +821,24 @@ impl Sim {
             events.extend(self.transit.collect_events());
             events.extend(self.driving.collect_events());
             events.extend(self.walking.collect_events());
+            events.extend(self.intersections.collect_events());
             for ev in events {
+                for log in &mut self.event_logs {
+                    log.event(self.time, &ev);
+                }
+                for sink in &mut self.event_sinks {
+                    sink.event(self.time, &ev);
+                }
                 self.analytics.event(ev, self.time, map);
             }
         }
         if let Some(t) = savestate_at {
             self.time = t;
+            self.flush_event_sinks();
             self.save();
         }
         self.time = target_time;
+        self.flush_event_sinks();
 
         self.trip_positions = None;
     }
@@ -589,6 +876,16 @@ impl Sim {
         }
     }
 
+    // Savestates record how much of each EventLog was written, so flush before saving.
+    fn flush_event_sinks(&mut self) {
+        for log in &mut self.event_logs {
+            log.flush();
+        }
+        for sink in &mut self.event_sinks {
+            sink.flush();
+        }
+    }
+
     pub fn dump_before_abort(&self) {
         println!(
             "********************************************************************************"
@@ -724,6 +1021,7 @@ impl Sim {
         abstutil::find_next_file(self.save_path(base_time))
     }
 
+    // Intersection policies and event sinks aren't restored; see set_intersection_policy.
     pub fn load_savestate(path: String, timer: &mut Timer) -> Result<Sim, std::io::Error> {
         println!("Loading {}", path);
         abstutil::read_binary(&path, timer)
@@ -758,6 +1056,24 @@ impl Sim {
         self.trips.get_finished_trips()
     }
 
+    // Cars on these trips follow the given route instead of pathfinding, as long as they start and
+    // end in the same place. Has to be called before trips spawn.
+    pub fn assign_car_routes(&mut self, routes: BTreeMap<TripID, Path>) {
+        self.trips.assign_car_routes(routes);
+    }
+
+    // Remember the route each car starts with. Off by default, since every path would wind up in
+    // savestates. Has to be called before trips spawn.
+    pub fn record_car_routes(&mut self) {
+        self.trips.record_car_routes();
+    }
+
+    // When each car started, where, and the route it started with. Later changes from dynamic
+    // rerouting aren't included. Empty unless record_car_routes was called.
+    pub fn get_car_routes(&self) -> &BTreeMap<TripID, (Duration, Position, Path)> {
+        self.trips.get_car_routes()
+    }
+
     pub fn count_trips_involving_bldg(&self, b: BuildingID) -> TripCount {
         self.trips.count_trips_involving_bldg(b, self.time)
     }
@@ -790,9 +1106,13 @@ impl Sim {
     pub fn car_tooltip(&self, car: CarID) -> Vec<String> {
         if let Some(mut lines) = self.driving.tooltip_lines(car, self.time) {
             lines.extend(self.trips.tooltip_lines(AgentID::Car(car)));
-            if car.1 == VehicleType::Bus {
+            if car.1.is_transit() {
                 let passengers = self.transit.get_passengers(car);
-                lines.push(format!("{} passengers riding", passengers.len()));
+                lines.push(format!(
+                    "{} / {} passengers riding",
+                    passengers.len(),
+                    self.transit.get_capacity(car)
+                ));
                 for (id, stop) in passengers {
                     lines.push(format!("- {} till {:?}", id, stop));
                 }
@@ -815,7 +1135,7 @@ impl Sim {
     }
 
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<BusRouteID> {
-        if maybe_bus.1 == VehicleType::Bus {
+        if maybe_bus.1.is_transit() {
             Some(self.transit.bus_route(maybe_bus))
         } else {
             None
@@ -839,7 +1159,12 @@ impl Sim {
     }
 
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
-        for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
+        for vt in &[
+            VehicleType::Car,
+            VehicleType::Bike,
+            VehicleType::Bus,
+            VehicleType::Train,
+        ] {
             let id = CarID(idx, *vt);
             if self.driving.tooltip_lines(id, self.time).is_some() {
                 return Some(id);
@@ -922,8 +1247,49 @@ impl Sim {
         self.intersections.get_accepted_agents(id)
     }
 
-    pub fn get_intersection_delays(&self, id: IntersectionID) -> &DurationHistogram {
-        self.intersections.get_intersection_delays(id)
+    // Everybody asking to start a turn through this intersection right now
+    pub fn get_waiting_requests(&self, id: IntersectionID) -> Vec<TurnRequest> {
+        self.intersections.get_waiting_requests(id)
+    }
+
+    pub fn get_intersection_delays(&self, id: IntersectionID) -> DurationHistogram {
+        self.analytics.intersection_delays(self.time, id)
+    }
+
+    pub fn get_ped_signal_delays(&self, id: IntersectionID) -> DurationHistogram {
+        self.analytics.ped_signal_delays(self.time, id)
+    }
+
+    pub fn get_num_stranded_peds(&self, id: IntersectionID) -> usize {
+        self.analytics.num_stranded_peds(self.time, id)
+    }
+
+    // How crowded a sidewalk is right now, as the number of pedestrians and the level-of-service
+    pub fn current_ped_los(&self, l: LaneID, map: &Map) -> (usize, PedLOS) {
+        let on = Traversable::Lane(l);
+        let count = self.walking.num_peds_on(on);
+        let los = if count == 0 {
+            PedLOS::A
+        } else {
+            PedLOS::from_space(walkable_area(on, map) / (count as f64))
+        };
+        (count, los)
+    }
+
+    // Incidents blocking part of the map right now
+    pub fn get_active_incidents(&self) -> Vec<&Incident> {
+        self.intersections.get_active_incidents()
+    }
+
+    // Use this instead of ControlTrafficSignal::current_phase_and_remaining_time, so actuated
+    // signals work.
+    pub fn current_signal_phase<'a>(
+        &self,
+        i: IntersectionID,
+        map: &'a Map,
+    ) -> (usize, &'a Phase, Duration) {
+        self.intersections
+            .current_phase_and_remaining_time(map.get_traffic_signal(i), self.time)
     }
 
     pub fn location_of_buses(&self, route: BusRouteID, map: &Map) -> Vec<(CarID, Pt2D)> {
@@ -962,7 +1328,8 @@ impl Sim {
 impl Sim {
     pub fn kill_stuck_car(&mut self, id: CarID, map: &Map) {
         if let Some(trip) = self.agent_to_trip(AgentID::Car(id)) {
-            self.trips.abort_trip_failed_start(trip);
+            self.trips
+                .abort_trip_failed_start(self.time, trip, &mut self.scheduler);
             self.driving.kill_stuck_car(
                 id,
                 self.time,
@@ -976,3 +1343,32 @@ impl Sim {
         }
     }
 }
+
+// For now, no desire for randomness. Caller can pass in list of specs if that ever changes.
+// Also returns the passenger capacity, which the route can override.
+fn transit_vehicle_spec(route: &BusRoute) -> (VehicleSpec, usize) {
+    let (spec, default_capacity) = if route.constraints == PathConstraints::Train {
+        (
+            VehicleSpec {
+                vehicle_type: VehicleType::Train,
+                length: TRAIN_LENGTH,
+                max_speed: None,
+                max_accel: TRAIN_MAX_ACCEL,
+                max_decel: TRAIN_MAX_DECEL,
+            },
+            TRAIN_CAPACITY,
+        )
+    } else {
+        (
+            VehicleSpec {
+                vehicle_type: VehicleType::Bus,
+                length: BUS_LENGTH,
+                max_speed: None,
+                max_accel: BUS_MAX_ACCEL,
+                max_decel: BUS_MAX_DECEL,
+            },
+            BUS_CAPACITY,
+        )
+    };
+    (spec, route.capacity.unwrap_or(default_capacity))
+}
@@ -1,13 +1,20 @@
 use crate::{CarID, Event, PedestrianID, Router, Scheduler, TripManager, WalkingSimState};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration};
-use map_model::{
-    BusRoute, BusRouteID, BusStopID, Map, Path, PathConstraints, PathRequest, Position,
-};
+use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position, Weekday};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-// These index stops along a route, not stops along a single sidewalk.
+// The time a bus spends at a stop is the time to open and close the doors, plus time for each
+// passenger getting on and off. Buses always wait at least TIME_TO_WAIT_AT_STOP, like they did
+// before dwell time depended on passengers, so lightly used stops behave the same as before.
+const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
+const TIME_TO_OPEN_AND_CLOSE_DOORS: Duration = Duration::const_seconds(5.0);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(3.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(2.0);
+
+// These index stops along a route, not stops along a single sidewalk. For buses following the
+// timetable, they index the stops of that scheduled trip instead.
 type StopIdx = usize;
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -20,6 +27,7 @@ struct StopForRoute {
 
 #[derive(Serialize, Deserialize, PartialEq)]
 struct Route {
+    // Only for routes with buses looping forever. Empty for routes following the timetable.
     stops: Vec<StopForRoute>,
     buses: Vec<CarID>,
 }
@@ -30,7 +38,11 @@ struct Bus {
     route: BusRouteID,
     // Where does each passenger want to deboard?
     passengers: Vec<(PedestrianID, BusStopID)>,
+    // How many passengers fit, seated and standing
+    capacity: usize,
     state: BusState,
+    // Only for buses following the timetable: which of the route's scheduled trips this is.
+    scheduled_trip: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -54,16 +66,26 @@ pub struct TransitSimState {
     routes: BTreeMap<BusRouteID, Route>,
     // Can organize this more to make querying cheaper
     peds_waiting: Vec<(PedestrianID, BusStopID, BusRouteID, BusStopID)>,
+    // Which day's timetable to follow
+    service_day: Weekday,
+    // Buses that'll start a scheduled trip later: (route, index into the timetable, capacity)
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    scheduled_buses: BTreeMap<CarID, (BusRouteID, usize, usize)>,
 
     events: Vec<Event>,
 }
 
 impl TransitSimState {
-    pub fn new() -> TransitSimState {
+    pub fn new(service_day: Weekday) -> TransitSimState {
         TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting: Vec::new(),
+            service_day,
+            scheduled_buses: BTreeMap::new(),
             events: Vec::new(),
         }
     }
@@ -94,7 +116,7 @@ impl TransitSimState {
                         .pathfind(PathRequest {
                             start: stop1.driving_pos,
                             end: map.get_bs(bus_route.stops[stop2_idx]).driving_pos,
-                            constraints: PathConstraints::Bus,
+                            constraints: bus_route.constraints,
                         })
                         .expect(&format!(
                             "No route between bus stops {:?} and {:?}",
@@ -125,7 +147,31 @@ impl TransitSimState {
         stops
     }
 
-    pub fn bus_created(&mut self, bus: CarID, route: BusRouteID, next_stop_idx: StopIdx) {
+    // Returns the indices of the scheduled trips that run on the service day.
+    pub fn create_scheduled_route(&mut self, bus_route: &BusRoute) -> Vec<usize> {
+        self.routes.insert(
+            bus_route.id,
+            Route {
+                stops: Vec::new(),
+                buses: Vec::new(),
+            },
+        );
+        bus_route
+            .timetable
+            .iter()
+            .enumerate()
+            .filter(|(_, trip)| trip.days.contains(&self.service_day))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    pub fn bus_created(
+        &mut self,
+        bus: CarID,
+        route: BusRouteID,
+        next_stop_idx: StopIdx,
+        capacity: usize,
+    ) {
         self.routes.get_mut(&route).unwrap().buses.push(bus);
         self.buses.insert(
             bus,
@@ -133,11 +179,45 @@ impl TransitSimState {
                 car: bus,
                 route,
                 passengers: Vec::new(),
+                capacity,
                 state: BusState::DrivingToStop(next_stop_idx),
+                scheduled_trip: None,
             },
         );
     }
 
+    // The bus won't exist until it spawns and calls bus_spawned.
+    pub fn bus_scheduled(
+        &mut self,
+        bus: CarID,
+        route: BusRouteID,
+        trip_idx: usize,
+        capacity: usize,
+    ) {
+        self.scheduled_buses
+            .insert(bus, (route, trip_idx, capacity));
+    }
+
+    // Called for every bus that spawns. Buses looping forever were already created.
+    pub fn bus_spawned(&mut self, bus: CarID) {
+        if let Some((route, trip_idx, capacity)) = self.scheduled_buses.remove(&bus) {
+            self.routes.get_mut(&route).unwrap().buses.push(bus);
+            self.buses.insert(
+                bus,
+                Bus {
+                    car: bus,
+                    route,
+                    passengers: Vec::new(),
+                    capacity,
+                    state: BusState::DrivingToStop(0),
+                    scheduled_trip: Some(trip_idx),
+                },
+            );
+        }
+    }
+
+    // Returns how long the bus should wait at the stop, or None if the bus just finished its
+    // scheduled trip and should vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Duration,
@@ -146,14 +226,24 @@ impl TransitSimState {
         walking: &mut WalkingSimState,
         scheduler: &mut Scheduler,
         map: &Map,
-    ) {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
+        let mut wait = TIME_TO_OPEN_AND_CLOSE_DOORS;
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
-                let stop = self.routes[&bus.route].stops[stop_idx].id;
+                let stop = get_stop(&self.routes, bus, stop_idx, map);
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop));
+                if let Some(trip_idx) = bus.scheduled_trip {
+                    let scheduled = &map.get_br(bus.route).timetable[trip_idx].stops[stop_idx];
+                    self.events.push(Event::ScheduledBusArrivedAtStop(
+                        id,
+                        bus.route,
+                        stop,
+                        scheduled.arrival,
+                    ));
+                }
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
@@ -161,61 +251,143 @@ impl TransitSimState {
                     if stop == stop2 {
                         self.events.push(Event::PedLeavesBus(ped, id, bus.route));
                         trips.ped_left_bus(now, ped, map, scheduler);
+                        wait += TIME_PER_ALIGHTING;
                     } else {
                         still_riding.push((ped, stop2));
                     }
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                if is_last_stop(bus, stop_idx, map) {
+                    // Nobody boards a bus that isn't going to their stop.
+                    assert!(bus.passengers.is_empty());
+                    let route = bus.route;
+                    self.buses.remove(&id);
+                    self.routes
+                        .get_mut(&route)
+                        .unwrap()
+                        .buses
+                        .retain(|b| *b != id);
+                    return None;
+                }
+
+                // Board new passengers, in the order they started waiting, until the bus fills up.
                 let mut still_waiting = Vec::new();
                 for (ped, stop1, route, stop2) in self.peds_waiting.drain(..) {
-                    if stop == stop1 && bus.route == route {
-                        bus.passengers.push((ped, stop2));
-                        self.events.push(Event::PedEntersBus(ped, id, route));
-                        trips.ped_boarded_bus(ped, walking);
-                    } else {
-                        still_waiting.push((ped, stop1, route, stop2));
+                    if stop == stop1 && bus.route == route && goes_to(bus, stop_idx, stop2, map) {
+                        if bus.passengers.len() < bus.capacity {
+                            bus.passengers.push((ped, stop2));
+                            self.events.push(Event::PedEntersBus(ped, id, route));
+                            trips.ped_boarded_bus(ped, walking);
+                            wait += TIME_PER_BOARDING;
+                            continue;
+                        }
+                        self.events
+                            .push(Event::PedPassedUpByFullBus(ped, id, route, stop));
                     }
+                    still_waiting.push((ped, stop1, route, stop2));
                 }
                 self.peds_waiting = still_waiting;
+                if wait < TIME_TO_WAIT_AT_STOP {
+                    wait = TIME_TO_WAIT_AT_STOP;
+                }
+
+                // Early buses hold at timepoints, so they don't run ahead of the timetable. They
+                // always wait to start the trip on time.
+                if let Some(trip_idx) = bus.scheduled_trip {
+                    let scheduled = &map.get_br(bus.route).timetable[trip_idx].stops[stop_idx];
+                    if (scheduled.timepoint || stop_idx == 0) && scheduled.departure - now > wait {
+                        wait = scheduled.departure - now;
+                    }
+                }
             }
             BusState::AtStop(_) => unreachable!(),
         };
+        Some(wait)
     }
 
-    pub fn bus_departed_from_stop(&mut self, id: CarID) -> Router {
+    // Fails if a bus following the timetable can't reach its next stop, like after map edits. The
+    // bus stays at the stop.
+    pub fn bus_departed_from_stop(&mut self, id: CarID, map: &Map) -> Result<Router, String> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(_) => unreachable!(),
             BusState::AtStop(stop_idx) => {
+                if let Some(trip_idx) = bus.scheduled_trip {
+                    let bus_route = map.get_br(bus.route);
+                    let trip = &bus_route.timetable[trip_idx];
+                    let stop1 = map.get_bs(trip.stops[stop_idx].stop);
+                    let stop2 = map.get_bs(trip.stops[stop_idx + 1].stop);
+                    let path = map
+                        .pathfind(PathRequest {
+                            start: stop1.driving_pos,
+                            end: stop2.driving_pos,
+                            constraints: bus_route.constraints,
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "{} on {} can't get from {} to {}",
+                                id, bus_route.name, stop1.id, stop2.id
+                            )
+                        })?;
+
+                    bus.state = BusState::DrivingToStop(stop_idx + 1);
+                    self.events
+                        .push(Event::BusDepartedFromStop(id, bus.route, stop1.id));
+                    self.events.push(Event::BusLoad(
+                        id,
+                        bus.route,
+                        stop1.id,
+                        bus.passengers.len(),
+                        bus.capacity,
+                    ));
+                    return Ok(Router::follow_bus_route(
+                        path,
+                        stop2.driving_pos.dist_along(),
+                    ));
+                }
+
                 let route = &self.routes[&bus.route];
                 let stop = &route.stops[stop_idx];
 
                 bus.state = BusState::DrivingToStop(stop.next_stop_idx);
                 self.events
                     .push(Event::BusDepartedFromStop(id, bus.route, stop.id));
-                Router::follow_bus_route(
+                self.events.push(Event::BusLoad(
+                    id,
+                    bus.route,
+                    stop.id,
+                    bus.passengers.len(),
+                    bus.capacity,
+                ));
+                Ok(Router::follow_bus_route(
                     stop.path_to_next_stop.clone(),
                     route.stops[stop.next_stop_idx].driving_pos.dist_along(),
-                )
+                ))
             }
         }
     }
 
-    // If true, the pedestrian boarded a bus immediately.
+    // If true, the pedestrian boarded a bus immediately. The bus doesn't wait any longer for them.
     pub fn ped_waiting_for_bus(
         &mut self,
         ped: PedestrianID,
         stop1: BusStopID,
         route_id: BusRouteID,
         stop2: BusStopID,
+        map: &Map,
     ) -> bool {
         assert!(stop1 != stop2);
         let route = &self.routes[&route_id];
         for bus in &route.buses {
-            if let BusState::AtStop(idx) = self.buses[bus].state {
-                if route.stops[idx].id == stop1 {
+            let b = &self.buses[bus];
+            if let BusState::AtStop(idx) = b.state {
+                if get_stop(&self.routes, b, idx, map) == stop1 && goes_to(b, idx, stop2, map) {
+                    if b.passengers.len() == b.capacity {
+                        self.events
+                            .push(Event::PedPassedUpByFullBus(ped, *bus, route_id, stop1));
+                        continue;
+                    }
                     self.buses
                         .get_mut(bus)
                         .unwrap()
@@ -240,6 +412,10 @@ impl TransitSimState {
         &self.buses[&bus].passengers
     }
 
+    pub fn get_capacity(&self, bus: CarID) -> usize {
+        self.buses[&bus].capacity
+    }
+
     pub fn bus_route(&self, bus: CarID) -> BusRouteID {
         self.buses[&bus].route
     }
@@ -252,3 +428,31 @@ impl TransitSimState {
         }
     }
 }
+
+fn get_stop(routes: &BTreeMap<BusRouteID, Route>, bus: &Bus, idx: StopIdx, map: &Map) -> BusStopID {
+    if let Some(trip_idx) = bus.scheduled_trip {
+        map.get_br(bus.route).timetable[trip_idx].stops[idx].stop
+    } else {
+        routes[&bus.route].stops[idx].id
+    }
+}
+
+fn is_last_stop(bus: &Bus, idx: StopIdx, map: &Map) -> bool {
+    if let Some(trip_idx) = bus.scheduled_trip {
+        idx == map.get_br(bus.route).timetable[trip_idx].stops.len() - 1
+    } else {
+        // Looping buses never stop.
+        false
+    }
+}
+
+// Will the bus, currently at this stop, reach another stop later?
+fn goes_to(bus: &Bus, idx: StopIdx, stop: BusStopID, map: &Map) -> bool {
+    if let Some(trip_idx) = bus.scheduled_trip {
+        map.get_br(bus.route).timetable[trip_idx].stops[idx + 1..]
+            .iter()
+            .any(|s| s.stop == stop)
+    } else {
+        true
+    }
+}
@@ -1,12 +1,13 @@
 use crate::{
     AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    Vehicle, WalkingSimState,
+    ParkingSpot, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState,
+    TripID, Vehicle, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Duration, Speed};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, Path, PathConstraints, PathRequest,
+    PathStep, Position,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
@@ -22,6 +23,21 @@ pub struct TripManager {
     active_trip_mode: BTreeMap<AgentID, TripID>,
     num_bus_trips: usize,
     unfinished_trips: usize,
+    people: Vec<Person>,
+    // Routes for cars decided ahead of time, from traffic assignment
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    assigned_car_routes: BTreeMap<TripID, Path>,
+    // Only filled out if record_car_routes is on: when each car started, where exactly, and the
+    // route it started with
+    record_car_routes: bool,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    car_routes: BTreeMap<TripID, (Duration, Position, Path)>,
 
     events: Vec<Event>,
 }
@@ -33,13 +49,29 @@ impl TripManager {
             active_trip_mode: BTreeMap::new(),
             num_bus_trips: 0,
             unfinished_trips: 0,
+            people: Vec::new(),
+            assigned_car_routes: BTreeMap::new(),
+            record_car_routes: false,
+            car_routes: BTreeMap::new(),
             events: Vec::new(),
         }
     }
 
+    pub fn new_person(&mut self) -> PersonID {
+        let id = PersonID(self.people.len());
+        self.people.push(Person {
+            trips: Vec::new(),
+            car: None,
+            delayed_trips: VecDeque::new(),
+        });
+        id
+    }
+
+    // Trips belonging to a person are created in the order they happen.
     pub fn new_trip(
         &mut self,
         spawned_at: Duration,
+        person: Option<PersonID>,
         start: TripStart,
         legs: Vec<TripLeg>,
     ) -> TripID {
@@ -95,10 +127,16 @@ impl TripManager {
             legs: VecDeque::from(legs),
             start,
             end,
+            person,
         };
         if !trip.is_bus_trip() {
             self.unfinished_trips += 1;
         }
+        if let Some(p) = person {
+            let person = &mut self.people[p.0];
+            person.trips.push(id);
+            person.remember_car(&trip.legs);
+        }
         self.trips.push(trip);
         id
     }
@@ -108,6 +146,90 @@ impl TripManager {
         trip.legs = VecDeque::from(legs);
         // This is only for peds using a previously unknown parked car
         trip.mode = TripMode::Drive;
+        if let Some(p) = trip.person {
+            self.people[p.0].remember_car(&trip.legs);
+        }
+    }
+
+    pub fn assign_car_routes(&mut self, routes: BTreeMap<TripID, Path>) {
+        self.assigned_car_routes = routes;
+    }
+
+    pub fn record_car_routes(&mut self) {
+        self.record_car_routes = true;
+    }
+
+    pub fn get_car_routes(&self) -> &BTreeMap<TripID, (Duration, Position, Path)> {
+        &self.car_routes
+    }
+
+    // Use the route assigned ahead of time if it still fits the request, since where the car
+    // starts might have changed. Otherwise find a new path.
+    pub fn pick_car_route<F: FnOnce() -> Option<Path>>(
+        &mut self,
+        now: Duration,
+        trip: TripID,
+        req: &PathRequest,
+        pathfind: F,
+    ) -> Option<Path> {
+        let path = match self.assigned_car_routes.get(&trip) {
+            Some(path)
+                if path.get_steps().front() == Some(&PathStep::Lane(req.start.lane()))
+                    && path.last_step() == PathStep::Lane(req.end.lane())
+                    && path.end_dist() == req.end.dist_along() =>
+            {
+                Some(path.clone())
+            }
+            _ => pathfind(),
+        }?;
+        if self.record_car_routes {
+            self.car_routes.insert(trip, (now, req.start, path.clone()));
+        }
+        Some(path)
+    }
+
+    // A person's trip can't start until their previous trip is over, so it might have to wait.
+    // Only for use while spawning trips in a batch.
+    pub fn schedule_trip_start(
+        &mut self,
+        start_time: Duration,
+        trip: TripID,
+        cmd: Command,
+        scheduler: &mut Scheduler,
+    ) {
+        if let Some(p) = self.trips[trip.0].person {
+            let person = &self.people[p.0];
+            let previous_done = person
+                .trips
+                .iter()
+                .take_while(|t| **t != trip)
+                .all(|t| self.trips[t.0].finished_at.is_some() || self.trips[t.0].aborted);
+            if !previous_done || !person.delayed_trips.is_empty() {
+                self.people[p.0]
+                    .delayed_trips
+                    .push_back((start_time, trip, cmd));
+                return;
+            }
+        }
+        scheduler.quick_push(start_time, cmd);
+    }
+
+    // The person's car, if they've used one and it's parked somewhere
+    pub fn get_person_car(&self, trip: TripID) -> Option<CarID> {
+        self.people[self.trips[trip.0].person?.0].car
+    }
+
+    // Called whenever a trip finishes or aborts. If it belonged to somebody, their next trip can
+    // start, but not before it was originally supposed to.
+    fn trip_over(&mut self, now: Duration, id: TripID, scheduler: &mut Scheduler) {
+        if let Some(p) = self.trips[id.0].person {
+            if let Some((start_time, next, cmd)) = self.people[p.0].delayed_trips.pop_front() {
+                // Don't count time spent waiting for the previous trip as part of this one
+                let start_time = start_time.max(now);
+                self.trips[next.0].spawned_at = start_time;
+                scheduler.push(start_time, cmd);
+            }
+        }
     }
 
     pub fn agent_starting_trip_leg(&mut self, agent: AgentID, trip: TripID) {
@@ -150,6 +272,8 @@ impl TripManager {
                         trip.mode,
                         now - trip.spawned_at,
                     ));
+                    let id = trip.id;
+                    self.trip_over(now, id, scheduler);
                     return;
                 }
                 _ => {}
@@ -164,6 +288,8 @@ impl TripManager {
             scheduler,
         ) {
             self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.trip_over(now, id, scheduler);
         }
     }
 
@@ -177,11 +303,11 @@ impl TripManager {
         scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedParkingSpot(ped, spot));
-        let trip = &mut self.trips[self
+        let id = self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
-            .unwrap()
-            .0];
+            .unwrap();
+        let trip = &mut self.trips[id.0];
 
         trip.assert_walking_leg(ped, SidewalkSpot::parking_spot(spot, map, parking));
         let (car, drive_to) = match trip.legs[0] {
@@ -197,20 +323,24 @@ impl TripManager {
             start = Position::new(start.lane(), start.dist_along() + parked_car.vehicle.length);
         }
         let end = drive_to.goal_pos(PathConstraints::Car, map);
-        let path = if let Some(p) = map.pathfind(PathRequest {
+        let req = PathRequest {
             start,
             end,
             constraints: PathConstraints::Car,
-        }) {
+        };
+        let path = if let Some(p) =
+            self.pick_car_route(now, id, &req, || map.pathfind_at(req.clone(), now))
+        {
             p
         } else {
             println!(
                 "Aborting {} at {} because no path for the car portion! {} to {}",
-                trip.id, now, start, end
+                id, now, start, end
             );
             self.unfinished_trips -= 1;
-            trip.aborted = true;
-            self.events.push(Event::TripAborted(trip.id));
+            self.trips[id.0].aborted = true;
+            self.events.push(Event::TripAborted(id));
+            self.trip_over(now, id, scheduler);
             return;
         };
 
@@ -218,7 +348,7 @@ impl TripManager {
         scheduler.push(
             now,
             Command::SpawnCar(
-                CreateCar::for_parked_car(parked_car.clone(), router, start.dist_along(), trip.id),
+                CreateCar::for_parked_car(parked_car.clone(), router, start.dist_along(), id),
                 true,
             ),
         );
@@ -262,7 +392,9 @@ impl TripManager {
             );
             self.unfinished_trips -= 1;
             trip.aborted = true;
-            self.events.push(Event::TripAborted(trip.id));
+            let id = trip.id;
+            self.events.push(Event::TripAborted(id));
+            self.trip_over(now, id, scheduler);
             return;
         };
 
@@ -297,6 +429,8 @@ impl TripManager {
 
         if !trip.spawn_ped(now, bike_rack, map, scheduler) {
             self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.trip_over(now, id, scheduler);
         }
     }
 
@@ -306,6 +440,7 @@ impl TripManager {
         ped: PedestrianID,
         bldg: BuildingID,
         map: &Map,
+        scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedBuilding(ped, bldg));
         let trip = &mut self.trips[self
@@ -318,11 +453,10 @@ impl TripManager {
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
-        self.events.push(Event::TripFinished(
-            trip.id,
-            trip.mode,
-            now - trip.spawned_at,
-        ));
+        let id = trip.id;
+        self.events
+            .push(Event::TripFinished(id, trip.mode, now - trip.spawned_at));
+        self.trip_over(now, id, scheduler);
     }
 
     // If no route is returned, the pedestrian boarded a bus immediately.
@@ -344,7 +478,7 @@ impl TripManager {
         }
         match trip.legs[1] {
             TripLeg::RideBus(_, route, stop2) => {
-                if transit.ped_waiting_for_bus(ped, stop, route, stop2) {
+                if transit.ped_waiting_for_bus(ped, stop, route, stop2, map) {
                     trip.legs.pop_front();
                     None
                 } else {
@@ -381,6 +515,8 @@ impl TripManager {
 
         if !trip.spawn_ped(now, start, map, scheduler) {
             self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.trip_over(now, id, scheduler);
         }
     }
 
@@ -390,6 +526,7 @@ impl TripManager {
         ped: PedestrianID,
         i: IntersectionID,
         map: &Map,
+        scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedBorder(ped, i));
         let trip = &mut self.trips[self
@@ -402,14 +539,19 @@ impl TripManager {
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
-        self.events.push(Event::TripFinished(
-            trip.id,
-            trip.mode,
-            now - trip.spawned_at,
-        ));
+        let id = trip.id;
+        self.events
+            .push(Event::TripFinished(id, trip.mode, now - trip.spawned_at));
+        self.trip_over(now, id, scheduler);
     }
 
-    pub fn car_or_bike_reached_border(&mut self, now: Duration, car: CarID, i: IntersectionID) {
+    pub fn car_or_bike_reached_border(
+        &mut self,
+        now: Duration,
+        car: CarID,
+        i: IntersectionID,
+        scheduler: &mut Scheduler,
+    ) {
         self.events.push(Event::CarOrBikeReachedBorder(car, i));
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
         match trip.legs.pop_front().unwrap() {
@@ -420,27 +562,51 @@ impl TripManager {
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
-        self.events.push(Event::TripFinished(
-            trip.id,
-            trip.mode,
-            now - trip.spawned_at,
-        ));
+        let id = trip.id;
+        self.events
+            .push(Event::TripFinished(id, trip.mode, now - trip.spawned_at));
+        self.trip_over(now, id, scheduler);
     }
 
-    pub fn abort_trip_failed_start(&mut self, id: TripID) {
+    pub fn bus_finished_trip(&mut self, now: Duration, car: CarID) {
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
+        match trip.legs.pop_front().unwrap() {
+            TripLeg::ServeBusRoute(id, _) => assert_eq!(car, id),
+            _ => unreachable!(),
+        };
+        assert!(trip.legs.is_empty());
+        assert!(!trip.finished_at.is_some());
+        trip.finished_at = Some(now);
+        self.num_bus_trips -= 1;
+        // No TripFinished event. Bus trips aren't anybody's trip, so they'd just skew the results.
+    }
+
+    pub fn abort_trip_failed_start(
+        &mut self,
+        now: Duration,
+        id: TripID,
+        scheduler: &mut Scheduler,
+    ) {
         self.trips[id.0].aborted = true;
         if !self.trips[id.0].is_bus_trip() {
             self.unfinished_trips -= 1;
         }
         self.events.push(Event::TripAborted(id));
+        self.trip_over(now, id, scheduler);
     }
 
-    pub fn abort_trip_impossible_parking(&mut self, car: CarID) {
+    pub fn abort_trip_impossible_parking(
+        &mut self,
+        now: Duration,
+        car: CarID,
+        scheduler: &mut Scheduler,
+    ) {
         let trip = self.active_trip_mode.remove(&AgentID::Car(car)).unwrap();
         assert!(!self.trips[trip.0].is_bus_trip());
         self.trips[trip.0].aborted = true;
         self.unfinished_trips -= 1;
         self.events.push(Event::TripAborted(trip));
+        self.trip_over(now, trip, scheduler);
     }
 
     pub fn active_agents(&self) -> Vec<AgentID> {
@@ -478,11 +644,21 @@ impl TripManager {
     pub fn tooltip_lines(&self, id: AgentID) -> Vec<String> {
         // Only called for agents that _should_ have trips
         let trip = &self.trips[self.active_trip_mode[&id].0];
-        vec![format!(
+        let mut lines = vec![format!(
             "{} has goal {:?}",
             trip.id,
             trip.legs.back().unwrap()
-        )]
+        )];
+        if let Some(p) = trip.person {
+            let person = &self.people[p.0];
+            lines.push(format!(
+                "{} is on trip {} of {}",
+                p,
+                person.trips.iter().position(|t| *t == trip.id).unwrap() + 1,
+                person.trips.len()
+            ));
+        }
+        lines
     }
 
     // (active not including buses, unfinished, buses)
@@ -502,9 +678,13 @@ impl TripManager {
         };
         for t in &self.trips {
             if let Some(end) = t.finished_at {
-                result
-                    .finished_trips
-                    .push((t.id, t.mode, end - t.spawned_at));
+                // Buses following the timetable finish their trips, but they aren't anybody's
+                // trip.
+                if !t.is_bus_trip() {
+                    result
+                        .finished_trips
+                        .push((t.id, t.mode, end - t.spawned_at));
+                }
             } else if t.aborted {
                 result.aborted_trips += 1;
             }
@@ -591,6 +771,7 @@ struct Trip {
     mode: TripMode,
     start: TripStart,
     end: TripEnd,
+    person: Option<PersonID>,
 }
 
 impl Trip {
@@ -667,6 +848,28 @@ impl Trip {
     }
 }
 
+// Somebody making a sequence of trips through the day. Each trip can only start once the previous
+// one is over, and the car they drive somewhere is the one they drive back.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Person {
+    trips: Vec<TripID>,
+    car: Option<CarID>,
+    // Trips waiting on the previous one to finish, with their original start time
+    delayed_trips: VecDeque<(Duration, TripID, Command)>,
+}
+
+impl Person {
+    fn remember_car(&mut self, legs: &VecDeque<TripLeg>) {
+        for leg in legs {
+            if let TripLeg::Drive(ref vehicle, _) = leg {
+                if vehicle.vehicle_type == VehicleType::Car {
+                    self.car = Some(vehicle.id);
+                }
+            }
+        }
+    }
+}
+
 // These don't specify where the leg starts, since it might be unknown -- like when we drive and
 // don't know where we'll wind up parking.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -721,6 +924,8 @@ impl std::fmt::Display for TripMode {
 pub enum TripStart {
     Bldg(BuildingID),
     Border(IntersectionID),
+    // Buses following a timetable start at the first stop of their scheduled trip.
+    BusStop(BusStopID),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
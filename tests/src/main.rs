@@ -1,9 +1,11 @@
 mod geom;
 mod map_conversion;
 mod parking;
+mod pathfinding;
 mod runner;
 mod sim_completion;
 mod sim_determinism;
+mod traffic_signals;
 mod transit;
 mod trips;
 
@@ -32,8 +34,10 @@ fn main() {
     geom::run(t.suite("geom"));
     map_conversion::run(t.suite("map_conversion"));
     parking::run(t.suite("parking"));
+    pathfinding::run(t.suite("pathfinding"));
     sim_completion::run(t.suite("sim_completion"));
     sim_determinism::run(t.suite("sim_determinism"));
+    traffic_signals::run(t.suite("traffic_signals"));
     transit::run(t.suite("transit"));
     trips::run(t.suite("trips"));
 
@@ -1,4 +1,6 @@
 use crate::runner::TestRunner;
+use geom::Speed;
+use map_model::osm;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("convert_osm_twice", |_| {
@@ -10,6 +12,7 @@ pub fn run(t: &mut TestRunner) {
             gtfs: Some("../data/input/google_transit_2018_18_08".to_string()),
             neighborhoods: Some("../data/input/neighborhoods.geojson".to_string()),
             clip: Some(abstutil::path_polygon("montlake")),
+            elevation: None,
             output: "convert_osm_twice.bin".to_string(),
         };
 
@@ -46,6 +49,97 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_slow("light_rail_tracks_convert", |_| {
+        // Smaller maps don't have any Link tracks or streetcars.
+        let map = map_model::Map::new(
+            &abstutil::path_raw_map("huge_seattle"),
+            true,
+            &mut abstutil::Timer::throwaway(),
+        )
+        .unwrap();
+
+        let mut num_tracks = 0;
+        for r in map.all_roads() {
+            let lanes = r.all_lanes();
+            if osm::is_light_rail(&r.osm_tags) && r.osm_tags.contains_key(osm::HIGHWAY) {
+                // Streetcars share the street with everybody else.
+                assert!(
+                    lanes.iter().any(|l| map.get_l(*l).is_light_rail()),
+                    "{} has a streetcar, but no track",
+                    r.id
+                );
+                assert!(
+                    lanes.iter().any(|l| !map.get_l(*l).is_light_rail()),
+                    "{} lost its other lanes to a streetcar",
+                    r.id
+                );
+            } else if osm::is_light_rail(&r.osm_tags) {
+                num_tracks += 1;
+                // Trains run both ways on the track, so there is one lane in each direction
+                assert_eq!(lanes.len(), 2, "{} should have one lane each way", r.id);
+                for l in lanes {
+                    assert!(
+                        map.get_l(l).is_light_rail(),
+                        "{} on {} isn't a track",
+                        l,
+                        r.id
+                    );
+                }
+                if !r.osm_tags.contains_key(osm::MAXSPEED) {
+                    assert_eq!(r.get_speed_limit(), Speed::miles_per_hour(35.0));
+                }
+            } else {
+                for l in lanes {
+                    assert!(
+                        !map.get_l(l).is_light_rail(),
+                        "{} on {} is a track",
+                        l,
+                        r.id
+                    );
+                }
+            }
+        }
+        assert!(num_tracks > 0, "No light rail tracks found");
+
+        // Trains never leave the tracks, and nothing else drives onto them.
+        for t in map.all_turns().values() {
+            assert_eq!(
+                map.get_l(t.id.src).is_light_rail(),
+                map.get_l(t.id.dst).is_light_rail(),
+                "{} connects a track to something else",
+                t.id
+            );
+        }
+    });
+
+    t.run_slow("osm_crossings_convert", |_| {
+        let flags = convert_osm::Flags {
+            osm: "../data/input/montlake.osm".to_string(),
+            parking_shapes: None,
+            offstreet_parking: None,
+            sidewalks: None,
+            gtfs: None,
+            neighborhoods: None,
+            clip: Some(abstutil::path_polygon("montlake")),
+            elevation: None,
+            output: "osm_crossings_convert.bin".to_string(),
+        };
+        let map = convert_osm::convert(&flags, &mut abstutil::Timer::throwaway());
+
+        // Crossing nodes are somewhere along the original OSM ways, but should only wind up on
+        // the end of the road piece they're close to.
+        let num_crossings = map
+            .roads
+            .values()
+            .filter(|r| {
+                r.osm_tags.contains_key(osm::CROSSING_FWD)
+                    || r.osm_tags.contains_key(osm::CROSSING_BACK)
+            })
+            .count();
+        assert!(num_crossings > 0, "No crossings found");
+        assert!(num_crossings < map.roads.len());
+    });
+
     t.run_slow("bigger_map_loads", |_| {
         map_model::Map::new(
             &abstutil::path_raw_map("23rd"),
@@ -0,0 +1,402 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, Polygon, Speed};
+use map_model::{
+    Budget, BuildingID, EditCmd, IntersectionID, LaneType, Map, Path, PathConstraints, PathRequest,
+    PathStep, Position, Reachability, TravelTimeTable, TurnPenalties, TurnType,
+};
+use popdat::psrc::Parcel;
+use popdat::{JobAccess, Skims, Zone};
+use sim::{SimFlags, TripMode};
+use std::collections::HashMap;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("route_around_peak_congestion", |h| {
+        let (mut map, _, _) =
+            SimFlags::for_test("route_around_peak_congestion").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let req = driving_request(&map, BuildingID(319), BuildingID(90));
+        let normal = map.pathfind(req.clone()).unwrap();
+        let lanes: Vec<_> = normal
+            .get_steps()
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Lane(l) => Some(*l),
+                _ => None,
+            })
+            .collect();
+        let jammed = lanes[lanes.len() / 2];
+
+        // Somebody sat in the middle of the usual route for an hour, but only around 8am.
+        let table = TravelTimeTable::from_observations(
+            Duration::minutes(15),
+            vec![(Duration::minutes(8 * 60), jammed, Duration::minutes(60))].into_iter(),
+        );
+        let path = h.output_path("travel_times.csv");
+        table.save_csv(&path).unwrap();
+        assert_eq!(TravelTimeTable::load_csv(&path).unwrap(), table);
+        map.set_travel_times(Some(table));
+
+        let peak = map
+            .pathfind_at(req.clone(), Duration::minutes(8 * 60))
+            .unwrap();
+        assert!(!peak.get_steps().contains(&PathStep::Lane(jammed)));
+        let early = map.pathfind_at(req, Duration::minutes(6 * 60)).unwrap();
+        assert!(early.get_steps().contains(&PathStep::Lane(jammed)));
+    });
+
+    t.run_slow("left_turn_penalty_changes_route", |_| {
+        let (mut map, _, _) =
+            SimFlags::for_test("left_turn_penalty_changes_route").load(&mut Timer::throwaway());
+        let bldgs: Vec<BuildingID> = map
+            .all_buildings()
+            .iter()
+            .filter(|b| {
+                map.find_closest_lane(b.sidewalk(), vec![LaneType::Driving])
+                    .is_ok()
+            })
+            .map(|b| b.id)
+            .collect();
+        let mut requests = Vec::new();
+        for from in bldgs.iter().take(10) {
+            for to in bldgs.iter().rev().take(10) {
+                requests.push(driving_request(&map, *from, *to));
+            }
+        }
+        // U-turns have their own penalty.
+        let num_lefts = |map: &Map, path: &Path| {
+            path.get_steps()
+                .iter()
+                .filter(|step| match step {
+                    PathStep::Turn(t) => {
+                        map.get_t(*t).turn_type == TurnType::Left
+                            && map.get_l(t.src).parent != map.get_l(t.dst).parent
+                    }
+                    _ => false,
+                })
+                .count()
+        };
+        let before: Vec<Option<usize>> = requests
+            .iter()
+            .map(|req| map.pathfind(req.clone()).map(|path| num_lefts(&map, &path)))
+            .collect();
+        assert!(before.iter().any(|n| n.unwrap_or(0) > 0));
+
+        // Left turns cost as much as driving for 10 minutes.
+        let mut penalties = TurnPenalties::none();
+        penalties.left = Duration::minutes(10);
+        map.set_turn_penalties(PathConstraints::Car, penalties);
+        map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+        let mut fewer_lefts = 0;
+        for (req, before) in requests.iter().zip(before) {
+            let after = map.pathfind(req.clone()).map(|path| num_lefts(&map, &path));
+            // Penalties don't make anything unreachable.
+            assert_eq!(before.is_some(), after.is_some());
+            if let (Some(before), Some(after)) = (before, after) {
+                assert!(after <= before);
+                if after < before {
+                    fewer_lefts += 1;
+                }
+            }
+        }
+        assert!(fewer_lefts > 0, "No route avoided a penalized left turn");
+    });
+
+    t.run_slow("alternative_routes", |_| {
+        let (map, _, _) = SimFlags::for_test("alternative_routes").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let req = driving_request(&map, BuildingID(319), BuildingID(90));
+        let alts = map.pathfind_alternatives(req.clone(), 3);
+        assert!(
+            alts.len() > 1,
+            "Only found {} route(s) for {}",
+            alts.len(),
+            req
+        );
+        assert_eq!(alts[0].overlap, 0.0);
+        for (idx, alt) in alts.iter().enumerate() {
+            assert!(alt.overlap <= 0.8);
+            for earlier in &alts[0..idx] {
+                assert_ne!(alt.path.get_steps(), earlier.path.get_steps());
+                assert!(alt.cost >= earlier.cost);
+            }
+        }
+    });
+
+    t.run_slow("isochrones_from_building", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("isochrones_from_building").load(&mut Timer::throwaway());
+        let budget = Duration::minutes(15);
+        for &(constraints, use_transit) in &[
+            (PathConstraints::Pedestrian, false),
+            (PathConstraints::Pedestrian, true),
+            (PathConstraints::Bike, false),
+            (PathConstraints::Car, false),
+        ] {
+            let reachable = Reachability::from_building(
+                BuildingID(319),
+                constraints,
+                use_transit,
+                Budget::Time(budget),
+                &map,
+            )
+            .unwrap();
+            assert!(!reachable.lanes.is_empty());
+            assert!(reachable.lanes.values().all(|r| r.time <= budget));
+            // Transit can only help.
+            if use_transit {
+                let walking = Reachability::from_building(
+                    BuildingID(319),
+                    constraints,
+                    false,
+                    Budget::Time(budget),
+                    &map,
+                )
+                .unwrap();
+                assert!(reachable.lanes.len() >= walking.lanes.len());
+            }
+        }
+    });
+
+    t.run_slow("walking_reach_matches_pathfinding", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("walking_reach_matches_pathfinding").load(&mut Timer::throwaway());
+        let walking_speed = Speed::meters_per_second(1.34);
+        let start = map.get_b(BuildingID(319)).front_path.sidewalk;
+        let reachable = Reachability::new(
+            start,
+            PathConstraints::Pedestrian,
+            false,
+            Budget::Time(Duration::minutes(30)),
+            &map,
+        );
+        let ends: Vec<Position> = map
+            .all_buildings()
+            .iter()
+            .map(|b| b.front_path.sidewalk)
+            .filter(|pos| reachable.lanes.contains_key(&pos.lane()))
+            .collect();
+        assert!(ends.len() >= 10);
+        for end in ends.iter().step_by(ends.len() / 10) {
+            let path = map
+                .pathfind(PathRequest {
+                    start,
+                    end: *end,
+                    constraints: PathConstraints::Pedestrian,
+                })
+                .unwrap();
+            // Everything before the last lane, counting all of the first lane. The last lane is
+            // reached right after that.
+            let steps = path.get_steps();
+            let before_last = steps
+                .iter()
+                .take(steps.len() - 1)
+                .fold(Distance::ZERO, |sum, step| {
+                    sum + step.as_traversable().length(&map)
+                });
+            let first_lane = map.get_l(start.lane()).length();
+            let reached = reachable.lanes[&end.lane()];
+            // Never slower than the actual route...
+            assert!(
+                reached.time <= before_last / walking_speed + Duration::seconds(1.0),
+                "Reached {} in {}, but the path there takes {}",
+                end.lane(),
+                reached.time,
+                before_last / walking_speed
+            );
+            // ... and not much faster either. Pathfinding avoids hills, so allow some detours.
+            assert!(
+                before_last <= 1.5 * (reached.dist + first_lane),
+                "Reached {} after {}, but the path there is {}",
+                end.lane(),
+                reached.dist,
+                before_last
+            );
+        }
+    });
+
+    t.run_slow("job_access", |_| {
+        let (map, _, _) = SimFlags::for_test("job_access").load(&mut Timer::throwaway());
+        let parcel = |num_households, num_employees| Parcel {
+            num_households,
+            num_employees,
+            offstreet_parking_spaces: 0,
+        };
+        let mut parcels = HashMap::new();
+        parcels.insert(BuildingID(319), parcel(10, 100));
+        parcels.insert(BuildingID(90), parcel(0, 1000));
+
+        let access = JobAccess::new(
+            &map,
+            &parcels,
+            Duration::minutes(15),
+            &mut Timer::throwaway(),
+        );
+        // Only buildings where people live
+        assert_eq!(access.bldgs.len(), 1);
+        let jobs = &access.bldgs[&BuildingID(319)];
+        // Everybody can get to the jobs in their own building.
+        for mode in TripMode::all() {
+            assert!(jobs[&mode] >= 100);
+        }
+        // Transit can only help.
+        assert!(jobs[&TripMode::Transit] >= jobs[&TripMode::Walk]);
+        assert_eq!(
+            access.average(&[BuildingID(319), BuildingID(90)], &parcels, TripMode::Walk),
+            jobs[&TripMode::Walk] as f64
+        );
+    });
+
+    t.run_slow("zone_skims", |_| {
+        let (map, _, _) = SimFlags::for_test("zone_skims").load(&mut Timer::throwaway());
+        // Each zone is just one building.
+        let zones: Vec<Zone> = vec![BuildingID(319), BuildingID(90)]
+            .into_iter()
+            .map(|b| Zone {
+                name: b.to_string(),
+                polygon: Polygon::rectangle(
+                    map.get_b(b).polygon.center(),
+                    Distance::meters(0.1),
+                    Distance::meters(0.1),
+                ),
+            })
+            .collect();
+        let skims = Skims::new(
+            &map,
+            &zones,
+            3,
+            Duration::minutes(90),
+            Duration::ZERO,
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(skims.zones.len(), 2);
+        for mode in TripMode::all() {
+            let within = skims.get(mode, 0, 0).unwrap();
+            assert_eq!(within.time, Duration::ZERO);
+            let across = skims.get(mode, 0, 1).unwrap();
+            assert!(across.time > Duration::ZERO);
+            assert!(across.dist > Distance::ZERO);
+        }
+        // Transit can only help, and nobody walking transfers.
+        assert!(
+            skims.get(TripMode::Transit, 0, 1).unwrap().time
+                <= skims.get(TripMode::Walk, 0, 1).unwrap().time
+        );
+        assert_eq!(skims.get(TripMode::Walk, 0, 1).unwrap().transfers, 0.0);
+    });
+
+    t.run_slow("incremental_pathfinding_matches_from_scratch", |_| {
+        let flags = SimFlags::for_test("incremental_pathfinding_matches_from_scratch");
+        let (mut incremental, _, _) = flags.load(&mut Timer::throwaway());
+        // Like loading a map with edits in headless mode
+        let (mut freshly_loaded, _, _) = flags.load(&mut Timer::throwaway());
+        let (mut from_scratch, _, _) = flags.load(&mut Timer::throwaway());
+
+        // Close an intersection along some route, so the edits definitely matter.
+        let map = &incremental;
+        let path = map
+            .pathfind(driving_request(map, BuildingID(319), BuildingID(90)))
+            .unwrap();
+        let lanes: Vec<_> = path
+            .get_steps()
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Lane(l) => Some(*l),
+                _ => None,
+            })
+            .collect();
+        let close = |i: IntersectionID| {
+            let mut edits = map.get_edits().clone();
+            edits.commands.push(EditCmd::CloseIntersection {
+                id: i,
+                orig_it: map.get_i(i).intersection_type,
+            });
+            edits
+        };
+        // The incremental map first gets some other edits, so the final rebuild replaces one
+        // that's still happening in the background.
+        let first_edits = close(map.get_l(lanes[lanes.len() / 4]).dst_i);
+        let edits = close(map.get_l(lanes[lanes.len() / 2]).dst_i);
+
+        let bldgs: Vec<BuildingID> = map
+            .all_buildings()
+            .iter()
+            .step_by(50)
+            .map(|b| b.id)
+            .filter(|b| {
+                map.find_closest_lane(map.get_b(*b).sidewalk(), vec![LaneType::Driving])
+                    .is_ok()
+            })
+            .collect();
+        let mut requests = Vec::new();
+        for from in &bldgs {
+            for to in &bldgs {
+                if from != to {
+                    let req = driving_request(map, *from, *to);
+                    requests.push(PathRequest {
+                        constraints: PathConstraints::Bike,
+                        ..req.clone()
+                    });
+                    requests.push(req);
+                }
+            }
+        }
+
+        incremental.apply_edits(first_edits, &mut Timer::throwaway());
+        incremental.start_recalculating_pathfinding_after_edits(&mut Timer::throwaway());
+        incremental.apply_edits(edits.clone(), &mut Timer::throwaway());
+        incremental.start_recalculating_pathfinding_after_edits(&mut Timer::throwaway());
+        freshly_loaded.apply_edits(edits.clone(), &mut Timer::throwaway());
+        freshly_loaded.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+        from_scratch.apply_edits(edits, &mut Timer::throwaway());
+        from_scratch.recalculate_pathfinding_from_scratch(&mut Timer::throwaway());
+
+        let paths = |map: &Map| -> Vec<Option<Path>> {
+            requests
+                .iter()
+                .map(|req| map.pathfind(req.clone()))
+                .collect()
+        };
+        // Pathfinding reports the cost as the length.
+        let costs = |paths: &Vec<Option<Path>>| -> Vec<Option<Distance>> {
+            paths
+                .iter()
+                .map(|path| path.as_ref().map(|p| p.total_length()))
+                .collect()
+        };
+        let steps = |paths: &Vec<Option<Path>>| -> Vec<Option<Vec<PathStep>>> {
+            paths
+                .iter()
+                .map(|path| {
+                    path.as_ref()
+                        .map(|p| p.get_steps().iter().cloned().collect())
+                })
+                .collect()
+        };
+
+        incremental.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+        assert!(incremental.finish_recalculating_pathfinding());
+        let incremental_paths = paths(&incremental);
+        // Both reuse the original graph's node ordering, so the routes must be exactly the same,
+        // no matter what order the edits happened in.
+        assert_eq!(steps(&incremental_paths), steps(&paths(&freshly_loaded)));
+        // A graph prepared from scratch breaks ties between equally good routes differently, so
+        // just compare costs.
+        assert_eq!(costs(&incremental_paths), costs(&paths(&from_scratch)));
+    });
+}
+
+fn driving_request(map: &Map, from: BuildingID, to: BuildingID) -> PathRequest {
+    let start = map
+        .find_closest_lane(map.get_b(from).sidewalk(), vec![LaneType::Driving])
+        .unwrap();
+    let end = map
+        .find_closest_lane(map.get_b(to).sidewalk(), vec![LaneType::Driving])
+        .unwrap();
+    PathRequest {
+        start: Position::new(start, Distance::ZERO),
+        end: Position::new(end, map.get_l(end).length()),
+        constraints: PathConstraints::Car,
+    }
+}
@@ -144,6 +144,7 @@ impl TestRunner {
         let start = std::time::Instant::now();
         let mut helper = TestHelper {
             debug_with_savestate: None,
+            output_prefix: format!("{}/{}", self.output_dir, test_name),
         };
         let output_path = format!("{}/{}.log", self.output_dir, test_name);
         std::fs::create_dir_all(std::path::Path::new(&output_path).parent().unwrap())
@@ -245,9 +246,15 @@ impl TestRunner {
 
 pub struct TestHelper {
     debug_with_savestate: Option<String>,
+    output_prefix: String,
 }
 
 impl TestHelper {
+    // Where a test should write any files of its own. They wind up next to the test's log.
+    pub fn output_path(&self, filename: &str) -> String {
+        format!("{}_{}", self.output_prefix, filename)
+    }
+
     pub fn setup_done(&mut self, sim: &Sim) {
         if self.debug_with_savestate.is_some() {
             panic!("Can't call setup_done twice in one test");
@@ -1,7 +1,15 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Scenario, SimFlags};
+use geom::{Duration, Speed};
+use map_model::{EditCmd, IntersectionID, LaneID, SignalMode, Traversable, TurnID, TurnType};
+use sim::{
+    AgentID, Blockage, CarID, CrosswalkRules, Event, EventSink, Incident, IncidentKind,
+    IntersectionContext, IntersectionPolicy, PedLOS, PolicyDecision, Scenario, Sim, SimFlags,
+    StrictFIFOStopSign, TripMode, TurnRequest, VehicleType,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -12,4 +20,711 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_slow("small_spawn_completes_with_acceleration", |h| {
+        let mut flags = SimFlags::for_test("small_spawn_completes_with_acceleration");
+        let (map, mut baseline, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(
+            &mut baseline,
+            &map,
+            &mut rng,
+            &mut Timer::throwaway(),
+        );
+        baseline.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        flags.opts.use_acceleration = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        // Speeding up from a stop and braking for turns, stop signs, and red lights takes time.
+        let before = mean_trip_time(&baseline, TripMode::Drive);
+        let after = mean_trip_time(&sim, TripMode::Drive);
+        println!(
+            "Driving trips took {} on average, {} with acceleration",
+            before, after
+        );
+        assert!(after > before);
+    });
+
+    t.run_slow("small_spawn_completes_with_mid_lane_changing", |h| {
+        let mut flags = SimFlags::for_test("small_spawn_completes_with_mid_lane_changing");
+        flags.opts.mid_lane_changing = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        // Which cars changed lanes, and which reached the end of their trip
+        let changed = Rc::new(RefCell::new(BTreeSet::new()));
+        let finished = Rc::new(RefCell::new(BTreeSet::new()));
+        {
+            let changed = changed.clone();
+            let finished = finished.clone();
+            sim.add_event_sink(Box::new(WatchEvents(move |_, ev: &Event| match ev {
+                Event::CarChangedLanes(car, _, _) => {
+                    changed.borrow_mut().insert(*car);
+                }
+                Event::CarReachedParkingSpot(car, _)
+                | Event::CarOrBikeReachedBorder(car, _)
+                | Event::BikeStoppedAtSidewalk(car, _) => {
+                    finished.borrow_mut().insert(*car);
+                }
+                _ => {}
+            })));
+        }
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        let changed = changed.borrow();
+        let finished = finished.borrow();
+        println!("{} vehicles changed lanes", changed.len());
+        assert!(!changed.is_empty());
+        for car in changed.iter() {
+            if !car.1.is_transit() {
+                assert!(
+                    finished.contains(car),
+                    "{} changed lanes, then got stranded",
+                    car
+                );
+            }
+        }
+    });
+
+    t.run_slow("cars_yield_to_waiting_peds", |h| {
+        let mut flags = SimFlags::for_test("cars_yield_to_waiting_peds");
+        flags.opts.crosswalk_rules = CrosswalkRules::PedsHaveRightOfWay;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        let stop_signs: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| map.maybe_get_stop_sign(i.id).is_some())
+            .map(|i| i.id)
+            .collect();
+        let entered: Rc<RefCell<Vec<(CarID, TurnID)>>> = Rc::new(RefCell::new(Vec::new()));
+        let entered_clone = entered.clone();
+        sim.add_event_sink(Box::new(WatchEvents(move |_, ev| {
+            if let Event::AgentEntersTraversable(AgentID::Car(car), Traversable::Turn(t)) = ev {
+                entered_clone.borrow_mut().push((*car, *t));
+            }
+        })));
+
+        // Crossing a crosswalk takes longer than one step, so anybody waiting to cross at the end
+        // of one step is still waiting or crossing throughout the next.
+        let mut peds_waiting: Vec<TurnID> = Vec::new();
+        let mut cars_yielded = 0;
+        for _ in 0..30 * 60 {
+            sim.step(&map, Duration::seconds(1.0));
+            for (car, t) in entered.borrow_mut().drain(..) {
+                if let Some(crosswalk) = peds_waiting
+                    .iter()
+                    .find(|c| map.get_t(**c).conflicts_with(map.get_t(t)))
+                {
+                    panic!(
+                        "{} started {} while somebody was waiting to cross {}",
+                        car, t, crosswalk
+                    );
+                }
+            }
+
+            peds_waiting.clear();
+            for i in &stop_signs {
+                let waiting = sim.get_waiting_requests(*i);
+                for req in &waiting {
+                    if let AgentID::Pedestrian(_) = req.agent {
+                        if map.get_t(req.turn).turn_type == TurnType::Crosswalk {
+                            peds_waiting.push(req.turn);
+                        }
+                    }
+                }
+                cars_yielded += waiting
+                    .iter()
+                    .filter(|req| match req.agent {
+                        AgentID::Car(_) => peds_waiting
+                            .iter()
+                            .any(|c| map.get_t(*c).conflicts_with(map.get_t(req.turn))),
+                        AgentID::Pedestrian(_) => false,
+                    })
+                    .count();
+            }
+        }
+        // Make sure the situation actually came up.
+        assert!(cars_yielded > 0);
+    });
+
+    t.run_slow("peds_judging_gaps_dont_wait_forever", |h| {
+        let mut flags = SimFlags::for_test("peds_judging_gaps_dont_wait_forever");
+        flags.opts.crosswalk_rules = CrosswalkRules::PedsJudgeGaps;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        let stop_signs: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| map.maybe_get_stop_sign(i.id).is_some())
+            .map(|i| i.id)
+            .collect();
+        // Peds give up on finding a gap after 30s and make traffic yield. Leave some time for
+        // vehicles already in the intersection to get out of the way.
+        let max_wait = Duration::minutes(2);
+        let mut waiting_since: BTreeMap<TurnRequest, Duration> = BTreeMap::new();
+        let mut num_waited = 0;
+        for _ in 0..30 * 60 {
+            sim.step(&map, Duration::seconds(1.0));
+            let mut still_waiting = BTreeMap::new();
+            for i in &stop_signs {
+                for req in sim.get_waiting_requests(*i) {
+                    if let AgentID::Pedestrian(_) = req.agent {
+                        if map.get_t(req.turn).turn_type == TurnType::Crosswalk {
+                            let since = waiting_since.get(&req).cloned().unwrap_or(sim.time());
+                            assert!(
+                                sim.time() - since <= max_wait,
+                                "{:?} has been waiting since {}",
+                                req,
+                                since
+                            );
+                            still_waiting.insert(req, since);
+                        }
+                    }
+                }
+            }
+            num_waited += waiting_since
+                .keys()
+                .filter(|req| !still_waiting.contains_key(req))
+                .count();
+            waiting_since = still_waiting;
+        }
+        // Make sure the situation actually came up.
+        assert!(num_waited > 0);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_slow("small_spawn_completes_with_actuated_signals", |h| {
+        let flags = SimFlags::for_test("small_spawn_completes_with_actuated_signals");
+        let (mut map, _, mut rng) = flags.load(&mut Timer::throwaway());
+
+        let mut edits = map.get_edits().clone();
+        for i in map.all_intersections() {
+            if let Some(signal) = map.maybe_get_traffic_signal(i.id) {
+                let mut signal = signal.clone();
+                signal.mode = SignalMode::default_actuated();
+                edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+            }
+        }
+        map.apply_edits(edits, &mut Timer::throwaway());
+        map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        // Watch every signal's phase changes
+        let signals: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| i.id)
+            .collect();
+        let mut current: BTreeMap<IntersectionID, (usize, Duration)> = BTreeMap::new();
+        let mut gapped_out = 0;
+        while !sim.is_done() {
+            assert!(sim.time() < Duration::minutes(70));
+            sim.step(&map, Duration::seconds(1.0));
+            for i in &signals {
+                let (idx, _, _) = sim.current_signal_phase(*i, &map);
+                match current.get(i).cloned() {
+                    Some((old_idx, started)) if old_idx != idx => {
+                        // Phases always last at least min_green. The signal might only notice 1s
+                        // late.
+                        let phase = &map.get_traffic_signal(*i).phases[old_idx];
+                        let lasted = sim.time() - started;
+                        assert!(lasted + Duration::seconds(1.0) >= phase.min_green);
+                        if lasted + Duration::seconds(1.0) < phase.max_green {
+                            gapped_out += 1;
+                        }
+                        current.insert(*i, (idx, sim.time()));
+                    }
+                    Some(_) => {}
+                    None => {
+                        current.insert(*i, (idx, sim.time()));
+                    }
+                }
+            }
+        }
+        // Without much traffic, phases should end early. A fixed time signal never does this.
+        println!("{} phases gapped out", gapped_out);
+        assert!(gapped_out > 0);
+    });
+
+    t.run_slow("blocked_lane_makes_drivers_reroute", |h| {
+        // First find the lane the most cars use.
+        let mut flags = SimFlags::for_test("blocked_lane_makes_drivers_reroute");
+        let (map, mut baseline, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(
+            &mut baseline,
+            &map,
+            &mut rng,
+            &mut Timer::throwaway(),
+        );
+        let entered = Rc::new(RefCell::new(BTreeMap::new()));
+        {
+            let entered = entered.clone();
+            baseline.add_event_sink(Box::new(WatchEvents(move |_, ev: &Event| {
+                if let Event::AgentEntersTraversable(AgentID::Car(_), Traversable::Lane(l)) = ev {
+                    *entered.borrow_mut().entry(*l).or_insert(0) += 1;
+                }
+            })));
+        }
+        baseline.just_run_until_done(&map, Some(Duration::minutes(70)));
+        let blocked = entered
+            .borrow()
+            .iter()
+            .filter(|(l, _)| !map.get_i(map.get_l(**l).dst_i).is_border())
+            .max_by_key(|(_, cnt)| **cnt)
+            .map(|(l, _)| *l)
+            .unwrap();
+
+        // Then nobody gets to leave that lane.
+        flags.opts.dynamic_rerouting = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.set_intersection_policy(map.get_l(blocked).dst_i, Box::new(BlockLane(blocked)));
+        let rerouted = Rc::new(RefCell::new(Vec::new()));
+        {
+            let rerouted = rerouted.clone();
+            sim.add_event_sink(Box::new(WatchEvents(move |_, ev: &Event| {
+                if let Event::CarRerouted(car) = ev {
+                    rerouted.borrow_mut().push(*car);
+                }
+            })));
+        }
+        h.setup_done(&sim);
+
+        // Cars stuck on the blocked lane never finish, so just run for a while. Check who was
+        // headed for the blocked lane before each step and who rerouted during it.
+        let uses_blocked = |sim: &Sim, car: CarID| {
+            sim.get_path(AgentID::Car(car))
+                .map(|path| {
+                    path.get_steps()
+                        .iter()
+                        .skip(1)
+                        .any(|step| step.as_traversable() == Traversable::Lane(blocked))
+                })
+                .unwrap_or(false)
+        };
+        let mut avoided = 0;
+        while sim.time() < Duration::minutes(40) {
+            let headed_for_blocked: BTreeSet<CarID> = sim
+                .active_agents()
+                .into_iter()
+                .filter_map(|id| match id {
+                    AgentID::Car(car) if uses_blocked(&sim, car) => Some(car),
+                    _ => None,
+                })
+                .collect();
+            sim.step(&map, Duration::seconds(1.0));
+            for car in rerouted.borrow_mut().drain(..) {
+                if headed_for_blocked.contains(&car) && !uses_blocked(&sim, car) {
+                    avoided += 1;
+                }
+            }
+        }
+        println!("{} drivers rerouted around {}", avoided, blocked);
+        assert!(avoided > 0);
+    });
+
+    t.run_slow("pedestrians_finish_crossing_before_signals_change", |h| {
+        let flags = SimFlags::for_test("pedestrians_finish_crossing_before_signals_change");
+        let (mut map, _, mut rng) = flags.load(&mut Timer::throwaway());
+
+        // Every phase with a crosswalk has to leave time to cross it.
+        for i in map.all_intersections() {
+            if let Some(signal) = map.maybe_get_traffic_signal(i.id) {
+                assert!(signal.clone().validate(&map).is_ok());
+                for (idx, phase) in signal.phases.iter().enumerate() {
+                    let clearance = signal.ped_clearance(phase, &map);
+                    if clearance > Duration::ZERO {
+                        let mut short = signal.clone();
+                        short.phases[idx].duration = clearance * 0.5;
+                        assert!(short.validate(&map).is_err());
+                    }
+                }
+            }
+        }
+
+        // Scenario::rand_ped_speed makes some people slower than signals normally assume, so
+        // time every crosswalk for the slowest of them. Then nobody has an excuse to get stranded.
+        let mut edits = map.get_edits().clone();
+        for i in map.all_intersections() {
+            if let Some(signal) = map.maybe_get_traffic_signal(i.id) {
+                let mut signal = signal.clone();
+                signal.walking_speed = Speed::meters_per_second(0.894);
+                let signal = signal.with_ped_clearance(&map).validate(&map).unwrap();
+                edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+            }
+        }
+        map.apply_edits(edits, &mut Timer::throwaway());
+        map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        let crossings = Rc::new(Cell::new(0));
+        let crossings_clone = crossings.clone();
+        sim.add_event_sink(Box::new(WatchEvents(move |_, ev| {
+            if let Event::PedSignalDelayMeasured(_, _) = ev {
+                crossings_clone.set(crossings_clone.get() + 1);
+            }
+        })));
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+        assert!(sim.get_analytics().stranded_peds.is_empty());
+        // Make sure the check above meant something.
+        assert!(crossings.get() > 0);
+    });
+
+    t.run_slow("cars_pass_bikes_and_bikes_use_boxes", |h| {
+        let mut flags = SimFlags::for_test("cars_pass_bikes_and_bikes_use_boxes");
+        flags.opts.pass_bikes = true;
+        let (mut map, _, mut rng) = flags.load(&mut Timer::throwaway());
+
+        // Bike boxes on every approach, and bikes get a head start wherever they have their own
+        // movements. Bikes only get their own movements when a signal opts in.
+        let mut edits = map.get_edits().clone();
+        for i in map.all_intersections() {
+            if let Some(signal) = map.maybe_get_traffic_signal(i.id) {
+                assert!(!signal.has_bike_groups());
+                let mut signal = signal.clone();
+                signal.bike_boxes = i.roads.clone();
+                signal.split_bike_groups(&map);
+                signal.add_leading_bike_intervals(Duration::seconds(5.0));
+                let signal = signal.validate(&map).unwrap();
+                edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+            }
+        }
+        map.apply_edits(edits, &mut Timer::throwaway());
+        map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        let passes = Rc::new(Cell::new(0));
+        let boxes = Rc::new(Cell::new(0));
+        let (passes_clone, boxes_clone) = (passes.clone(), boxes.clone());
+        let box_lanes: BTreeSet<LaneID> = map
+            .all_lanes()
+            .iter()
+            .filter(|l| {
+                map.maybe_get_traffic_signal(l.dst_i)
+                    .map(|ts| ts.bike_boxes.contains(&l.parent))
+                    .unwrap_or(false)
+            })
+            .map(|l| l.id)
+            .collect();
+        sim.add_event_sink(Box::new(WatchEvents(move |_, ev| match ev {
+            Event::CarPassedBike(car, bike, _) => {
+                assert_eq!(car.1, VehicleType::Car);
+                assert_eq!(bike.1, VehicleType::Bike);
+                passes_clone.set(passes_clone.get() + 1);
+            }
+            Event::BikeEnteredBikeBox(bike, l) => {
+                assert_eq!(bike.1, VehicleType::Bike);
+                assert!(box_lanes.contains(l));
+                boxes_clone.set(boxes_clone.get() + 1);
+            }
+            _ => {}
+        })));
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+        assert!(passes.get() > 0);
+        assert!(boxes.get() > 0);
+    });
+
+    t.run_slow("sidewalk_level_of_service", |h| {
+        let flags = SimFlags::for_test("sidewalk_level_of_service");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        assert_eq!(PedLOS::from_space(10.0), PedLOS::A);
+        assert_eq!(PedLOS::from_space(0.5), PedLOS::F);
+        let analytics = sim.get_analytics();
+        assert!(!analytics.sidewalk_peds.is_empty());
+        let l = *analytics.sidewalk_peds.keys().next().unwrap();
+        let grades = analytics.ped_los(sim.time(), l, Duration::minutes(15), &map);
+        assert!(!grades.is_empty());
+        for (_, peds, _) in grades {
+            assert!(peds > 0);
+        }
+        // Everybody's done walking.
+        assert_eq!(sim.current_ped_los(l, &map), (0, PedLOS::A));
+    });
+
+    t.run_slow("incidents_block_until_cleared", |h| {
+        let flags = SimFlags::for_test("incidents_block_until_cleared");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        let lane = map.all_lanes().iter().find(|l| l.is_driving()).unwrap();
+        scenario.incidents.push(Incident {
+            kind: IncidentKind::Crash,
+            blockage: Blockage::Lanes(map.get_r(lane.parent).all_lanes()),
+            start_time: Duration::seconds(30.0),
+            duration: Duration::minutes(5),
+        });
+        let i = map
+            .all_intersections()
+            .iter()
+            .find(|i| i.is_traffic_signal())
+            .unwrap();
+        scenario.incidents.push(Incident {
+            kind: IncidentKind::WorkZone,
+            blockage: Blockage::Intersection(i.id),
+            start_time: Duration::seconds(60.0),
+            duration: Duration::minutes(2),
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        let rerouted = Rc::new(Cell::new(0));
+        // When each incident started and cleared
+        let started: Rc<RefCell<Vec<(Incident, Duration)>>> = Rc::new(RefCell::new(Vec::new()));
+        let cleared: Rc<RefCell<Vec<(Incident, Duration)>>> = Rc::new(RefCell::new(Vec::new()));
+        // What each agent most recently entered, and when
+        let on: Rc<RefCell<BTreeMap<AgentID, (Traversable, Duration)>>> =
+            Rc::new(RefCell::new(BTreeMap::new()));
+        {
+            let rerouted = rerouted.clone();
+            let started = started.clone();
+            let cleared = cleared.clone();
+            let on = on.clone();
+            sim.add_event_sink(Box::new(WatchEvents(move |time, ev: &Event| match ev {
+                Event::IncidentStarted(incident) => {
+                    started.borrow_mut().push((incident.clone(), time));
+                }
+                Event::IncidentCleared(incident) => {
+                    cleared.borrow_mut().push((incident.clone(), time));
+                }
+                Event::CarReroutedAroundIncident(_) => {
+                    rerouted.set(rerouted.get() + 1);
+                }
+                Event::AgentEntersTraversable(agent, to) => {
+                    let prev = on.borrow_mut().insert(*agent, (*to, time));
+                    for (incident, start) in started.borrow().iter() {
+                        if !incident.blocks(*to)
+                            || cleared.borrow().iter().any(|(i, _)| i == incident)
+                        {
+                            continue;
+                        }
+                        // Somebody already partway through a turn into a blocked lane when the
+                        // incident started may finish it.
+                        let already_turning = match (*to, prev) {
+                            (Traversable::Lane(_), Some((Traversable::Turn(_), entered))) => {
+                                entered <= *start
+                            }
+                            _ => false,
+                        };
+                        assert!(
+                            already_turning,
+                            "{:?} entered {:?} at {} during {:?}",
+                            agent, to, time, incident
+                        );
+                    }
+                }
+                _ => {}
+            })));
+        }
+        h.setup_done(&sim);
+
+        // Before each step, note who's waiting to enter an active incident. If it clears during
+        // the step, they should move again.
+        let mut waiting_at_clear: Vec<(AgentID, Duration)> = Vec::new();
+        while sim.time() < Duration::minutes(10) {
+            let mut waiting: Vec<(Incident, AgentID)> = Vec::new();
+            for incident in sim.get_active_incidents() {
+                for i in incident.affected_intersections(&map) {
+                    for req in sim.get_waiting_requests(i) {
+                        if incident.blocks(Traversable::Turn(req.turn)) {
+                            waiting.push((incident.clone(), req.agent));
+                        }
+                    }
+                }
+            }
+            sim.step(&map, Duration::seconds(1.0));
+            for (incident, agent) in waiting {
+                if let Some((_, time)) = cleared.borrow().iter().find(|(i, _)| *i == incident) {
+                    waiting_at_clear.push((agent, *time));
+                }
+            }
+        }
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        assert_eq!(started.borrow().len(), 2);
+        assert_eq!(cleared.borrow().len(), 2);
+        assert!(sim.get_active_incidents().is_empty());
+        assert!(rerouted.get() > 0);
+        assert!(!waiting_at_clear.is_empty());
+        for (agent, time) in waiting_at_clear {
+            let entered = on.borrow().get(&agent).map(|(_, t)| *t);
+            assert!(
+                entered.map(|t| t >= time).unwrap_or(false),
+                "{:?} didn't move after an incident cleared at {}",
+                agent,
+                time
+            );
+        }
+    });
+
+    t.run_slow(
+        "small_spawn_completes_with_custom_intersection_policies",
+        |h| {
+            let flags =
+                SimFlags::for_test("small_spawn_completes_with_custom_intersection_policies");
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            let times_asked = Rc::new(Cell::new(0));
+            let arrivals: Rc<RefCell<BTreeMap<TurnRequest, Duration>>> =
+                Rc::new(RefCell::new(BTreeMap::new()));
+            for i in map.all_intersections() {
+                if map.maybe_get_stop_sign(i.id).is_some() {
+                    sim.set_intersection_policy(
+                        i.id,
+                        Box::new(RecordArrivals {
+                            arrivals: arrivals.clone(),
+                        }),
+                    );
+                } else if map.maybe_get_traffic_signal(i.id).is_some() {
+                    sim.set_intersection_policy(
+                        i.id,
+                        Box::new(Freeform {
+                            times_asked: times_asked.clone(),
+                        }),
+                    );
+                }
+            }
+            Scenario::small_run(&map).instantiate(
+                &mut sim,
+                &map,
+                &mut rng,
+                &mut Timer::throwaway(),
+            );
+
+            // When each vehicle that went through a stop sign had first asked to go, in the order
+            // they went
+            let served: Rc<RefCell<BTreeMap<IntersectionID, Vec<Duration>>>> =
+                Rc::new(RefCell::new(BTreeMap::new()));
+            let served_clone = served.clone();
+            sim.add_event_sink(Box::new(WatchEvents(move |_, ev| {
+                if let Event::AgentEntersTraversable(AgentID::Car(car), Traversable::Turn(turn)) =
+                    ev
+                {
+                    let req = TurnRequest {
+                        agent: AgentID::Car(*car),
+                        turn: *turn,
+                    };
+                    if let Some(arrived) = arrivals.borrow().get(&req) {
+                        served_clone
+                            .borrow_mut()
+                            .entry(turn.parent)
+                            .or_insert_with(Vec::new)
+                            .push(*arrived);
+                    }
+                }
+            })));
+
+            h.setup_done(&sim);
+            sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+            assert!(times_asked.get() > 0);
+
+            let served = served.borrow();
+            assert!(served.values().any(|times| times.len() > 1));
+            for (i, times) in served.iter() {
+                for pair in times.windows(2) {
+                    assert!(
+                        pair[0] <= pair[1],
+                        "At {}, somebody who arrived at {} went after somebody who arrived at {}",
+                        i,
+                        pair[0],
+                        pair[1]
+                    );
+                }
+            }
+        },
+    );
+}
+
+// Just StrictFIFOStopSign, remembering when each vehicle first asked to go
+struct RecordArrivals {
+    arrivals: Rc<RefCell<BTreeMap<TurnRequest, Duration>>>,
+}
+
+impl IntersectionPolicy for RecordArrivals {
+    fn maybe_start_turn(
+        &mut self,
+        req: &TurnRequest,
+        speed: Speed,
+        ctx: &IntersectionContext,
+    ) -> PolicyDecision {
+        self.arrivals
+            .borrow_mut()
+            .insert(req.clone(), ctx.waiting[req]);
+        StrictFIFOStopSign.maybe_start_turn(req, speed, ctx)
+    }
+}
+
+// Lets anybody go when nothing conflicts, counting how many times it was asked
+struct Freeform {
+    times_asked: Rc<Cell<usize>>,
+}
+
+impl IntersectionPolicy for Freeform {
+    fn maybe_start_turn(
+        &mut self,
+        _: &TurnRequest,
+        _: Speed,
+        _: &IntersectionContext,
+    ) -> PolicyDecision {
+        self.times_asked.set(self.times_asked.get() + 1);
+        PolicyDecision::Go
+    }
+}
+
+fn mean_trip_time(sim: &Sim, mode: TripMode) -> Duration {
+    let times: Vec<Duration> = sim
+        .get_analytics()
+        .finished_trips
+        .iter()
+        .filter(|(_, _, m, _)| *m == Some(mode))
+        .map(|(_, _, _, dt)| *dt)
+        .collect();
+    assert!(!times.is_empty());
+    let total = times.iter().fold(Duration::ZERO, |sum, dt| sum + *dt);
+    Duration::seconds(total.inner_seconds() / (times.len() as f64))
+}
+
+// Never lets anybody leave one lane
+struct BlockLane(LaneID);
+
+impl IntersectionPolicy for BlockLane {
+    fn maybe_start_turn(
+        &mut self,
+        req: &TurnRequest,
+        _: Speed,
+        _: &IntersectionContext,
+    ) -> PolicyDecision {
+        if req.turn.src == self.0 {
+            PolicyDecision::Wait
+        } else {
+            PolicyDecision::Go
+        }
+    }
+}
+
+// Calls something for every event
+struct WatchEvents<F: FnMut(Duration, &Event)>(F);
+
+impl<F: FnMut(Duration, &Event)> EventSink for WatchEvents<F> {
+    fn event(&mut self, time: Duration, ev: &Event) {
+        (self.0)(time, ev);
+    }
 }
@@ -110,4 +110,29 @@ pub fn run(t: &mut TestRunner) {
 
         std::fs::remove_file(sim1_save).unwrap();
     });
+
+    t.run_slow("event_log_with_savestating", |_| {
+        let mut flags = SimFlags::for_test("event_log_with_savestating");
+        let log_path = "../data/save/event_log_with_savestating.jsonl".to_string();
+        flags.opts.event_logs = vec![log_path.clone()];
+        let (map, mut sim1, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim1, &map, &mut rng, &mut Timer::throwaway());
+
+        sim1.step(&map, Duration::minutes(10));
+        let sim1_save = sim1.save();
+        sim1.step(&map, Duration::seconds(30.0));
+        let log1 = std::fs::read_to_string(&log_path).unwrap();
+
+        // Resuming should rewind the log and then write exactly the same events.
+        let mut sim2: Sim =
+            Sim::load_savestate(sim1_save.clone(), &mut Timer::throwaway()).unwrap();
+        sim2.step(&map, Duration::seconds(30.0));
+        let log2 = std::fs::read_to_string(&log_path).unwrap();
+        if log1 != log2 {
+            panic!("event log differs after resuming from {}", sim1_save);
+        }
+
+        std::fs::remove_file(sim1_save).unwrap();
+        std::fs::remove_file(log_path).unwrap();
+    });
 }
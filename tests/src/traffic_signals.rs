@@ -0,0 +1,98 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Duration, Speed};
+use map_model::{IntersectionID, Map, RoadID};
+use sim::SimFlags;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("green_wave_offsets_follow_travel_time", |_| {
+        let (mut map, _, _) = SimFlags::for_test("green_wave_offsets_follow_travel_time")
+            .load(&mut Timer::throwaway());
+        let corridor = find_corridor(&map);
+        let speed = Speed::miles_per_hour(25.0);
+        let edits =
+            map_model::green_wave::coordinate_signals(&map, &corridor, speed, None).unwrap();
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        // A platoon leaving the first signal when its green starts should reach every later
+        // signal just as the movement along the corridor turns green.
+        let mut arrival = Duration::ZERO;
+        for (idx, i) in corridor.iter().enumerate() {
+            let from = if idx == 0 {
+                None
+            } else {
+                let r = connecting_road(&map, corridor[idx - 1], *i);
+                arrival += map.get_r(r).center_pts.length() / speed;
+                Some(r)
+            };
+            let to = corridor
+                .get(idx + 1)
+                .map(|next| connecting_road(&map, *i, *next));
+
+            let signal = map.get_traffic_signal(*i);
+            let (_, phase, remaining) =
+                signal.current_phase_and_remaining_time(arrival + Duration::seconds(1.0));
+            println!(
+                "Platoon reaches {} at {}, and the phase has {} left",
+                i, arrival, remaining
+            );
+            assert!(phase
+                .protected_groups
+                .iter()
+                .chain(phase.yield_groups.iter())
+                .any(|g| g.crosswalk.is_none()
+                    && from.map(|r| g.from == r).unwrap_or(true)
+                    && to.map(|r| g.to == r).unwrap_or(true)));
+            let elapsed = phase.duration - remaining;
+            assert!((elapsed.inner_seconds() - 1.0).abs() < 0.1);
+        }
+    });
+}
+
+// Some traffic signals connected one after another
+fn find_corridor(map: &Map) -> Vec<IntersectionID> {
+    let is_signal = |i: IntersectionID| map.maybe_get_traffic_signal(i).is_some();
+    let mut best = Vec::new();
+    for r in map.all_roads() {
+        if !is_signal(r.src_i) || !is_signal(r.dst_i) {
+            continue;
+        }
+        let mut corridor = vec![r.src_i, r.dst_i];
+        loop {
+            let last = *corridor.last().unwrap();
+            let next = map.get_i(last).roads.iter().find_map(|r| {
+                let road = map.get_r(*r);
+                let other = if road.src_i == last {
+                    road.dst_i
+                } else {
+                    road.src_i
+                };
+                if is_signal(other) && !corridor.contains(&other) {
+                    Some(other)
+                } else {
+                    None
+                }
+            });
+            match next {
+                Some(i) if corridor.len() < 5 => corridor.push(i),
+                _ => break,
+            }
+        }
+        if corridor.len() > best.len() {
+            best = corridor;
+        }
+    }
+    assert!(best.len() >= 2, "No signals next to each other");
+    best
+}
+
+fn connecting_road(map: &Map, i1: IntersectionID, i2: IntersectionID) -> RoadID {
+    *map.get_i(i1)
+        .roads
+        .iter()
+        .find(|r| {
+            let road = map.get_r(**r);
+            (road.src_i == i1 && road.dst_i == i2) || (road.src_i == i2 && road.dst_i == i1)
+        })
+        .unwrap()
+}
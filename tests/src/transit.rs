@@ -1,6 +1,7 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::Duration;
+use map_model::Weekday;
 use sim::{Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
 
 pub fn run(t: &mut TestRunner) {
@@ -79,4 +80,127 @@ pub fn run(t: &mut TestRunner) {
             Duration::minutes(9),
         );
     });
+
+    t.run_slow("full_bus_leaves_riders_behind", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("full_bus_leaves_riders_behind").load(&mut Timer::throwaway());
+        // Only one passenger fits on each bus.
+        let mut route = map.get_bus_route("49").unwrap().clone();
+        route.capacity = Some(1);
+        let buses = sim.seed_bus_route(&route, &map, &mut Timer::throwaway());
+        let bus = buses[0];
+        let ped_stop1 = route.stops[1];
+        let ped_stop2 = route.stops[2];
+        let start_bldg = *map
+            .get_l(map.get_bs(ped_stop1).sidewalk_pos.lane())
+            .building_paths
+            .last()
+            .unwrap();
+        let goal_bldg = map
+            .get_l(map.get_bs(ped_stop2).sidewalk_pos.lane())
+            .building_paths[0];
+        // Two people wait at the same stop, so the first bus can't take both.
+        let ped_speed = Scenario::rand_ped_speed(&mut rng);
+        for _ in 0..2 {
+            sim.schedule_trip(
+                Duration::ZERO,
+                TripSpec::UsingTransit {
+                    start: SidewalkSpot::building(start_bldg, &map),
+                    route: route.id,
+                    stop1: ped_stop1,
+                    stop2: ped_stop2,
+                    goal: SidewalkSpot::building(goal_bldg, &map),
+                    ped_speed,
+                },
+                &map,
+            )
+            .0
+            .unwrap();
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::BusArrivedAtStop(bus, route.id, ped_stop1),
+                Event::BusDepartedFromStop(bus, route.id, ped_stop1),
+            ],
+            Duration::minutes(9),
+        );
+
+        let analytics = sim.get_analytics();
+        assert!(
+            analytics
+                .bus_passups
+                .iter()
+                .any(|(_, r, stop)| *r == route.id && *stop == ped_stop1),
+            "Nobody was left behind at {}",
+            ped_stop1
+        );
+        let (_, _, _, _, passengers, capacity) = analytics
+            .bus_loads
+            .iter()
+            .find(|(_, b, _, stop, _, _)| *b == bus && *stop == ped_stop1)
+            .unwrap();
+        assert_eq!(*passengers, 1);
+        assert_eq!(*capacity, 1);
+    });
+
+    t.run_slow("scheduled_bus_follows_timetable", |h| {
+        let mut flags = SimFlags::for_test("scheduled_bus_follows_timetable");
+        flags.opts.service_day = Weekday::Monday;
+        let (map, mut sim, _) = flags.load(&mut Timer::throwaway());
+        let route = map
+            .get_all_bus_routes()
+            .iter()
+            .find(|r| {
+                r.timetable
+                    .iter()
+                    .any(|trip| trip.days.contains(&Weekday::Monday))
+            })
+            .expect("No bus route has a timetable for Monday");
+        let buses = sim.schedule_bus_route(route, &map, &mut Timer::throwaway());
+        let bus = buses[0];
+        let trip = route
+            .timetable
+            .iter()
+            .find(|trip| trip.days.contains(&Weekday::Monday))
+            .unwrap();
+        h.setup_done(&sim);
+
+        let mut expectations: Vec<Event> = Vec::new();
+        for (idx, stop) in trip.stops.iter().enumerate() {
+            expectations.push(Event::BusArrivedAtStop(bus, route.id, stop.stop));
+            expectations.push(Event::ScheduledBusArrivedAtStop(
+                bus,
+                route.id,
+                stop.stop,
+                stop.arrival,
+            ));
+            // The bus vanishes at the last stop.
+            if idx != trip.stops.len() - 1 {
+                expectations.push(Event::BusDepartedFromStop(bus, route.id, stop.stop));
+            }
+        }
+
+        let last_arrival = trip.stops.last().unwrap().arrival;
+        sim.run_until_expectations_met(&map, expectations, last_arrival + Duration::minutes(30));
+
+        // The bus shows up early enough to start on time.
+        let first_stop = &trip.stops[0];
+        let (arrived, _, _, _, _) = sim
+            .get_analytics()
+            .scheduled_bus_arrivals
+            .iter()
+            .find(|(_, b, _, stop, _)| *b == bus && *stop == first_stop.stop)
+            .unwrap();
+        assert!(
+            *arrived <= first_stop.arrival,
+            "{} reached the first stop at {}, but was scheduled for {}",
+            bus,
+            arrived,
+            first_stop.arrival
+        );
+    });
 }
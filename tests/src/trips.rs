@@ -1,8 +1,9 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::Duration;
-use map_model::{BuildingID, IntersectionID};
+use map_model::{BuildingID, IntersectionID, LaneType, PathConstraints, PathRequest, Position};
 use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use std::collections::BTreeMap;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bike_from_border", |h| {
@@ -37,4 +38,91 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+    t.run_slow("drive_to_work_and_back", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("drive_to_work_and_back").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let home = BuildingID(319);
+        let work = BuildingID(90);
+        let parking = map
+            .find_closest_lane(map.get_b(home).sidewalk(), vec![LaneType::Parking])
+            .unwrap();
+        h.seed_parked_cars(&mut sim, &mut rng, parking, Some(home), vec![0]);
+        // Both trips want to leave immediately, but the drive home has to wait. Nothing is parked
+        // near work, so the trip home only works if the same car is used.
+        sim.schedule_person(
+            vec![
+                (
+                    Duration::ZERO,
+                    TripSpec::MaybeUsingParkedCar {
+                        start_bldg: home,
+                        goal: DrivingGoal::ParkNear(work),
+                        ped_speed: Scenario::rand_ped_speed(&mut rng),
+                    },
+                ),
+                (
+                    Duration::ZERO,
+                    TripSpec::MaybeUsingParkedCar {
+                        start_bldg: work,
+                        goal: DrivingGoal::ParkNear(home),
+                        ped_speed: Scenario::rand_ped_speed(&mut rng),
+                    },
+                ),
+            ],
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&sim);
+
+        sim.just_run_until_done(&map, Some(Duration::minutes(20)));
+        let results = sim.get_finished_trips();
+        assert_eq!(results.aborted_trips, 0);
+        assert_eq!(results.finished_trips.len(), 2);
+    });
+
+    t.run_slow("cars_follow_assigned_routes", |h| {
+        let flags = SimFlags::for_test("cars_follow_assigned_routes");
+        // First see which routes drivers pick on their own.
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        sim.record_car_routes();
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        // Then send a few of them a different way.
+        let mut assigned = BTreeMap::new();
+        for (trip, (_, start, path)) in sim.get_car_routes() {
+            let req = PathRequest {
+                start: *start,
+                end: Position::new(path.last_step().as_lane(), path.end_dist()),
+                constraints: PathConstraints::Car,
+            };
+            if let Some(alt) = map.pathfind_alternatives(req, 2).into_iter().nth(1) {
+                assert_ne!(&alt.path, path);
+                assigned.insert(*trip, alt.path);
+            }
+            if assigned.len() == 5 {
+                break;
+            }
+        }
+        assert!(!assigned.is_empty(), "No driver has another way to go");
+
+        // Load them like headless --dta saves them.
+        let mut flags = flags.clone();
+        flags.car_routes = Some(h.output_path("car_routes.bin"));
+        abstutil::write_binary(flags.car_routes.as_ref().unwrap(), &assigned).unwrap();
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        sim.record_car_routes();
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        let routes = sim.get_car_routes();
+        for (trip, path) in &assigned {
+            assert_eq!(
+                &routes[trip].2, path,
+                "{} didn't follow its assigned route",
+                trip
+            );
+        }
+    });
 }